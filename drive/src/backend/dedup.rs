@@ -0,0 +1,619 @@
+//! A backend storing the contents of files once however many files hold
+//! them.
+//!
+//! The contents of files are kept in a content-addressed blob store, named
+//! after their SHA-256 hash, and files are pointers to the blob holding their
+//! contents:
+//!
+//! ```text
+//! [Blob]
+//! Hash=2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824
+//! Size=5
+//! Key=3kTMd8VxQe0cBn6WfJr1Lz9pYa2GhUo7
+//! ```
+//!
+//! The blob store lives in `.mibox/blobs/` of the backend it was created
+//! with, which scoped backends keep sharing, so identical files are stored
+//! once across every drive. Each blob `<hash>` is kept in the
+//! `.mibox/blobs/<first two hex digits>/` directory next to a
+//! `<hash>.blobinfo` record counting the pointers referencing it, and is
+//! removed along with the last of them.
+//!
+//! The record also holds the random key of the blob, which pointers must
+//! hold to be taken for one. Pointers are never read back as they are, so
+//! files that were not written by the backend cannot point to the blobs of
+//! others however they look.
+//!
+//! Files of at most `INLINE_LENGTH` bytes are not worth a blob and are
+//! stored as they are, unless they could be taken for a pointer. Files
+//! written before the backend was deduplicated are read as they are too.
+use super::{Backend, ByteStream, StorageBackend, SymlinkPolicy};
+use crate::{
+    entry::Metadata,
+    hash::HashingReader,
+    reserved::{format_record, parse_record, time_id},
+    RESERVED_DIR,
+};
+use rand::{distributions::Alphanumeric, Rng};
+use std::{
+    future::Future,
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::Mutex,
+};
+use tokio_stream::StreamExt;
+
+const BLOBS_DIR: &str = "blobs";
+
+/// Where blobs are written to before their hash is known.
+const STAGING_DIR: &str = "staging";
+
+const INFO_EXTENSION: &str = "blobinfo";
+
+const INFO_HEADER: &str = "[Blob Info]";
+
+const POINTER_HEADER: &str = "[Blob]";
+
+/// The largest file stored as is rather than as a blob, pointers are smaller
+/// still so that larger files are never read to be told apart from them.
+pub const INLINE_LENGTH: u64 = 256;
+
+/// How old staged blobs must be to be removed by `collect_garbage`, younger
+/// ones may belong to a write in progress.
+const STAGING_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Tells apart the staged blobs of the writes started within the same
+/// nanosecond.
+static STAGING_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// The length of the keys of blobs.
+const KEY_LENGTH: usize = 32;
+
+/// The blob a file points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pointer {
+    hash: String,
+    size: u64,
+    /// The key of the blob, see `BlobInfo`.
+    key: String,
+}
+
+impl Pointer {
+    fn format(&self) -> String {
+        format_record(
+            POINTER_HEADER,
+            &[
+                ("Hash", &self.hash),
+                ("Size", &self.size.to_string()),
+                ("Key", &self.key),
+            ],
+        )
+    }
+
+    /// What `content` would point to, `None` if it does not look like a
+    /// pointer. Whether the blob has the same key is left to be checked.
+    fn parse(content: &[u8]) -> Option<Self> {
+        let fields = parse_record(POINTER_HEADER, std::str::from_utf8(content).ok()?)?;
+        let hash = fields.get("Hash").filter(|hash| is_hash(hash))?;
+        Some(Self {
+            hash: hash.to_owned(),
+            size: fields.get("Size")?.parse().ok()?,
+            key: fields.get("Key").cloned().unwrap_or_default(),
+        })
+    }
+}
+
+/// The record of a blob.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BlobInfo {
+    /// The number of pointers referencing the blob.
+    references: u64,
+    /// The key pointers to the blob hold, drawn at random when the blob is
+    /// stored.
+    key: String,
+}
+
+impl BlobInfo {
+    fn new() -> Self {
+        Self {
+            references: 1,
+            key: rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(KEY_LENGTH)
+                .map(char::from)
+                .collect(),
+        }
+    }
+
+    fn format(&self) -> String {
+        format_record(
+            INFO_HEADER,
+            &[
+                ("References", &self.references.to_string()),
+                ("Key", &self.key),
+            ],
+        )
+    }
+
+    fn parse(record: &[u8]) -> Option<Self> {
+        let fields = parse_record(INFO_HEADER, std::str::from_utf8(record).ok()?)?;
+        let key = fields.get("Key").filter(|key| key.len() == KEY_LENGTH)?;
+        Some(Self {
+            references: fields.get("References")?.parse().ok()?,
+            key: key.to_owned(),
+        })
+    }
+}
+
+/// Whether `hash` is a SHA-256 hash in lowercase hexadecimal.
+fn is_hash(hash: &str) -> bool {
+    hash.len() == 64
+        && hash
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+}
+
+fn blobs_dir() -> PathBuf {
+    Path::new(RESERVED_DIR).join(BLOBS_DIR)
+}
+
+fn blob_path(hash: &str) -> PathBuf {
+    blobs_dir().join(&hash[..2]).join(hash)
+}
+
+fn info_path(hash: &str) -> PathBuf {
+    blobs_dir()
+        .join(&hash[..2])
+        .join(format!("{}.{}", hash, INFO_EXTENSION))
+}
+
+/// Backend deduplicating the contents of the files of `T` (see the module
+/// documentation).
+///
+/// The reference counts are only kept consistent between the clones of a
+/// backend, a blob store must not be shared by several processes.
+#[derive(Debug, Clone)]
+pub struct DedupBackend<T> {
+    files: T,
+    blobs: T,
+    /// Serializes the updates of the reference counts.
+    lock: Arc<Mutex<()>>,
+}
+
+impl<T: StorageBackend + Clone> DedupBackend<T> {
+    /// Deduplicates the files of `backend`, keeping blobs in its
+    /// `.mibox/blobs/` directory.
+    pub fn new(backend: T) -> Self {
+        Self {
+            files: backend.clone(),
+            blobs: backend,
+            lock: Arc::default(),
+        }
+    }
+}
+
+impl<T> DedupBackend<T> {
+    /// The backend the files are kept in.
+    pub fn files(&self) -> &T {
+        &self.files
+    }
+}
+
+impl DedupBackend<Backend> {
+    /// A backend rooted at the `dir` directory of this one which shares its
+    /// blob store.
+    pub fn scoped(&self, dir: impl AsRef<Path>) -> Self {
+        Self {
+            files: self.files.scoped(dir),
+            blobs: self.blobs.clone(),
+            lock: self.lock.clone(),
+        }
+    }
+
+    /// Sets the symlink policy of the underlying backend.
+    pub fn with_symlink_policy(self, symlink_policy: SymlinkPolicy) -> Self {
+        Self {
+            files: self.files.with_symlink_policy(symlink_policy),
+            blobs: self.blobs.with_symlink_policy(symlink_policy),
+            lock: self.lock,
+        }
+    }
+}
+
+/// A boxed future of a deduplicated [`Backend`].
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send + 'a>>;
+
+/// The operations of a deduplicated [`Backend`] behind boxed futures, which
+/// [`Backend`] dispatches to as its own futures cannot contain themselves.
+impl DedupBackend<Backend> {
+    pub(super) fn boxed_resolve<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, PathBuf> {
+        Box::pin(self.resolve(path))
+    }
+
+    pub(super) fn boxed_list<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxFuture<'a, Vec<(PathBuf, Metadata)>> {
+        Box::pin(self.list(path))
+    }
+
+    pub(super) fn boxed_stat<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Option<Metadata>> {
+        Box::pin(self.stat(path))
+    }
+
+    pub(super) fn boxed_read<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, ByteStream> {
+        Box::pin(self.read(path))
+    }
+
+    pub(super) fn boxed_read_range<'a>(
+        &'a self,
+        path: &'a Path,
+        range: Range<u64>,
+    ) -> BoxFuture<'a, ByteStream> {
+        Box::pin(self.read_range(path, range))
+    }
+
+    pub(super) fn boxed_write<'a, R: AsyncRead + Send + Unpin + 'a>(
+        &'a self,
+        path: &'a Path,
+        reader: R,
+    ) -> BoxFuture<'a, ()> {
+        Box::pin(self.write(path, reader))
+    }
+
+    pub(super) fn boxed_rename<'a>(&'a self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, ()> {
+        Box::pin(self.rename(from, to))
+    }
+
+    pub(super) fn boxed_remove_file<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, ()> {
+        Box::pin(self.remove_file(path))
+    }
+
+    pub(super) fn boxed_remove_dir<'a>(
+        &'a self,
+        path: &'a Path,
+        recursive: bool,
+    ) -> BoxFuture<'a, ()> {
+        Box::pin(self.remove_dir(path, recursive))
+    }
+
+    pub(super) fn boxed_mkdir<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, ()> {
+        Box::pin(self.mkdir(path))
+    }
+}
+
+impl<T: StorageBackend> DedupBackend<T> {
+    /// Creates `path` of the blob store and the directories leading to it
+    /// when missing.
+    async fn create_blobs_dir(&self, path: &Path) -> io::Result<()> {
+        let mut ancestors = path.ancestors().collect::<Vec<_>>();
+        ancestors.reverse();
+        for ancestor in ancestors {
+            if ancestor.as_os_str().is_empty() || self.blobs.stat(ancestor).await?.is_some() {
+                continue;
+            }
+            self.blobs.mkdir(ancestor).await?;
+        }
+        Ok(())
+    }
+
+    /// The pointer held by the file at `path` of the files, `None` if it is
+    /// not a pointer or does not hold the key of its blob.
+    async fn pointer(&self, path: &Path, metadata: &Metadata) -> io::Result<Option<Pointer>> {
+        if metadata.is_directory() || metadata.size() > INLINE_LENGTH {
+            return Ok(None);
+        }
+        let mut stream = self.files.read(path).await?;
+        let mut content = vec![];
+        while let Some(chunk) = stream.next().await {
+            content.extend_from_slice(&chunk?);
+        }
+        let Some(pointer) = Pointer::parse(&content) else {
+            return Ok(None);
+        };
+        let holds_key = self
+            .info(&pointer.hash)
+            .await?
+            .is_some_and(|info| info.key == pointer.key);
+        Ok(holds_key.then_some(pointer))
+    }
+
+    /// The pointer held by the file at `path`, if it exists and holds one.
+    async fn pointer_at(&self, path: &Path) -> io::Result<Option<Pointer>> {
+        match self.files.stat(path).await? {
+            Some(metadata) => self.pointer(path, &metadata).await,
+            None => Ok(None),
+        }
+    }
+
+    /// `metadata` of the file at `path` with the size of the blob it points
+    /// to, if any.
+    async fn resolve_metadata(&self, path: &Path, metadata: Metadata) -> io::Result<Metadata> {
+        Ok(match self.pointer(path, &metadata).await? {
+            Some(pointer) => metadata.with_size(pointer.size),
+            None => metadata,
+        })
+    }
+
+    /// The record of the blob `hash`, `None` when it has none.
+    async fn info(&self, hash: &str) -> io::Result<Option<BlobInfo>> {
+        let path = info_path(hash);
+        if self.blobs.stat(&path).await?.is_none() {
+            return Ok(None);
+        }
+        let mut stream = self.blobs.read(&path).await?;
+        let mut record = vec![];
+        while let Some(chunk) = stream.next().await {
+            record.extend_from_slice(&chunk?);
+        }
+        Ok(BlobInfo::parse(&record))
+    }
+
+    async fn set_info(&self, hash: &str, info: &BlobInfo) -> io::Result<()> {
+        self.blobs
+            .write(&info_path(hash), info.format().as_bytes())
+            .await
+    }
+
+    /// Drops a reference to the blob `hash`, removing it along with the last
+    /// one. Must be called with the lock held.
+    async fn release(&self, hash: &str) -> io::Result<()> {
+        match self.info(hash).await? {
+            Some(info) if info.references > 1 => {
+                let references = info.references - 1;
+                self.set_info(hash, &BlobInfo { references, ..info }).await
+            }
+            _ => {
+                if self.blobs.stat(&blob_path(hash)).await?.is_some() {
+                    self.blobs.remove_file(&blob_path(hash)).await?;
+                }
+                if self.blobs.stat(&info_path(hash)).await?.is_some() {
+                    self.blobs.remove_file(&info_path(hash)).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the contents of `reader` to the blob store, returning the
+    /// pointer to their blob with a reference taken on it.
+    async fn store<R: AsyncRead + Send + Unpin>(&self, reader: R) -> io::Result<Pointer> {
+        let staging = blobs_dir().join(STAGING_DIR);
+        self.create_blobs_dir(&staging).await?;
+        let staged = staging.join(format!(
+            "{}-{}",
+            time_id(),
+            STAGING_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut reader = HashingReader::new(reader);
+        // Written as a trait object, otherwise the readers handed down nested
+        // deduplicated backends would nest without end.
+        let erased: &mut (dyn AsyncRead + Send + Unpin) = &mut reader;
+        if let Err(e) = self.blobs.write(&staged, erased).await {
+            let _ = self.blobs.remove_file(&staged).await;
+            return Err(e);
+        }
+        let size = reader.size();
+        let hash = reader.finish();
+
+        let _lock = self.lock.lock().await;
+        let stored = self.blobs.stat(&blob_path(&hash)).await?.is_some();
+        let info = match self.info(&hash).await? {
+            Some(info) if stored => BlobInfo {
+                references: info.references + 1,
+                ..info
+            },
+            _ => BlobInfo::new(),
+        };
+        if stored {
+            self.blobs.remove_file(&staged).await?;
+        } else {
+            self.create_blobs_dir(blob_path(&hash).parent().unwrap_or(&staging))
+                .await?;
+            self.blobs.rename(&staged, &blob_path(&hash)).await?;
+        }
+        self.set_info(&hash, &info).await?;
+        Ok(Pointer {
+            hash,
+            size,
+            key: info.key,
+        })
+    }
+
+    /// The hashes of the blobs referenced by the files within `path`, once
+    /// per file.
+    async fn referenced(&self, path: &Path) -> io::Result<Vec<String>> {
+        let mut hashes = vec![];
+        let mut pending = vec![path.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for (path, metadata) in self.files.list(&dir).await? {
+                if metadata.is_directory() {
+                    if path != blobs_dir() {
+                        pending.push(path);
+                    }
+                } else if let Some(pointer) = self.pointer(&path, &metadata).await? {
+                    hashes.push(pointer.hash);
+                }
+            }
+        }
+        Ok(hashes)
+    }
+
+    /// Recounts the references to every blob and removes the blobs no
+    /// longer referenced, returning how many were removed.
+    ///
+    /// Blobs are removed along with their last reference, this repairs the
+    /// counts left behind by writes that were interrupted. It walks every
+    /// file of the backend, which must be the one the blob store was created
+    /// with, and holds up writes meanwhile.
+    pub async fn collect_garbage(&self) -> io::Result<usize> {
+        let _lock = self.lock.lock().await;
+        if self.blobs.stat(&blobs_dir()).await?.is_none() {
+            return Ok(0);
+        }
+        let mut counts = std::collections::HashMap::<String, u64>::new();
+        for hash in self.referenced(Path::new("")).await? {
+            *counts.entry(hash).or_default() += 1;
+        }
+
+        let mut removed = 0;
+        for (shard, metadata) in self.blobs.list(&blobs_dir()).await? {
+            if !metadata.is_directory() {
+                continue;
+            }
+            if shard == blobs_dir().join(STAGING_DIR) {
+                let expired = SystemTime::now() - STAGING_TTL;
+                for (staged, metadata) in self.blobs.list(&shard).await? {
+                    if metadata
+                        .modified()
+                        .is_some_and(|modified| modified < expired)
+                    {
+                        self.blobs.remove_file(&staged).await?;
+                    }
+                }
+                continue;
+            }
+            for (path, _) in self.blobs.list(&shard).await? {
+                let Some(hash) = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .filter(|name| is_hash(name))
+                else {
+                    continue;
+                };
+                match (counts.get(hash), self.info(hash).await?) {
+                    (Some(&references), Some(info)) => {
+                        if info.references != references {
+                            self.set_info(hash, &BlobInfo { references, ..info })
+                                .await?;
+                        }
+                    }
+                    (Some(_), None) => {}
+                    (None, _) => {
+                        self.release(hash).await?;
+                        removed += 1;
+                    }
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
+
+impl<T: StorageBackend> StorageBackend for DedupBackend<T> {
+    async fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        self.files.resolve(path).await
+    }
+
+    async fn list(&self, path: &Path) -> io::Result<Vec<(PathBuf, Metadata)>> {
+        let mut entries = vec![];
+        for (path, metadata) in self.files.list(path).await? {
+            let metadata = self.resolve_metadata(&path, metadata).await?;
+            entries.push((path, metadata));
+        }
+        Ok(entries)
+    }
+
+    async fn stat(&self, path: &Path) -> io::Result<Option<Metadata>> {
+        match self.files.stat(path).await? {
+            Some(metadata) => Ok(Some(self.resolve_metadata(path, metadata).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn read(&self, path: &Path) -> io::Result<ByteStream> {
+        match self.pointer_at(path).await? {
+            Some(pointer) => self.blobs.read(&blob_path(&pointer.hash)).await,
+            None => self.files.read(path).await,
+        }
+    }
+
+    async fn read_range(&self, path: &Path, range: Range<u64>) -> io::Result<ByteStream> {
+        match self.pointer_at(path).await? {
+            Some(pointer) => {
+                self.blobs
+                    .read_range(&blob_path(&pointer.hash), range)
+                    .await
+            }
+            None => self.files.read_range(path, range).await,
+        }
+    }
+
+    async fn write<R: AsyncRead + Send + Unpin>(&self, path: &Path, reader: R) -> io::Result<()> {
+        // Reads one byte past the inline length to tell whether the contents
+        // fit.
+        let mut head = vec![];
+        let mut reader = reader;
+        (&mut reader)
+            .take(INLINE_LENGTH + 1)
+            .read_to_end(&mut head)
+            .await?;
+        let pointer = if head.len() as u64 <= INLINE_LENGTH && Pointer::parse(&head).is_none() {
+            None
+        } else {
+            Some(self.store(head.as_slice().chain(reader)).await?)
+        };
+
+        // The file is replaced with the lock held so that concurrent writes
+        // release what the other replaced only once.
+        let _lock = self.lock.lock().await;
+        let previous = self.pointer_at(path).await?;
+        let written = match &pointer {
+            Some(pointer) => self.files.write(path, pointer.format().as_bytes()).await,
+            None => self.files.write(path, head.as_slice()).await,
+        };
+        match (written, &pointer, previous) {
+            (Err(e), Some(pointer), _) => {
+                self.release(&pointer.hash).await?;
+                Err(e)
+            }
+            (Err(e), None, _) => Err(e),
+            (Ok(()), _, Some(previous)) => self.release(&previous.hash).await,
+            (Ok(()), _, None) => Ok(()),
+        }
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.files.rename(from, to).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> io::Result<()> {
+        // The lock is held from reading the pointer to releasing its blob so
+        // that a concurrent write to the path cannot release it as well.
+        let _lock = self.lock.lock().await;
+        let pointer = self.pointer_at(path).await?;
+        self.files.remove_file(path).await?;
+        if let Some(pointer) = pointer {
+            self.release(&pointer.hash).await?;
+        }
+        Ok(())
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> io::Result<()> {
+        let _lock = self.lock.lock().await;
+        let referenced = if recursive {
+            self.referenced(path).await?
+        } else {
+            vec![]
+        };
+        self.files.remove_dir(path, recursive).await?;
+        for hash in referenced {
+            self.release(&hash).await?;
+        }
+        Ok(())
+    }
+
+    async fn mkdir(&self, path: &Path) -> io::Result<()> {
+        self.files.mkdir(path).await
+    }
+}
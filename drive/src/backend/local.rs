@@ -0,0 +1,258 @@
+use super::{ByteStream, StorageBackend};
+use crate::entry::Metadata;
+use std::{
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+
+/// Prefix of the temporary files writes go through, they are hidden from
+/// listings.
+const TEMPORARY_PREFIX: &str = ".mibox-write-";
+
+/// How symbolic links found inside the drive are treated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Any path going through a symbolic link is rejected.
+    Deny,
+    /// Symbolic links are followed as long as they resolve inside the drive.
+    #[default]
+    FollowWithinBase,
+    /// Symbolic links are followed wherever they point to.
+    FollowAll,
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "deny" => Ok(Self::Deny),
+            "follow-within-base" => Ok(Self::FollowWithinBase),
+            "follow-all" => Ok(Self::FollowAll),
+            other => Err(format!(
+                "{} is not a supported symlink policy. Use either `deny`, `follow-within-base` or `follow-all`.",
+                other
+            )),
+        }
+    }
+}
+
+/// Backend that stores the drive in a directory of the local filesystem.
+#[derive(Debug, Clone)]
+pub struct LocalBackend {
+    base: PathBuf,
+    symlink_policy: SymlinkPolicy,
+}
+
+impl LocalBackend {
+    pub fn new(base: impl AsRef<Path>) -> Self {
+        let base = base.as_ref().to_path_buf();
+        Self {
+            base,
+            symlink_policy: SymlinkPolicy::default(),
+        }
+    }
+
+    pub fn with_symlink_policy(mut self, symlink_policy: SymlinkPolicy) -> Self {
+        self.symlink_policy = symlink_policy;
+        self
+    }
+
+    /// A backend rooted at the `dir` directory of this one, with the same
+    /// symlink policy.
+    pub fn scoped(&self, dir: impl AsRef<Path>) -> Self {
+        Self {
+            base: self.base.join(dir),
+            symlink_policy: self.symlink_policy,
+        }
+    }
+
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    pub fn symlink_policy(&self) -> SymlinkPolicy {
+        self.symlink_policy
+    }
+
+    /// A path next to `path` that no other write uses.
+    fn temporary_path(path: &Path) -> PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        path.with_file_name(format!(
+            "{}{}-{}-{}",
+            TEMPORARY_PREFIX,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+            name
+        ))
+    }
+
+    /// Writes the contents of the reader into a new file and flushes it to
+    /// the disk.
+    async fn write_synced<R: AsyncRead + Send + Unpin>(
+        path: &Path,
+        mut reader: R,
+    ) -> io::Result<()> {
+        let file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .await?;
+        let mut writer = tokio::io::BufWriter::new(file);
+        tokio::io::copy(&mut reader, &mut writer).await?;
+        writer.flush().await?;
+        writer.into_inner().sync_all().await
+    }
+
+    fn escapes(path: &Path, reason: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{:?} {}", path, reason),
+        )
+    }
+
+    /// Reads the metadata of an entry, symbolic links are reported as such
+    /// together with the metadata of their target when it exists.
+    async fn metadata(path: &Path) -> io::Result<Metadata> {
+        let link = tokio::fs::symlink_metadata(path).await?;
+        if !link.is_symlink() {
+            return Ok(link.into());
+        }
+        match tokio::fs::metadata(path).await {
+            Ok(target) => Ok(Metadata::from(target).with_symlink(true)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(link.into()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl StorageBackend for LocalBackend {
+    /// Walks the path from the base of the drive applying the symlink policy
+    /// to every link found on the way, links are canonicalized so that chains
+    /// of links and links to `..` are caught as well.
+    ///
+    /// Links are not resolved when all of them are followed, as they may lead
+    /// anywhere.
+    async fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        if self.symlink_policy == SymlinkPolicy::FollowAll {
+            return Ok(path.to_path_buf());
+        }
+        let base = match tokio::fs::canonicalize(&self.base).await {
+            Ok(base) => base,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(path.to_path_buf()),
+            Err(e) => return Err(e),
+        };
+        let mut current = base.clone();
+        let mut components = path.components();
+        while let Some(component) = components.next() {
+            let candidate = current.join(component);
+            let metadata = match tokio::fs::symlink_metadata(&candidate).await {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    current = candidate.join(components.as_path());
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            if !metadata.is_symlink() {
+                current = candidate;
+                continue;
+            }
+            if self.symlink_policy == SymlinkPolicy::Deny {
+                return Err(Self::escapes(path, "goes through a symbolic link"));
+            }
+            current = match tokio::fs::canonicalize(&candidate).await {
+                Ok(target) if target.starts_with(&base) => target,
+                Ok(_) => return Err(Self::escapes(path, "resolves outside of the drive")),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    return Err(Self::escapes(path, "goes through a dangling symbolic link"))
+                }
+                Err(e) => return Err(e),
+            };
+        }
+        Ok(current
+            .strip_prefix(&base)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| path.to_path_buf()))
+    }
+
+    async fn list(&self, path: &Path) -> io::Result<Vec<(PathBuf, Metadata)>> {
+        let mut entries = vec![];
+        let mut directory = tokio::fs::read_dir(self.base.join(path)).await?;
+        while let Some(read_dir_entry) = directory.next_entry().await? {
+            if read_dir_entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(TEMPORARY_PREFIX)
+            {
+                continue;
+            }
+            let metadata = Self::metadata(&read_dir_entry.path()).await?;
+            entries.push((path.join(read_dir_entry.file_name()), metadata));
+        }
+        Ok(entries)
+    }
+
+    async fn stat(&self, path: &Path) -> io::Result<Option<Metadata>> {
+        match Self::metadata(&self.base.join(path)).await {
+            Ok(metadata) => Ok(Some(metadata)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn read(&self, path: &Path) -> io::Result<ByteStream> {
+        let file = tokio::fs::File::open(self.base.join(path)).await?;
+        Ok(Box::pin(ReaderStream::new(file)))
+    }
+
+    async fn read_range(&self, path: &Path, range: Range<u64>) -> io::Result<ByteStream> {
+        let mut file = tokio::fs::File::open(self.base.join(path)).await?;
+        file.seek(io::SeekFrom::Start(range.start)).await?;
+        Ok(Box::pin(ReaderStream::new(
+            file.take(range.end - range.start),
+        )))
+    }
+
+    /// Writes go through a temporary file next to the destination which is
+    /// renamed into place once complete, so readers never see a partial file
+    /// and a failed write leaves the destination untouched.
+    async fn write<R: AsyncRead + Send + Unpin>(&self, path: &Path, reader: R) -> io::Result<()> {
+        let destination = self.base.join(path);
+        let temporary = Self::temporary_path(&destination);
+        let written = match Self::write_synced(&temporary, reader).await {
+            Ok(()) => tokio::fs::rename(&temporary, &destination).await,
+            Err(e) => Err(e),
+        };
+        if written.is_err() {
+            let _ = tokio::fs::remove_file(&temporary).await;
+        }
+        written
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        tokio::fs::rename(self.base.join(from), self.base.join(to)).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> io::Result<()> {
+        tokio::fs::remove_file(self.base.join(path)).await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> io::Result<()> {
+        if recursive {
+            tokio::fs::remove_dir_all(self.base.join(path)).await
+        } else {
+            tokio::fs::remove_dir(self.base.join(path)).await
+        }
+    }
+
+    async fn mkdir(&self, path: &Path) -> io::Result<()> {
+        tokio::fs::create_dir(self.base.join(path)).await
+    }
+}
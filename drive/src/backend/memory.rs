@@ -0,0 +1,291 @@
+use super::{ByteStream, StorageBackend};
+use crate::entry::Metadata;
+use bytes::Bytes;
+use std::{
+    collections::BTreeMap,
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_stream::StreamExt;
+
+#[derive(Debug, Clone)]
+enum Content {
+    Directory,
+    File(Bytes),
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    content: Content,
+    created: SystemTime,
+    modified: SystemTime,
+}
+
+impl Node {
+    fn new(content: Content) -> Self {
+        let now = SystemTime::now();
+        Self {
+            content,
+            created: now,
+            modified: now,
+        }
+    }
+}
+
+/// Backend that keeps the whole drive in memory.
+///
+/// Clones share the same storage, the contents are lost once the last clone
+/// is dropped.
+#[derive(Debug, Clone)]
+pub struct MemoryBackend {
+    nodes: Arc<RwLock<BTreeMap<PathBuf, Node>>>,
+    root: PathBuf,
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(PathBuf::new(), Node::new(Content::Directory));
+        Self {
+            nodes: Arc::new(RwLock::new(nodes)),
+            root: PathBuf::new(),
+        }
+    }
+
+    /// A backend sharing the storage of this one but rooted at its `dir`
+    /// directory, which must exist.
+    pub fn scoped(&self, dir: impl AsRef<Path>) -> Self {
+        Self {
+            nodes: self.nodes.clone(),
+            root: self.root.join(dir),
+        }
+    }
+
+    /// The key of `path` in the shared storage.
+    fn key(&self, path: &Path) -> PathBuf {
+        if path.as_os_str().is_empty() {
+            self.root.clone()
+        } else {
+            self.root.join(path)
+        }
+    }
+
+    fn not_found(path: &Path) -> io::Error {
+        io::Error::new(io::ErrorKind::NotFound, format!("{:?} not found", path))
+    }
+
+    /// Checks that the parent of `path` exists and is a directory.
+    fn parent_exists(nodes: &BTreeMap<PathBuf, Node>, path: &Path) -> io::Result<()> {
+        let parent = path
+            .parent()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the root has no parent"))?;
+        match nodes.get(parent).map(|node| &node.content) {
+            Some(Content::Directory) => Ok(()),
+            Some(Content::File(_)) => Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("{:?} is not a directory", parent),
+            )),
+            None => Err(Self::not_found(parent)),
+        }
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    async fn list(&self, path: &Path) -> io::Result<Vec<(PathBuf, Metadata)>> {
+        let path = &self.key(path);
+        let nodes = self.nodes.read().expect("memory backend lock poisoned");
+        match nodes.get(path).map(|node| &node.content) {
+            Some(Content::Directory) => {}
+            Some(Content::File(_)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotADirectory,
+                    format!("{:?} is not a directory", path),
+                ))
+            }
+            None => return Err(Self::not_found(path)),
+        }
+        Ok(nodes
+            .iter()
+            .filter(|(child, _)| child.parent() == Some(path))
+            .map(|(child, node)| {
+                let child = child
+                    .strip_prefix(&self.root)
+                    .expect("child is under the root");
+                (child.to_path_buf(), node.into())
+            })
+            .collect())
+    }
+
+    async fn stat(&self, path: &Path) -> io::Result<Option<Metadata>> {
+        let path = &self.key(path);
+        let nodes = self.nodes.read().expect("memory backend lock poisoned");
+        Ok(nodes.get(path).map(Metadata::from))
+    }
+
+    async fn read(&self, path: &Path) -> io::Result<ByteStream> {
+        let path = &self.key(path);
+        let nodes = self.nodes.read().expect("memory backend lock poisoned");
+        match nodes.get(path).map(|node| &node.content) {
+            Some(Content::File(content)) => Ok(Box::pin(tokio_stream::once(Ok(content.clone())))),
+            Some(Content::Directory) => Err(io::Error::new(
+                io::ErrorKind::IsADirectory,
+                format!("{:?} is a directory", path),
+            )),
+            None => Err(Self::not_found(path)),
+        }
+    }
+
+    async fn read_range(&self, path: &Path, range: Range<u64>) -> io::Result<ByteStream> {
+        let content = self
+            .read(path)
+            .await?
+            .next()
+            .await
+            .unwrap_or(Ok(Bytes::new()))?;
+        let end = (range.end as usize).min(content.len());
+        let start = (range.start as usize).min(end);
+        Ok(Box::pin(tokio_stream::once(Ok(content.slice(start..end)))))
+    }
+
+    async fn write<R: AsyncRead + Send + Unpin>(
+        &self,
+        path: &Path,
+        mut reader: R,
+    ) -> io::Result<()> {
+        let path = &self.key(path);
+        let mut content = vec![];
+        reader.read_to_end(&mut content).await?;
+        let mut nodes = self.nodes.write().expect("memory backend lock poisoned");
+        Self::parent_exists(&nodes, path)?;
+        match nodes.get_mut(path) {
+            Some(Node {
+                content: Content::Directory,
+                ..
+            }) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::IsADirectory,
+                    format!("{:?} is a directory", path),
+                ))
+            }
+            Some(node) => {
+                node.content = Content::File(content.into());
+                node.modified = SystemTime::now();
+            }
+            None => {
+                nodes.insert(path.to_path_buf(), Node::new(Content::File(content.into())));
+            }
+        }
+        Ok(())
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let from = &self.key(from);
+        let to = &self.key(to);
+        let mut nodes = self.nodes.write().expect("memory backend lock poisoned");
+        if !nodes.contains_key(from) {
+            return Err(Self::not_found(from));
+        }
+        if to.starts_with(from) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot move {:?} into itself", from),
+            ));
+        }
+        Self::parent_exists(&nodes, to)?;
+        let moved = nodes
+            .keys()
+            .filter(|path| path.starts_with(from))
+            .cloned()
+            .collect::<Vec<_>>();
+        for path in moved {
+            let node = nodes.remove(&path).expect("node listed above");
+            let suffix = path.strip_prefix(from).expect("node is under `from`");
+            nodes.insert(to.join(suffix), node);
+        }
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let path = &self.key(path);
+        let mut nodes = self.nodes.write().expect("memory backend lock poisoned");
+        match nodes.get(path).map(|node| &node.content) {
+            Some(Content::File(_)) => {
+                nodes.remove(path);
+                Ok(())
+            }
+            Some(Content::Directory) => Err(io::Error::new(
+                io::ErrorKind::IsADirectory,
+                format!("{:?} is a directory", path),
+            )),
+            None => Err(Self::not_found(path)),
+        }
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> io::Result<()> {
+        let path = &self.key(path);
+        let mut nodes = self.nodes.write().expect("memory backend lock poisoned");
+        match nodes.get(path).map(|node| &node.content) {
+            Some(Content::Directory) => {}
+            Some(Content::File(_)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotADirectory,
+                    format!("{:?} is not a directory", path),
+                ))
+            }
+            None => return Err(Self::not_found(path)),
+        }
+        let removed = nodes
+            .keys()
+            .filter(|child| child.starts_with(path) && child.as_path() != path)
+            .cloned()
+            .collect::<Vec<_>>();
+        if !recursive && !removed.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::DirectoryNotEmpty,
+                format!("{:?} is not empty", path),
+            ));
+        }
+        for child in removed {
+            nodes.remove(&child);
+        }
+        nodes.remove(path);
+        Ok(())
+    }
+
+    async fn mkdir(&self, path: &Path) -> io::Result<()> {
+        let path = &self.key(path);
+        let mut nodes = self.nodes.write().expect("memory backend lock poisoned");
+        Self::parent_exists(&nodes, path)?;
+        if nodes.contains_key(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{:?} already exists", path),
+            ));
+        }
+        nodes.insert(path.to_path_buf(), Node::new(Content::Directory));
+        Ok(())
+    }
+}
+
+impl From<&Node> for Metadata {
+    fn from(node: &Node) -> Self {
+        let size = match &node.content {
+            Content::Directory => 0,
+            Content::File(content) => content.len() as u64,
+        };
+        Metadata::new(matches!(node.content, Content::Directory))
+            .with_size(size)
+            .with_modified(Some(node.modified))
+            .with_created(Some(node.created))
+    }
+}
@@ -0,0 +1,257 @@
+use crate::entry::Metadata;
+use std::{
+    future::Future,
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+    pin::Pin,
+    str::FromStr,
+};
+use tokio::io::AsyncRead;
+mod dedup;
+pub use dedup::*;
+mod local;
+pub use local::*;
+mod memory;
+pub use memory::*;
+mod s3;
+pub use s3::*;
+
+/// Stream of bytes produced by a backend when reading an entry.
+pub type ByteStream = Pin<Box<dyn futures_core::Stream<Item = io::Result<bytes::Bytes>> + Send>>;
+
+/// The storage operations a [`crate::Drive`] relies on.
+///
+/// Every path handed to a backend is relative to the root of the drive and
+/// has already been validated by the drive (see `Drive::entry_valid`), so
+/// backends only need to map it onto their own storage.
+pub trait StorageBackend: Send + Sync {
+    /// Returns the path a path designates once resolved by the storage,
+    /// relative to the root of the drive, and fails with
+    /// [`io::ErrorKind::PermissionDenied`] when it leaves the drive.
+    ///
+    /// Only storages with a notion of links need to resolve anything.
+    fn resolve(&self, path: &Path) -> impl Future<Output = io::Result<PathBuf>> + Send {
+        let path = path.to_path_buf();
+        async { Ok(path) }
+    }
+
+    /// Lists the direct children of a directory as `(path, metadata)` pairs,
+    /// where `path` is relative to the root of the drive.
+    fn list(
+        &self,
+        path: &Path,
+    ) -> impl Future<Output = io::Result<Vec<(PathBuf, Metadata)>>> + Send;
+
+    /// Returns the metadata of an entry or `None` if it does not exist.
+    fn stat(&self, path: &Path) -> impl Future<Output = io::Result<Option<Metadata>>> + Send;
+
+    /// Opens a file for reading.
+    fn read(&self, path: &Path) -> impl Future<Output = io::Result<ByteStream>> + Send;
+
+    /// Opens a file for reading the bytes within `range`, which the caller
+    /// guarantees to lie within the file.
+    fn read_range(
+        &self,
+        path: &Path,
+        range: Range<u64>,
+    ) -> impl Future<Output = io::Result<ByteStream>> + Send;
+
+    /// Writes the contents of the reader into a file, replacing it if it exists.
+    fn write<R: AsyncRead + Send + Unpin>(
+        &self,
+        path: &Path,
+        reader: R,
+    ) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Renames an entry.
+    fn rename(&self, from: &Path, to: &Path) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Removes a file.
+    fn remove_file(&self, path: &Path) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Removes a directory, including its contents when `recursive` is set.
+    fn remove_dir(
+        &self,
+        path: &Path,
+        recursive: bool,
+    ) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Creates a directory.
+    fn mkdir(&self, path: &Path) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+/// A backend selected at runtime.
+///
+/// It can be parsed from a drive url: `memory://` selects a [`MemoryBackend`],
+/// `s3://bucket/prefix` selects a [`S3Backend`] and `file:///some/path` or a
+/// plain path selects a [`LocalBackend`]. Any of them can then be
+/// deduplicated (see `Backend::deduplicated`).
+#[derive(Debug, Clone)]
+pub enum Backend {
+    Local(LocalBackend),
+    Memory(MemoryBackend),
+    S3(S3Backend),
+    Dedup(Box<DedupBackend<Backend>>),
+}
+
+impl Backend {
+    /// Creates the backend described by `url`, `s3` is only used by `s3://` urls.
+    pub fn from_url(url: &str, s3: &S3Options) -> Result<Self, String> {
+        match url.split_once("://") {
+            None => Ok(Self::Local(LocalBackend::new(url))),
+            Some(("file", path)) => Ok(Self::Local(LocalBackend::new(path))),
+            Some(("memory", _)) => Ok(Self::Memory(MemoryBackend::new())),
+            Some(("s3", _)) => Ok(Self::S3(S3Backend::from_url(url, s3)?)),
+            Some((other, _)) => Err(format!(
+                "{} is not a supported drive scheme. Use either `file`, `memory` or `s3`.",
+                other
+            )),
+        }
+    }
+
+    /// Sets the symlink policy of a local backend, other backends have no
+    /// symbolic links.
+    pub fn with_symlink_policy(self, symlink_policy: SymlinkPolicy) -> Self {
+        match self {
+            Backend::Local(backend) => Backend::Local(backend.with_symlink_policy(symlink_policy)),
+            Backend::Dedup(backend) => {
+                Backend::Dedup(Box::new(backend.with_symlink_policy(symlink_policy)))
+            }
+            backend => backend,
+        }
+    }
+
+    /// Stores the contents of files once however many files hold them, in a
+    /// blob store kept in `.mibox/blobs/` of this backend (see
+    /// [`DedupBackend`]).
+    pub fn deduplicated(self) -> Self {
+        match self {
+            Backend::Dedup(backend) => Backend::Dedup(backend),
+            backend => Backend::Dedup(Box::new(DedupBackend::new(backend))),
+        }
+    }
+
+    /// The directory the files are kept in when they are kept on the local
+    /// file system, `None` otherwise.
+    pub fn local_base(&self) -> Option<&Path> {
+        match self {
+            Backend::Local(backend) => Some(backend.base()),
+            Backend::Dedup(backend) => backend.files().local_base(),
+            Backend::Memory(_) | Backend::S3(_) => None,
+        }
+    }
+
+    /// A backend rooted at the `dir` directory of this one, the paths it is
+    /// given cannot reach outside of `dir`.
+    ///
+    /// The directory must exist for the local and memory backends.
+    pub fn scoped(&self, dir: impl AsRef<Path>) -> Self {
+        match self {
+            Backend::Local(backend) => Backend::Local(backend.scoped(dir)),
+            Backend::Memory(backend) => Backend::Memory(backend.scoped(dir)),
+            Backend::S3(backend) => Backend::S3(backend.scoped(dir)),
+            Backend::Dedup(backend) => Backend::Dedup(Box::new(backend.scoped(dir))),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_url(s, &S3Options::default())
+    }
+}
+
+impl StorageBackend for Backend {
+    async fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        match self {
+            Backend::Local(backend) => backend.resolve(path).await,
+            Backend::Memory(backend) => backend.resolve(path).await,
+            Backend::S3(backend) => backend.resolve(path).await,
+            Backend::Dedup(backend) => backend.boxed_resolve(path).await,
+        }
+    }
+
+    async fn list(&self, path: &Path) -> io::Result<Vec<(PathBuf, Metadata)>> {
+        match self {
+            Backend::Local(backend) => backend.list(path).await,
+            Backend::Memory(backend) => backend.list(path).await,
+            Backend::S3(backend) => backend.list(path).await,
+            Backend::Dedup(backend) => backend.boxed_list(path).await,
+        }
+    }
+
+    async fn stat(&self, path: &Path) -> io::Result<Option<Metadata>> {
+        match self {
+            Backend::Local(backend) => backend.stat(path).await,
+            Backend::Memory(backend) => backend.stat(path).await,
+            Backend::S3(backend) => backend.stat(path).await,
+            Backend::Dedup(backend) => backend.boxed_stat(path).await,
+        }
+    }
+
+    async fn read(&self, path: &Path) -> io::Result<ByteStream> {
+        match self {
+            Backend::Local(backend) => backend.read(path).await,
+            Backend::Memory(backend) => backend.read(path).await,
+            Backend::S3(backend) => backend.read(path).await,
+            Backend::Dedup(backend) => backend.boxed_read(path).await,
+        }
+    }
+
+    async fn read_range(&self, path: &Path, range: Range<u64>) -> io::Result<ByteStream> {
+        match self {
+            Backend::Local(backend) => backend.read_range(path, range).await,
+            Backend::Memory(backend) => backend.read_range(path, range).await,
+            Backend::S3(backend) => backend.read_range(path, range).await,
+            Backend::Dedup(backend) => backend.boxed_read_range(path, range).await,
+        }
+    }
+
+    async fn write<R: AsyncRead + Send + Unpin>(&self, path: &Path, reader: R) -> io::Result<()> {
+        match self {
+            Backend::Local(backend) => backend.write(path, reader).await,
+            Backend::Memory(backend) => backend.write(path, reader).await,
+            Backend::S3(backend) => backend.write(path, reader).await,
+            Backend::Dedup(backend) => backend.boxed_write(path, reader).await,
+        }
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match self {
+            Backend::Local(backend) => backend.rename(from, to).await,
+            Backend::Memory(backend) => backend.rename(from, to).await,
+            Backend::S3(backend) => backend.rename(from, to).await,
+            Backend::Dedup(backend) => backend.boxed_rename(from, to).await,
+        }
+    }
+
+    async fn remove_file(&self, path: &Path) -> io::Result<()> {
+        match self {
+            Backend::Local(backend) => backend.remove_file(path).await,
+            Backend::Memory(backend) => backend.remove_file(path).await,
+            Backend::S3(backend) => backend.remove_file(path).await,
+            Backend::Dedup(backend) => backend.boxed_remove_file(path).await,
+        }
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> io::Result<()> {
+        match self {
+            Backend::Local(backend) => backend.remove_dir(path, recursive).await,
+            Backend::Memory(backend) => backend.remove_dir(path, recursive).await,
+            Backend::S3(backend) => backend.remove_dir(path, recursive).await,
+            Backend::Dedup(backend) => backend.boxed_remove_dir(path, recursive).await,
+        }
+    }
+
+    async fn mkdir(&self, path: &Path) -> io::Result<()> {
+        match self {
+            Backend::Local(backend) => backend.mkdir(path).await,
+            Backend::Memory(backend) => backend.mkdir(path).await,
+            Backend::S3(backend) => backend.mkdir(path).await,
+            Backend::Dedup(backend) => backend.boxed_mkdir(path).await,
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use super::{ByteStream, StorageBackend};
+use crate::entry::Metadata;
+use object_store::{
+    aws::AmazonS3Builder, path::Path as ObjectPath, prefix::PrefixStore, GetOptions, ObjectMeta,
+    ObjectStore,
+};
+use percent_encoding::percent_decode_str;
+use std::{
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::io::{AsyncRead, AsyncWriteExt};
+use tokio_stream::StreamExt;
+
+/// Name of the empty object that marks a directory, object stores have no
+/// notion of directories so an empty directory would otherwise vanish.
+const DIRECTORY_MARKER: &str = ".directory";
+
+/// Connection options of an S3 compatible object store.
+///
+/// Options that are not set fall back to the standard `AWS_*` environment
+/// variables.
+#[derive(Debug, Clone, Default)]
+pub struct S3Options {
+    pub region: Option<String>,
+    pub endpoint: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub allow_http: bool,
+}
+
+/// Backend that stores the drive in an S3 compatible bucket.
+///
+/// Directories are mapped onto key prefixes and listings onto delimited
+/// prefix listings.
+#[derive(Debug, Clone)]
+pub struct S3Backend {
+    store: Arc<dyn ObjectStore>,
+}
+
+impl S3Backend {
+    /// Creates a backend storing the drive under `prefix` in `bucket`.
+    pub fn new(bucket: &str, prefix: &str, options: &S3Options) -> Result<Self, String> {
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .with_allow_http(options.allow_http);
+        if let Some(region) = &options.region {
+            builder = builder.with_region(region);
+        }
+        if let Some(endpoint) = &options.endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        if let Some(access_key_id) = &options.access_key_id {
+            builder = builder.with_access_key_id(access_key_id);
+        }
+        if let Some(secret_access_key) = &options.secret_access_key {
+            builder = builder.with_secret_access_key(secret_access_key);
+        }
+        let store = builder.build().map_err(|e| e.to_string())?;
+        let store: Arc<dyn ObjectStore> = if prefix.is_empty() {
+            Arc::new(store)
+        } else {
+            Arc::new(PrefixStore::new(store, prefix))
+        };
+        Ok(Self { store })
+    }
+
+    /// Creates a backend from a `s3://bucket/prefix` url.
+    pub fn from_url(url: &str, options: &S3Options) -> Result<Self, String> {
+        let location = url
+            .strip_prefix("s3://")
+            .ok_or_else(|| format!("{} is not a s3 url", url))?;
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        if bucket.is_empty() {
+            return Err(format!("{} has no bucket", url));
+        }
+        Self::new(bucket, prefix.trim_matches('/'), options)
+    }
+
+    /// A backend storing the drive below the `dir` prefix of this one.
+    pub fn scoped(&self, dir: impl AsRef<Path>) -> Self {
+        let store: Arc<dyn ObjectStore> = Arc::new(PrefixStore::new(
+            self.store.clone(),
+            Self::object_path(dir.as_ref()),
+        ));
+        Self { store }
+    }
+
+    fn object_path(path: &Path) -> ObjectPath {
+        ObjectPath::from_iter(path.iter().map(|part| part.to_string_lossy().into_owned()))
+    }
+
+    fn marker_path(path: &Path) -> ObjectPath {
+        Self::object_path(path).child(DIRECTORY_MARKER)
+    }
+
+    fn entry_path(path: &Path, location: &ObjectPath) -> Option<PathBuf> {
+        let name = location.filename()?;
+        let name = percent_decode_str(name).decode_utf8_lossy();
+        Some(path.join(name.as_ref()))
+    }
+
+    fn file_metadata(meta: &ObjectMeta) -> Metadata {
+        Metadata::new(false)
+            .with_size(meta.size as u64)
+            .with_modified(Some(meta.last_modified.into()))
+    }
+
+    fn io_error(error: object_store::Error) -> io::Error {
+        match error {
+            object_store::Error::NotFound { .. } => io::Error::new(io::ErrorKind::NotFound, error),
+            object_store::Error::AlreadyExists { .. } => {
+                io::Error::new(io::ErrorKind::AlreadyExists, error)
+            }
+            error => io::Error::other(error),
+        }
+    }
+
+    /// Lists every object stored below `path`.
+    async fn objects(&self, path: &Path) -> io::Result<Vec<ObjectPath>> {
+        let prefix = Self::object_path(path);
+        self.store
+            .list(Some(&prefix))
+            .map(|meta| meta.map(|meta| meta.location))
+            .collect::<Result<Vec<_>, _>>()
+            .await
+            .map_err(Self::io_error)
+    }
+
+    /// Checks that the parent of `path` exists and is a directory.
+    async fn parent_exists(&self, path: &Path) -> io::Result<()> {
+        let parent = path.parent().unwrap_or(Path::new(""));
+        match self.stat(parent).await? {
+            Some(metadata) if metadata.is_directory() => Ok(()),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("{:?} is not a directory", parent),
+            )),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{:?} not found", parent),
+            )),
+        }
+    }
+}
+
+impl StorageBackend for S3Backend {
+    async fn list(&self, path: &Path) -> io::Result<Vec<(PathBuf, Metadata)>> {
+        let prefix = Self::object_path(path);
+        let prefix = (!path.as_os_str().is_empty()).then_some(&prefix);
+        let listing = self
+            .store
+            .list_with_delimiter(prefix)
+            .await
+            .map_err(Self::io_error)?;
+        let directories = listing
+            .common_prefixes
+            .iter()
+            .filter_map(|location| Self::entry_path(path, location))
+            .map(|path| (path, Metadata::new(true)));
+        let files = listing
+            .objects
+            .iter()
+            .filter(|meta| meta.location.filename() != Some(DIRECTORY_MARKER))
+            .filter_map(|meta| {
+                Self::entry_path(path, &meta.location).map(|path| (path, Self::file_metadata(meta)))
+            });
+        Ok(directories.chain(files).collect())
+    }
+
+    async fn stat(&self, path: &Path) -> io::Result<Option<Metadata>> {
+        if path.as_os_str().is_empty() {
+            return Ok(Some(Metadata::new(true)));
+        }
+        match self.store.head(&Self::object_path(path)).await {
+            Ok(meta) => return Ok(Some(Self::file_metadata(&meta))),
+            Err(object_store::Error::NotFound { .. }) => {}
+            Err(e) => return Err(Self::io_error(e)),
+        }
+        let listing = self
+            .store
+            .list_with_delimiter(Some(&Self::object_path(path)))
+            .await
+            .map_err(Self::io_error)?;
+        if listing.objects.is_empty() && listing.common_prefixes.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Metadata::new(true)))
+    }
+
+    async fn read(&self, path: &Path) -> io::Result<ByteStream> {
+        let result = self
+            .store
+            .get(&Self::object_path(path))
+            .await
+            .map_err(Self::io_error)?;
+        Ok(Box::pin(
+            result
+                .into_stream()
+                .map(|chunk| chunk.map_err(Self::io_error)),
+        ))
+    }
+
+    async fn read_range(&self, path: &Path, range: Range<u64>) -> io::Result<ByteStream> {
+        let options = GetOptions {
+            range: Some((range.start as usize..range.end as usize).into()),
+            ..Default::default()
+        };
+        let result = self
+            .store
+            .get_opts(&Self::object_path(path), options)
+            .await
+            .map_err(Self::io_error)?;
+        Ok(Box::pin(
+            result
+                .into_stream()
+                .map(|chunk| chunk.map_err(Self::io_error)),
+        ))
+    }
+
+    async fn write<R: AsyncRead + Send + Unpin>(
+        &self,
+        path: &Path,
+        mut reader: R,
+    ) -> io::Result<()> {
+        self.parent_exists(path).await?;
+        let location = Self::object_path(path);
+        let (id, mut writer) = self
+            .store
+            .put_multipart(&location)
+            .await
+            .map_err(Self::io_error)?;
+        let upload = async {
+            tokio::io::copy(&mut reader, &mut writer).await?;
+            writer.shutdown().await
+        };
+        if let Err(e) = upload.await {
+            let _ = self.store.abort_multipart(&location, &id).await;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let source = Self::object_path(from);
+        if self.store.head(&source).await.is_ok() {
+            return self
+                .store
+                .rename(&source, &Self::object_path(to))
+                .await
+                .map_err(Self::io_error);
+        }
+        let destination = Self::object_path(to);
+        for location in self.objects(from).await? {
+            let suffix = location
+                .prefix_match(&source)
+                .expect("object is listed under the source prefix");
+            let target = suffix.fold(destination.clone(), |target, part| target.child(part));
+            self.store
+                .rename(&location, &target)
+                .await
+                .map_err(Self::io_error)?;
+        }
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.store
+            .delete(&Self::object_path(path))
+            .await
+            .map_err(Self::io_error)
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> io::Result<()> {
+        let objects = self.objects(path).await?;
+        let marker = Self::marker_path(path);
+        if !recursive && objects.iter().any(|location| *location != marker) {
+            return Err(io::Error::new(
+                io::ErrorKind::DirectoryNotEmpty,
+                format!("{:?} is not empty", path),
+            ));
+        }
+        for location in objects {
+            self.store.delete(&location).await.map_err(Self::io_error)?;
+        }
+        Ok(())
+    }
+
+    async fn mkdir(&self, path: &Path) -> io::Result<()> {
+        self.parent_exists(path).await?;
+        self.store
+            .put(&Self::marker_path(path), bytes::Bytes::new())
+            .await
+            .map_err(Self::io_error)?;
+        Ok(())
+    }
+}
@@ -1,4 +1,133 @@
-use std::{fs::Metadata, path::PathBuf};
+use std::{path::PathBuf, time::SystemTime};
+
+/// Backend independent metadata of an entry.
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    is_directory: bool,
+    is_symlink: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+    created: Option<SystemTime>,
+    permissions: Option<u32>,
+    inode: Option<u64>,
+    hash: Option<String>,
+    mime_type: Option<String>,
+}
+
+impl Metadata {
+    pub fn new(is_directory: bool) -> Self {
+        Self {
+            is_directory,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_symlink(mut self, is_symlink: bool) -> Self {
+        self.is_symlink = is_symlink;
+        self
+    }
+
+    pub fn with_size(mut self, size: u64) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_modified(mut self, modified: Option<SystemTime>) -> Self {
+        self.modified = modified;
+        self
+    }
+
+    pub fn with_created(mut self, created: Option<SystemTime>) -> Self {
+        self.created = created;
+        self
+    }
+
+    pub fn with_permissions(mut self, permissions: Option<u32>) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    pub fn with_inode(mut self, inode: Option<u64>) -> Self {
+        self.inode = inode;
+        self
+    }
+
+    pub fn with_hash(mut self, hash: Option<String>) -> Self {
+        self.hash = hash;
+        self
+    }
+
+    pub fn with_mime_type(mut self, mime_type: Option<String>) -> Self {
+        self.mime_type = mime_type;
+        self
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.is_directory
+    }
+
+    /// Whether the entry is a symbolic link, the remaining metadata describes
+    /// the target of the link.
+    pub fn is_symlink(&self) -> bool {
+        self.is_symlink
+    }
+
+    /// Size in bytes, zero for directories.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    pub fn created(&self) -> Option<SystemTime> {
+        self.created
+    }
+
+    /// Unix permission bits, if the backend has such a notion.
+    pub fn permissions(&self) -> Option<u32> {
+        self.permissions
+    }
+
+    /// Inode number, if the backend has such a notion.
+    pub fn inode(&self) -> Option<u64> {
+        self.inode
+    }
+
+    /// SHA-256 hash of the contents in lowercase hexadecimal, only known to
+    /// indexed drives (see the index module).
+    pub fn hash(&self) -> Option<&str> {
+        self.hash.as_deref()
+    }
+
+    /// MIME type detected when the file was indexed.
+    pub fn mime_type(&self) -> Option<&str> {
+        self.mime_type.as_deref()
+    }
+}
+
+impl From<std::fs::Metadata> for Metadata {
+    fn from(metadata: std::fs::Metadata) -> Self {
+        #[cfg(unix)]
+        let (permissions, inode) = {
+            use std::os::unix::fs::{MetadataExt, PermissionsExt};
+            (
+                Some(metadata.permissions().mode() & 0o7777),
+                Some(metadata.ino()),
+            )
+        };
+        #[cfg(not(unix))]
+        let (permissions, inode) = (None, None);
+        Self::new(metadata.is_dir())
+            .with_symlink(metadata.is_symlink())
+            .with_size(if metadata.is_dir() { 0 } else { metadata.len() })
+            .with_modified(metadata.modified().ok())
+            .with_created(metadata.created().ok())
+            .with_permissions(permissions)
+            .with_inode(inode)
+    }
+}
 
 #[derive(Debug)]
 pub struct Entry {
@@ -12,7 +141,7 @@ impl Entry {
     }
     pub fn is_directory(&self) -> bool {
         match self.metadata {
-            Some(ref metadata) => metadata.is_dir(),
+            Some(ref metadata) => metadata.is_directory(),
             None => false,
         }
     }
@@ -27,4 +156,51 @@ impl Entry {
             .and_then(|m| m.to_str())
             .map(|m| m.to_string())
     }
+
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.metadata.as_ref().is_some_and(Metadata::is_symlink)
+    }
+
+    pub fn size(&self) -> u64 {
+        self.metadata.as_ref().map_or(0, Metadata::size)
+    }
+
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.metadata.as_ref().and_then(Metadata::modified)
+    }
+
+    pub fn created(&self) -> Option<SystemTime> {
+        self.metadata.as_ref().and_then(Metadata::created)
+    }
+
+    pub fn permissions(&self) -> Option<u32> {
+        self.metadata.as_ref().and_then(Metadata::permissions)
+    }
+
+    pub fn inode(&self) -> Option<u64> {
+        self.metadata.as_ref().and_then(Metadata::inode)
+    }
+
+    pub fn hash(&self) -> Option<&str> {
+        self.metadata.as_ref().and_then(Metadata::hash)
+    }
+
+    /// The MIME type detected when the entry was indexed, otherwise the one
+    /// registered for its extension. Directories and files with an unknown
+    /// extension have none.
+    ///
+    /// See `Drive::mime_type` to sniff the type from the content as well.
+    pub fn mime_type(&self) -> Option<String> {
+        if self.is_directory() {
+            return None;
+        }
+        match self.metadata.as_ref().and_then(Metadata::mime_type) {
+            Some(mime_type) => Some(mime_type.to_owned()),
+            None => crate::mime::from_extension(&self.path),
+        }
+    }
 }
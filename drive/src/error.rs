@@ -8,6 +8,10 @@ pub enum DriveError {
     EntryExists(String),
     #[error("{0}")]
     EntryNotFound(String),
+    #[error("{0}")]
+    EntryNotEmpty(String),
+    #[error("{0}")]
+    EntryRangeNotSatisfiable(String),
     #[error("error retrieving metadata")]
     EntryMetadata(#[source] std::io::Error),
     #[error("error performing entry rename operation")]
@@ -16,6 +20,12 @@ pub enum DriveError {
     EntryWalk(#[source] std::io::Error),
     #[error("error performing entry create operation")]
     EntryCreate(#[source] std::io::Error),
+    #[error("error performing entry remove operation")]
+    EntryRemove(#[source] std::io::Error),
+    #[error("error performing entry write operation")]
+    EntryWrite(#[source] std::io::Error),
+    #[error("error performing entry index operation")]
+    EntryIndex(#[source] std::io::Error),
 }
 
 fn error_chain_fmt(
@@ -0,0 +1,52 @@
+//! SHA-256 hashing of the contents of files as they are written.
+use sha2::{Digest, Sha256};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Hashes the bytes read through it.
+pub(crate) struct HashingReader<R> {
+    reader: R,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<R> HashingReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    /// The number of bytes read so far.
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The hash of the bytes read, in lowercase hexadecimal.
+    pub(crate) fn finish(self) -> String {
+        format!("{:x}", self.hasher.finalize())
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HashingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut self.reader).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = &poll {
+            let read = &buf.filled()[filled..];
+            self.hasher.update(read);
+            self.size += read.len() as u64;
+        }
+        poll
+    }
+}
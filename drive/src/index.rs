@@ -0,0 +1,516 @@
+//! An index of the metadata of the entries of drives, kept in SQLite.
+//!
+//! A drive with an index (see `Drive::with_index`) records there every entry
+//! it creates, writes, renames or removes, along with the SHA-256 hash and
+//! the MIME type of files, and serves `Drive::stat` and `Drive::entries` from
+//! it rather than from its backend. Entries changed behind the back of the
+//! drive are only seen once the index is rebuilt (see `Drive::rebuild_index`).
+//!
+//! A database can index several drives, e.g. the home drives of the users,
+//! each under the name of its owner (see `Index::scoped`). Paths are recorded
+//! relative to the root of their drive.
+use crate::{
+    backend::StorageBackend,
+    entry::{Entry, Metadata},
+    error::DriveError,
+    hash::HashingReader,
+    search::{EntryKind, SearchQuery},
+    Drive, Result, RESERVED_DIR,
+};
+use rusqlite::{params, params_from_iter, types::Value, Connection, OptionalExtension, Row};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::pin;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS entries (
+    owner TEXT NOT NULL,
+    path TEXT NOT NULL,
+    parent TEXT NOT NULL,
+    is_directory INTEGER NOT NULL,
+    is_symlink INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    modified INTEGER,
+    created INTEGER,
+    permissions INTEGER,
+    inode INTEGER,
+    hash TEXT,
+    mime_type TEXT,
+    PRIMARY KEY (owner, path)
+);
+CREATE INDEX IF NOT EXISTS entries_by_parent ON entries (owner, parent);
+";
+
+const COLUMNS: &str = "path, is_directory, is_symlink, size, modified, created, permissions, \
+                       inode, hash, mime_type";
+
+/// Matches the entry `?2` of the owner `?1` and the entries within it.
+const SUBTREE: &str = "owner = ?1 AND (path = ?2 OR substr(path, 1, length(?2) + 1) = ?2 || '/')";
+
+/// The path as recorded, its components joined by `/`.
+fn key(path: &Path) -> String {
+    path.iter()
+        .map(|component| component.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn parent_key(path: &Path) -> String {
+    key(path.parent().unwrap_or(Path::new("")))
+}
+
+/// A time as recorded, in nanoseconds since the Unix epoch.
+fn to_nanos(time: Option<SystemTime>) -> Option<i64> {
+    let nanos = time?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+    i64::try_from(nanos).ok()
+}
+
+fn from_nanos(nanos: Option<i64>) -> Option<SystemTime> {
+    Some(UNIX_EPOCH + Duration::from_nanos(u64::try_from(nanos?).ok()?))
+}
+
+fn index_error(error: rusqlite::Error) -> io::Error {
+    io::Error::other(error)
+}
+
+/// The entry held by a row selected with `COLUMNS`.
+fn from_row(row: &Row) -> rusqlite::Result<(PathBuf, Metadata)> {
+    let metadata = Metadata::new(row.get(1)?)
+        .with_symlink(row.get(2)?)
+        .with_size(row.get::<_, i64>(3)? as u64)
+        .with_modified(from_nanos(row.get(4)?))
+        .with_created(from_nanos(row.get(5)?))
+        .with_permissions(row.get(6)?)
+        .with_inode(row.get::<_, Option<i64>>(7)?.map(|inode| inode as u64))
+        .with_hash(row.get(8)?)
+        .with_mime_type(row.get(9)?);
+    Ok((PathBuf::from(row.get::<_, String>(0)?), metadata))
+}
+
+fn insert(
+    connection: &Connection,
+    owner: &str,
+    path: &Path,
+    metadata: &Metadata,
+) -> rusqlite::Result<()> {
+    connection.execute(
+        &format!(
+            "INSERT OR REPLACE INTO entries (owner, parent, {}) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            COLUMNS
+        ),
+        params![
+            owner,
+            parent_key(path),
+            key(path),
+            metadata.is_directory(),
+            metadata.is_symlink(),
+            metadata.size() as i64,
+            to_nanos(metadata.modified()),
+            to_nanos(metadata.created()),
+            metadata.permissions(),
+            metadata.inode().map(|inode| inode as i64),
+            metadata.hash(),
+            metadata.mime_type(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Index of the metadata of the entries of the drive owned by `owner`.
+///
+/// Clones and scoped indexes share their connection to the database.
+#[derive(Debug, Clone)]
+pub struct Index {
+    connection: Arc<Mutex<Connection>>,
+    owner: String,
+}
+
+impl Index {
+    /// Opens the database at `path`, creating it when missing.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let connection = Connection::open(path).map_err(index_error)?;
+        connection
+            .pragma_update(None, "journal_mode", "WAL")
+            .map_err(index_error)?;
+        Self::with_connection(connection)
+    }
+
+    /// An index kept in memory, lost once the last of its clones is dropped.
+    pub fn open_in_memory() -> io::Result<Self> {
+        Self::with_connection(Connection::open_in_memory().map_err(index_error)?)
+    }
+
+    fn with_connection(connection: Connection) -> io::Result<Self> {
+        connection.execute_batch(SCHEMA).map_err(index_error)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            owner: String::new(),
+        })
+    }
+
+    /// The index of the drive owned by `owner`, in the same database.
+    pub fn scoped(&self, owner: impl Into<String>) -> Self {
+        Self {
+            connection: self.connection.clone(),
+            owner: owner.into(),
+        }
+    }
+
+    /// The owner of the indexed drive, empty for the unscoped index.
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// Runs `f` with the connection on a thread where blocking is allowed.
+    async fn call<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection, &str) -> rusqlite::Result<R> + Send + 'static,
+    ) -> io::Result<R> {
+        let connection = self.connection.clone();
+        let owner = self.owner.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection
+                .lock()
+                .map_err(|_| io::Error::other("index lock poisoned"))?;
+            f(&mut connection, &owner).map_err(index_error)
+        })
+        .await?
+    }
+
+    /// Whether the database holds no entry at all, whoever their owner, e.g.
+    /// because it was just created.
+    pub async fn is_empty(&self) -> io::Result<bool> {
+        self.call(|connection, _| {
+            connection.query_row("SELECT NOT EXISTS (SELECT 1 FROM entries)", [], |row| {
+                row.get(0)
+            })
+        })
+        .await
+    }
+
+    /// The metadata recorded for `path`, `None` if it is not indexed.
+    pub async fn get(&self, path: &Path) -> io::Result<Option<Metadata>> {
+        let path = key(path);
+        self.call(move |connection, owner| {
+            connection
+                .query_row(
+                    &format!(
+                        "SELECT {} FROM entries WHERE owner = ?1 AND path = ?2",
+                        COLUMNS
+                    ),
+                    params![owner, path],
+                    from_row,
+                )
+                .optional()
+                .map(|entry| entry.map(|(_, metadata)| metadata))
+        })
+        .await
+    }
+
+    /// The entries recorded as direct children of the directory `path`,
+    /// ordered by path.
+    pub async fn children(&self, path: &Path) -> io::Result<Vec<(PathBuf, Metadata)>> {
+        let path = key(path);
+        self.call(move |connection, owner| {
+            let mut statement = connection.prepare_cached(&format!(
+                "SELECT {} FROM entries WHERE owner = ?1 AND parent = ?2 ORDER BY path",
+                COLUMNS
+            ))?;
+            let children = statement
+                .query_map(params![owner, path], from_row)?
+                .collect();
+            children
+        })
+        .await
+    }
+
+    /// The entries within `query.within` which may match `query`, leaving
+    /// the matching of names to `Drive::search`.
+    pub(crate) async fn search(&self, query: &SearchQuery) -> io::Result<Vec<(PathBuf, Metadata)>> {
+        let within = key(&query.within);
+        let mut conditions = vec![];
+        let mut values = vec![];
+        if !within.is_empty() {
+            conditions.push("substr(path, 1, length(?2) + 1) = ?2 || '/'");
+            values.push(Value::Text(within));
+        }
+        let mut filter = |condition, value| {
+            conditions.push(condition);
+            values.push(value);
+        };
+        if let Some(kind) = query.kind {
+            let is_directory = i64::from(kind == EntryKind::Directory);
+            filter("is_directory = ?", Value::Integer(is_directory));
+        }
+        if let Some(min) = query.min_size {
+            filter(
+                "size >= ?",
+                Value::Integer(min.try_into().unwrap_or(i64::MAX)),
+            );
+        }
+        if let Some(max) = query.max_size {
+            filter(
+                "size <= ?",
+                Value::Integer(max.try_into().unwrap_or(i64::MAX)),
+            );
+        }
+        if let Some(after) = to_nanos(query.modified_after) {
+            filter("modified >= ?", Value::Integer(after));
+        }
+        if let Some(before) = to_nanos(query.modified_before) {
+            filter("modified <= ?", Value::Integer(before));
+        }
+        if let Some(needle) = query.needle() {
+            filter("instr(lower(path), ?) > 0", Value::Text(needle));
+        }
+        let sql = std::iter::once(format!("SELECT {} FROM entries WHERE owner = ?1", COLUMNS))
+            .chain(conditions.iter().map(|condition| condition.to_string()))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        self.call(move |connection, owner| {
+            let mut statement = connection.prepare(&sql)?;
+            let mut parameters = vec![Value::Text(owner.to_string())];
+            parameters.extend(values);
+            let entries = statement
+                .query_map(params_from_iter(parameters), from_row)?
+                .collect();
+            entries
+        })
+        .await
+    }
+
+    /// Records `metadata` for `path`, replacing what was recorded.
+    pub(crate) async fn insert(&self, path: &Path, metadata: Metadata) -> io::Result<()> {
+        let path = path.to_path_buf();
+        self.call(move |connection, owner| insert(connection, owner, &path, &metadata))
+            .await
+    }
+
+    /// Moves what is recorded for `from` and the entries within it to `to`.
+    pub(crate) async fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let (from, to, parent) = (key(from), key(to), parent_key(to));
+        self.call(move |connection, owner| {
+            let transaction = connection.transaction()?;
+            transaction.execute(
+                &format!("DELETE FROM entries WHERE {}", SUBTREE),
+                params![owner, to],
+            )?;
+            transaction.execute(
+                "UPDATE entries SET path = ?3, parent = ?4 WHERE owner = ?1 AND path = ?2",
+                params![owner, from, to, parent],
+            )?;
+            transaction.execute(
+                "UPDATE entries \
+                 SET path = ?3 || substr(path, length(?2) + 1), \
+                     parent = ?3 || substr(parent, length(?2) + 1) \
+                 WHERE owner = ?1 AND substr(path, 1, length(?2) + 1) = ?2 || '/'",
+                params![owner, from, to],
+            )?;
+            transaction.commit()
+        })
+        .await
+    }
+
+    /// Forgets `path` and the entries within it.
+    pub(crate) async fn remove(&self, path: &Path) -> io::Result<()> {
+        let path = key(path);
+        self.call(move |connection, owner| {
+            connection.execute(
+                &format!("DELETE FROM entries WHERE {}", SUBTREE),
+                params![owner, path],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Replaces everything recorded for the drive with `entries`.
+    async fn replace(&self, entries: Vec<(PathBuf, Metadata)>) -> io::Result<()> {
+        self.call(move |connection, owner| {
+            let transaction = connection.transaction()?;
+            transaction.execute("DELETE FROM entries WHERE owner = ?1", params![owner])?;
+            for (path, metadata) in &entries {
+                insert(&transaction, owner, path, metadata)?;
+            }
+            transaction.commit()
+        })
+        .await
+    }
+}
+
+impl<T: StorageBackend> Drive<T> {
+    /// The entry at `path` as recorded by `index`, the root of the drive is
+    /// never recorded and is looked up in the backend.
+    pub(crate) async fn indexed_entry(&self, index: &Index, path: &Path) -> Result<Entry> {
+        let resolved = self.resolve(path).await?;
+        if path.as_os_str().is_empty() {
+            return self.entry(path).await;
+        }
+        if let Some(metadata) = index.get(path).await.map_err(DriveError::EntryIndex)? {
+            return Ok(Entry::new(path.to_path_buf(), Some(metadata)));
+        }
+        // The entries within linked directories are recorded under the path
+        // they resolve to.
+        if resolved.as_os_str().is_empty() {
+            return self.entry(path).await;
+        }
+        match index.get(&resolved).await.map_err(DriveError::EntryIndex)? {
+            Some(metadata) => Ok(Entry::new(path.to_path_buf(), Some(metadata))),
+            None => Err(DriveError::EntryNotFound(format!("{:?} not found", path))),
+        }
+    }
+
+    /// The metadata of the entry at `path` as it is to be recorded, along
+    /// with its MIME type when it is a file.
+    async fn indexed_metadata(
+        &self,
+        path: &Path,
+        metadata: Metadata,
+        hash: Option<String>,
+    ) -> Result<Metadata> {
+        if metadata.is_directory() {
+            return Ok(metadata);
+        }
+        let entry = Entry::new(path.to_path_buf(), Some(metadata));
+        let mime_type = self.mime_type(&entry).await?;
+        let metadata = entry.metadata().cloned().unwrap_or_default();
+        Ok(metadata.with_hash(hash).with_mime_type(mime_type))
+    }
+
+    /// Records the entry at `path` in the index, if the drive has one, with
+    /// the `hash` of its contents when it is a file.
+    pub(crate) async fn index_entry(&self, path: &Path, hash: Option<String>) -> Result<()> {
+        let Some(index) = &self.index else {
+            return Ok(());
+        };
+        let Some(metadata) = self
+            .backend
+            .stat(path)
+            .await
+            .map_err(DriveError::EntryMetadata)?
+        else {
+            return Ok(());
+        };
+        let metadata = self.indexed_metadata(path, metadata, hash).await?;
+        index
+            .insert(path, metadata)
+            .await
+            .map_err(DriveError::EntryIndex)
+    }
+
+    /// Moves the entries recorded within `from` to `to`, if the drive has an
+    /// index.
+    pub(crate) async fn index_rename(&self, from: &Path, to: &Path) -> Result<()> {
+        match &self.index {
+            Some(index) => index.rename(from, to).await.map_err(DriveError::EntryIndex),
+            None => Ok(()),
+        }
+    }
+
+    /// Forgets the entries recorded within `path`, if the drive has an
+    /// index.
+    pub(crate) async fn unindex(&self, path: &Path) -> Result<()> {
+        match &self.index {
+            Some(index) => index.remove(path).await.map_err(DriveError::EntryIndex),
+            None => Ok(()),
+        }
+    }
+
+    /// Hashes the contents of the file at `path`.
+    async fn hash_file(&self, path: &Path) -> Result<String> {
+        let content = self
+            .backend
+            .read(path)
+            .await
+            .map_err(DriveError::EntryMetadata)?;
+        pin! {
+            let reader = tokio_util::io::StreamReader::new(content);
+        };
+        let mut reader = HashingReader::new(reader);
+        tokio::io::copy(&mut reader, &mut tokio::io::sink())
+            .await
+            .map_err(DriveError::EntryMetadata)?;
+        Ok(reader.finish())
+    }
+
+    /// The entries within the directory `path` as they are to be recorded,
+    /// read from the backend. RESERVED_DIR is left out, as are the entries
+    /// within linked directories which may lead back up the tree.
+    async fn walk(&self, path: &Path) -> Result<Vec<(PathBuf, Metadata)>> {
+        let mut entries = vec![];
+        let mut pending = vec![path.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            let listing = self
+                .backend
+                .list(&dir)
+                .await
+                .map_err(DriveError::EntryWalk)?;
+            for (path, metadata) in listing {
+                if path == Path::new(RESERVED_DIR) {
+                    continue;
+                }
+                let hash = if metadata.is_directory() {
+                    if !metadata.is_symlink() {
+                        pending.push(path.clone());
+                    }
+                    None
+                } else {
+                    Some(self.hash_file(&path).await?)
+                };
+                let metadata = self.indexed_metadata(&path, metadata, hash).await?;
+                entries.push((path, metadata));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Records the entry at `path` and the entries within it, if the drive
+    /// has an index.
+    pub(crate) async fn index_tree(&self, path: &Path) -> Result<()> {
+        let Some(index) = &self.index else {
+            return Ok(());
+        };
+        let Some(metadata) = self
+            .backend
+            .stat(path)
+            .await
+            .map_err(DriveError::EntryMetadata)?
+        else {
+            return Ok(());
+        };
+        if !metadata.is_directory() {
+            let hash = self.hash_file(path).await?;
+            return self.index_entry(path, Some(hash)).await;
+        }
+        self.index_entry(path, None).await?;
+        for (path, metadata) in self.walk(path).await? {
+            index
+                .insert(&path, metadata)
+                .await
+                .map_err(DriveError::EntryIndex)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the index from the entries found in the backend, returning
+    /// how many were recorded. Drives without an index record none.
+    ///
+    /// Every file is read to be hashed.
+    pub async fn rebuild_index(&self) -> Result<usize> {
+        let Some(index) = &self.index else {
+            return Ok(0);
+        };
+        let entries = self.walk(Path::new("")).await?;
+        let indexed = entries.len();
+        index
+            .replace(entries)
+            .await
+            .map_err(DriveError::EntryIndex)?;
+        Ok(indexed)
+    }
+}
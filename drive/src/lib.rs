@@ -1,72 +1,172 @@
 use crate::entry::Entry;
-use std::path::{Path, PathBuf};
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
+use backend::{ByteStream, LocalBackend, StorageBackend};
 use bytes::Buf;
 use error::DriveError;
+use index::Index;
 use tokio::pin;
-use tokio_util::io::ReaderStream;
+use tokio_stream::StreamExt;
+use versions::VersionPolicy;
+pub mod backend;
 pub mod entry;
 pub mod error;
-pub struct Drive {
-    base: PathBuf,
+mod hash;
+pub mod index;
+pub mod mime;
+pub mod records;
+mod reserved;
+pub mod search;
+pub mod trash;
+pub mod versions;
+#[derive(Clone)]
+pub struct Drive<T = LocalBackend> {
+    backend: T,
+    /// Keeps prior versions of overwritten files when set.
+    versioning: Option<VersionPolicy>,
+    /// Who writes through the drive, recorded along with versions.
+    author: Option<String>,
+    /// Records the metadata of the entries and serves it when set.
+    index: Option<Index>,
 }
 
 type Result<T> = std::result::Result<T, DriveError>;
 
+/// The directory at the root of a drive holding what the drive keeps about
+/// itself, such as its trash. It cannot be reached through the drive.
+pub const RESERVED_DIR: &str = ".mibox";
+
+/// Whether `path` only walks down the file tree, i.e. all of its components
+/// are of type std::path::Component::Normal, as required of the paths of a
+/// drive.
+pub fn is_valid_path(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .components()
+        .all(|component| matches!(component, std::path::Component::Normal(_)))
+}
+
 impl Drive {
     pub fn new(base: impl AsRef<Path>) -> Self {
-        let base = base.as_ref().to_path_buf();
-        Self { base }
+        Self::with_backend(LocalBackend::new(base))
+    }
+}
+
+impl<T: StorageBackend> Drive<T> {
+    pub fn with_backend(backend: T) -> Self {
+        Self {
+            backend,
+            versioning: None,
+            author: None,
+            index: None,
+        }
+    }
+
+    /// Keeps the prior versions of overwritten files following `policy`
+    /// (see the versions module).
+    pub fn with_versioning(mut self, policy: VersionPolicy) -> Self {
+        self.versioning = Some(policy);
+        self
+    }
+
+    /// Records `author` as the author of the files written through the drive.
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    /// Keeps the metadata of the entries in `index` and serves stat and
+    /// listings from it (see the index module).
+    pub fn with_index(mut self, index: Index) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    pub fn index(&self) -> Option<&Index> {
+        self.index.as_ref()
     }
 
-    /// Checks if the path exists and if not an error is returned.
-    fn entry_exists(path: impl AsRef<Path>) -> Result<()> {
-        if !path.as_ref().exists() {
-            return Err(DriveError::EntryNotFound(format!(
+    pub fn backend(&self) -> &T {
+        &self.backend
+    }
+
+    /// Checks if the path exists and if so returns its metadata otherwise
+    /// an error is returned.
+    async fn entry_exists(&self, path: impl AsRef<Path>) -> Result<entry::Metadata> {
+        match self
+            .backend
+            .stat(path.as_ref())
+            .await
+            .map_err(DriveError::EntryMetadata)?
+        {
+            Some(metadata) => Ok(metadata),
+            None => Err(DriveError::EntryNotFound(format!(
                 "{:?} not found",
                 path.as_ref()
-            )));
+            ))),
         }
-        Ok(())
     }
 
     /// Validates that the provided path does not walk through the
-    /// file tree and if so an error is returned otherwise returns a path
-    /// with the base as prefix
+    /// file tree and if so an error is returned otherwise returns the path
+    /// relative to the root of the drive.
     ///
     /// This is achieved by checking if all the path components
-    /// are of type std::path::Component::Normal.
-    fn entry_valid(&self, path: impl AsRef<Path>) -> Result<PathBuf> {
-        if !path
-            .as_ref()
-            .components()
-            .all(|component| matches!(component, std::path::Component::Normal(_)))
-        {
+    /// are of type std::path::Component::Normal and by letting the backend
+    /// resolve the path, so that links leading out of the drive are rejected
+    /// as well (see StorageBackend::resolve). Paths within RESERVED_DIR are
+    /// rejected too.
+    async fn entry_valid(&self, path: impl AsRef<Path>) -> Result<PathBuf> {
+        self.resolve(path.as_ref()).await?;
+        Ok(path.as_ref().to_path_buf())
+    }
+
+    /// Returns the path `path` designates once the links it goes through are
+    /// followed, relative to the root of the drive.
+    ///
+    /// The path is validated as by Self::entry_valid, it does not need to
+    /// exist.
+    pub async fn resolve(&self, path: impl AsRef<Path>) -> Result<PathBuf> {
+        if !is_valid_path(path.as_ref()) {
             return Err(DriveError::EntryNameInvalid(format!(
                 "{:?} invalid",
                 path.as_ref()
             )));
         }
-        let entry = self.base.join(path);
-        Ok(entry)
+        if path.as_ref().starts_with(RESERVED_DIR) {
+            return Err(DriveError::EntryNameInvalid(format!(
+                "{:?} is reserved",
+                path.as_ref()
+            )));
+        }
+        self.backend
+            .resolve(path.as_ref())
+            .await
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::PermissionDenied => {
+                    DriveError::EntryNameInvalid(format!("{:?} invalid: {}", path.as_ref(), e))
+                }
+                _ => DriveError::EntryMetadata(e),
+            })
     }
 
     /// The method that create an entry given a path.
     ///
     /// The entry will only be created if the path exists and there are no
     /// path walks in the final path (Self::entry_valid).
-    fn entry(&self, path: impl AsRef<Path>) -> Result<entry::Entry> {
-        let entry = self.entry_valid(path.as_ref())?;
-        Self::entry_exists(&entry)?;
-        let metadata = entry.metadata().map_err(DriveError::EntryMetadata)?;
+    async fn entry(&self, path: impl AsRef<Path>) -> Result<entry::Entry> {
+        let entry = self.entry_valid(path.as_ref()).await?;
+        let metadata = self.entry_exists(&entry).await?;
         Ok(entry::Entry::new(entry, Some(metadata)))
     }
 
     /// The method that returns a PathBuf after checking it doesn't exists
     /// and there are no path walks in the final path (Self::entry_valid).
-    fn entry_non_existant(&self, path: impl AsRef<Path>) -> Result<PathBuf> {
-        let entry = self.entry_valid(path.as_ref())?;
-        if Self::entry_exists(&entry).is_ok() {
+    async fn entry_non_existant(&self, path: impl AsRef<Path>) -> Result<PathBuf> {
+        let entry = self.entry_valid(path.as_ref()).await?;
+        if self.entry_exists(&entry).await.is_ok() {
             return Err(DriveError::EntryExists(format!(
                 "{:?} already exists",
                 entry
@@ -75,12 +175,50 @@ impl Drive {
         Ok(entry)
     }
 
+    /// Returns the entry provided by the path along with its metadata.
+    pub async fn stat(&self, path: impl AsRef<Path>) -> Result<Entry> {
+        match &self.index {
+            Some(index) => self.indexed_entry(index, path.as_ref()).await,
+            None => self.entry(path).await,
+        }
+    }
+
+    /// Returns the MIME type of an entry, directories have none.
+    ///
+    /// The type is guessed from the extension of the file and otherwise
+    /// sniffed from its first bytes (see mime::detect).
+    pub async fn mime_type(&self, entry: &Entry) -> Result<Option<String>> {
+        if entry.is_directory() {
+            return Ok(None);
+        }
+        if let Some(mime_type) = entry.mime_type() {
+            return Ok(Some(mime_type));
+        }
+        let path = self.entry_valid(entry.path()).await?;
+        let length = entry.size().min(mime::SNIFF_LENGTH);
+        let mut head = Vec::with_capacity(length as usize);
+        if length > 0 {
+            let mut stream = self
+                .backend
+                .read_range(&path, 0..length)
+                .await
+                .map_err(DriveError::EntryMetadata)?;
+            while let Some(chunk) = stream.next().await {
+                head.extend_from_slice(&chunk.map_err(DriveError::EntryMetadata)?);
+            }
+        }
+        Ok(Some(mime::detect(&path, &head)))
+    }
+
     /// Create a directory if it does not exists
     pub async fn create_directory(&self, path: impl AsRef<Path>) -> Result<()> {
-        let entry_to = self.entry_non_existant(path)?;
-        tokio::fs::create_dir(entry_to)
+        let entry_to = self.entry_non_existant(path).await?;
+        self.backend
+            .mkdir(&entry_to)
             .await
-            .map_err(DriveError::EntryCreate)
+            .map_err(DriveError::EntryCreate)?;
+        self.forget_versions(&entry_to).await?;
+        self.index_entry(&entry_to, None).await
     }
 
     /// Renames a directory entry.
@@ -91,17 +229,147 @@ impl Drive {
         from: impl AsRef<Path>,
         to: impl AsRef<Path>,
     ) -> Result<()> {
-        let entry_from = self.entry(from)?;
-        let entry_to = self.entry_non_existant(to)?;
+        let entry_from = self.entry(from).await?;
+        let entry_to = self.entry_non_existant(to).await?;
         if !(entry_from.is_directory()) {
             return Err(DriveError::EntryUnexpectedType(format!(
                 "{:?} is not an directory",
                 entry_to
             )));
         }
-        tokio::fs::rename(entry_from.path(), entry_to)
+        self.backend
+            .rename(entry_from.path(), &entry_to)
+            .await
+            .map_err(DriveError::EntryRename)?;
+        self.forget_versions(&entry_to).await?;
+        self.index_rename(entry_from.path(), &entry_to).await
+    }
+
+    /// Renames a file.
+    ///
+    /// This operation won't overwrite the `to` path.
+    pub async fn rename_file(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<()> {
+        let entry_from = self.entry(from).await?;
+        let entry_to = self.entry_non_existant(to).await?;
+        if entry_from.is_directory() {
+            return Err(DriveError::EntryUnexpectedType(format!(
+                "{:?} is not a file",
+                entry_from.path()
+            )));
+        }
+        self.backend
+            .rename(entry_from.path(), &entry_to)
             .await
-            .map_err(DriveError::EntryRename)
+            .map_err(DriveError::EntryRename)?;
+        self.index_rename(entry_from.path(), &entry_to).await
+    }
+
+    /// Copies a file.
+    ///
+    /// If the destination file exists then it will be overwritten.
+    pub async fn copy_file(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<()> {
+        let entry_from = self.entry(from).await?;
+        if entry_from.is_directory() {
+            return Err(DriveError::EntryUnexpectedType(format!(
+                "{:?} is not a file",
+                entry_from.path()
+            )));
+        }
+        let stream = self
+            .backend
+            .read(entry_from.path())
+            .await
+            .map_err(DriveError::EntryMetadata)?;
+        self.write(stream, to).await
+    }
+
+    /// Copies a directory along with its contents.
+    ///
+    /// This operation won't overwrite the `to` path.
+    pub async fn copy_directory(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<()> {
+        let entry_from = self.entry(from).await?;
+        let entry_to = self.entry_non_existant(to).await?;
+        if !entry_from.is_directory() {
+            return Err(DriveError::EntryUnexpectedType(format!(
+                "{:?} is not an directory",
+                entry_from.path()
+            )));
+        }
+        if entry_to.starts_with(entry_from.path()) {
+            return Err(DriveError::EntryNameInvalid(format!(
+                "{:?} cannot be copied into itself",
+                entry_from.path()
+            )));
+        }
+        let mut pending = vec![(entry_from.path().clone(), entry_to)];
+        while let Some((from, to)) = pending.pop() {
+            self.backend
+                .mkdir(&to)
+                .await
+                .map_err(DriveError::EntryCreate)?;
+            self.forget_versions(&to).await?;
+            self.index_entry(&to, None).await?;
+            for entry in self.entries(&from).await? {
+                let Some(name) = entry.path().file_name() else {
+                    continue;
+                };
+                if entry.is_directory() {
+                    pending.push((entry.path().clone(), to.join(name)));
+                } else {
+                    self.copy_file(entry.path(), to.join(name)).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a file.
+    ///
+    /// An error will be returned if the path does not correspond to a file.
+    pub async fn remove_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let entry = self.entry(path).await?;
+        if entry.is_directory() {
+            return Err(DriveError::EntryUnexpectedType(format!(
+                "{:?} is not a file",
+                entry.path()
+            )));
+        }
+        self.backend
+            .remove_file(entry.path())
+            .await
+            .map_err(DriveError::EntryRemove)?;
+        self.unindex(entry.path()).await
+    }
+
+    /// Removes a directory, when `recursive` is not set the directory must
+    /// be empty.
+    ///
+    /// An error will be returned if the path does not correspond to a
+    /// directory or if it corresponds to the root of the drive.
+    pub async fn remove_directory(&self, path: impl AsRef<Path>, recursive: bool) -> Result<()> {
+        if path.as_ref().as_os_str().is_empty() {
+            return Err(DriveError::EntryNameInvalid(
+                "the root of the drive cannot be removed".to_string(),
+            ));
+        }
+        let entry = self.entry(path).await?;
+        if !(entry.is_directory()) {
+            return Err(DriveError::EntryUnexpectedType(format!(
+                "{:?} is not an directory",
+                entry.path()
+            )));
+        }
+        if !recursive && !self.entries(entry.path()).await?.is_empty() {
+            return Err(DriveError::EntryNotEmpty(format!(
+                "{:?} is not empty",
+                entry.path()
+            )));
+        }
+        self.backend
+            .remove_dir(entry.path(), recursive)
+            .await
+            .map_err(DriveError::EntryRemove)?;
+        self.unindex(entry.path()).await
     }
 
     /// Queries all entries from a given path
@@ -109,9 +377,9 @@ impl Drive {
     /// An error will be returned if the path does not correspond to a directory.
     pub async fn entries(&self, path: impl AsRef<Path>) -> Result<Vec<Entry>> {
         let path = if path.as_ref().as_os_str().is_empty() {
-            self.base.clone()
+            PathBuf::new()
         } else {
-            let entry = self.entry(path)?;
+            let entry = self.stat(path).await?;
             if !(entry.is_directory()) {
                 return Err(DriveError::EntryUnexpectedType(format!(
                     "{:?} is not an directory",
@@ -120,65 +388,118 @@ impl Drive {
             }
             entry.path().to_path_buf()
         };
-        let mut entries = vec![];
-        let mut directory = tokio::fs::read_dir(path)
-            .await
-            .map_err(DriveError::EntryWalk)?;
-        while let Some(read_dir_entry) = directory
-            .next_entry()
-            .await
-            .map_err(DriveError::EntryWalk)?
-        {
-            let path = read_dir_entry.path();
-            let metadata = read_dir_entry
-                .metadata()
+        let listing = match &self.index {
+            // The entries within linked directories are recorded under the
+            // path they resolve to.
+            Some(index) => index
+                .children(&self.resolve(&path).await?)
                 .await
-                .map_err(DriveError::EntryMetadata)?;
-
-            entries.push(Entry::new(path, Some(metadata)));
-        }
+                .map_err(DriveError::EntryIndex)?
+                .into_iter()
+                .filter_map(|(child, metadata)| Some((path.join(child.file_name()?), metadata)))
+                .collect(),
+            None => self
+                .backend
+                .list(&path)
+                .await
+                .map_err(DriveError::EntryWalk)?,
+        };
+        let entries = listing
+            .into_iter()
+            .filter(|(path, _)| path != Path::new(RESERVED_DIR))
+            .map(|(path, metadata)| Entry::new(path, Some(metadata)))
+            .collect();
         Ok(entries)
     }
 
     /// Reads the file provided by the path as a stream.
-    pub async fn read(
+    pub async fn read(&self, path: impl AsRef<Path>) -> Result<ByteStream> {
+        let entry = self.entry(path).await?;
+        if entry.is_directory() {
+            return Err(DriveError::EntryUnexpectedType(
+                "Entry is a directory".to_string(),
+            ));
+        }
+        self.backend
+            .read(entry.path())
+            .await
+            .map_err(|_e| DriveError::EntryNameInvalid("invalid path".to_string()))
+    }
+
+    /// Reads the bytes within `range` of the file provided by the path as a
+    /// stream.
+    ///
+    /// An error will be returned if the range does not lie within the file.
+    pub async fn read_range(
         &self,
         path: impl AsRef<Path>,
-    ) -> Result<impl futures_core::Stream<Item = std::result::Result<bytes::Bytes, std::io::Error>>>
-    {
-        let entry = self.entry(path)?;
+        range: Range<u64>,
+    ) -> Result<ByteStream> {
+        let entry = self.stat(path).await?;
         if entry.is_directory() {
             return Err(DriveError::EntryUnexpectedType(
                 "Entry is a directory".to_string(),
             ));
         }
-        let file = tokio::fs::File::open(entry.path())
+        if range.start > range.end || range.end > entry.size() {
+            return Err(DriveError::EntryRangeNotSatisfiable(format!(
+                "{:?} is not within the {} bytes of {:?}",
+                range,
+                entry.size(),
+                entry.path()
+            )));
+        }
+        self.backend
+            .read_range(entry.path(), range)
             .await
-            .map_err(|_e| DriveError::EntryNameInvalid("invalid path".to_string()))?;
-        let reader = ReaderStream::new(file);
-        Ok::<ReaderStream<tokio::fs::File>, DriveError>(reader)
+            .map_err(|_e| DriveError::EntryNameInvalid("invalid path".to_string()))
     }
 
     /// Writes the contents of the stream into a file.
     ///
-    /// If destination file exists then it will be overwritten.
+    /// If destination file exists then it will be overwritten, the new
+    /// contents only replace it once the whole stream has been written so a
+    /// failed write leaves the destination untouched. With versioning the
+    /// overwritten contents are kept as a version of the file once the write
+    /// succeeds, with an index the contents are hashed as they are written.
     pub async fn write<
-        B: Buf,
-        S: futures_core::Stream<Item = std::result::Result<B, std::io::Error>>,
+        B: Buf + Send,
+        S: futures_core::Stream<Item = std::result::Result<B, std::io::Error>> + Send,
     >(
         &self,
         stream: S,
         path: impl AsRef<Path>,
     ) -> Result<()> {
-        let entry_to = self.entry_valid(path.as_ref())?;
-        let file = tokio::fs::File::create(entry_to)
-            .await
-            .map_err(|_e| DriveError::EntryNameInvalid("invalid path".to_string()))?;
+        let entry_to = self.entry_valid(path.as_ref()).await?;
+        let snapshot = match &self.versioning {
+            Some(policy) => self.keep_version(&entry_to, policy).await?,
+            None => None,
+        };
         pin! {
             let reader = tokio_util::io::StreamReader::new(stream);
-            let writer = tokio::io::BufWriter::new(file);
         };
-        tokio::io::copy(&mut reader, &mut writer).await.unwrap();
+        let written = if self.index.is_some() {
+            let mut reader = hash::HashingReader::new(reader);
+            self.backend
+                .write(&entry_to, &mut reader)
+                .await
+                .map(|()| Some(reader.finish()))
+        } else {
+            self.backend.write(&entry_to, reader).await.map(|()| None)
+        };
+        let hash = match written {
+            Ok(hash) => hash,
+            Err(e) => {
+                if let Some(snapshot) = snapshot {
+                    self.discard_version(&entry_to, snapshot).await?;
+                }
+                return Err(DriveError::EntryWrite(e));
+            }
+        };
+        self.index_entry(&entry_to, hash).await?;
+        if let Some(policy) = &self.versioning {
+            self.record_version(&entry_to, policy, snapshot).await?;
+        }
         Ok(())
     }
 }
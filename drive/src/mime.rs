@@ -0,0 +1,101 @@
+//! MIME type detection from file names and from the first bytes of files.
+use std::path::Path;
+
+/// How many bytes of a file are read to sniff its type.
+pub const SNIFF_LENGTH: u64 = 512;
+
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Signatures found at a fixed offset at the start of files, along with the
+/// type they identify.
+const SIGNATURES: &[(usize, &[u8], &str)] = &[
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xff\xd8\xff", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (0, b"BM", "image/bmp"),
+    (0, b"\x00\x00\x01\x00", "image/x-icon"),
+    (0, b"II*\x00", "image/tiff"),
+    (0, b"MM\x00*", "image/tiff"),
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"PK\x05\x06", "application/zip"),
+    (0, b"\x1f\x8b", "application/gzip"),
+    (0, b"BZh", "application/x-bzip2"),
+    (0, b"\xfd7zXZ\x00", "application/x-xz"),
+    (0, b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (0, b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (257, b"ustar", "application/x-tar"),
+    (0, b"SQLite format 3\x00", "application/vnd.sqlite3"),
+    (0, b"\x7fELF", "application/x-executable"),
+    (0, b"\x00asm", "application/wasm"),
+    (0, b"{\\rtf", "application/rtf"),
+    (0, b"wOFF", "font/woff"),
+    (0, b"wOF2", "font/woff2"),
+    (0, b"ID3", "audio/mpeg"),
+    (0, b"OggS", "audio/ogg"),
+    (0, b"fLaC", "audio/flac"),
+    (0, b"\x1a\x45\xdf\xa3", "video/webm"),
+    (4, b"ftyp", "video/mp4"),
+];
+
+/// Containers whose actual format is given by a four character code at
+/// offset 8, e.g. `RIFF....WEBP`.
+const RIFF_FORMATS: &[(&[u8], &str)] = &[
+    (b"WEBP", "image/webp"),
+    (b"WAVE", "audio/wav"),
+    (b"AVI ", "video/x-msvideo"),
+];
+
+/// The MIME type registered for the extension of `path`, if any.
+pub fn from_extension(path: impl AsRef<Path>) -> Option<String> {
+    mime_guess::from_path(path)
+        .first_raw()
+        .map(ToOwned::to_owned)
+}
+
+/// The MIME type of a file guessed from `head`, its first bytes (see
+/// `SNIFF_LENGTH`), if they carry a known signature or look like text.
+pub fn sniff(head: &[u8]) -> Option<&'static str> {
+    if let Some((_, _, mime_type)) = SIGNATURES.iter().find(|(offset, signature, _)| {
+        head.get(*offset..offset + signature.len()) == Some(*signature)
+    }) {
+        return Some(mime_type);
+    }
+    if head.starts_with(b"RIFF") {
+        let format = head.get(8..12)?;
+        return RIFF_FORMATS
+            .iter()
+            .find(|(code, _)| *code == format)
+            .map(|(_, mime_type)| *mime_type);
+    }
+    is_text(head).then_some("text/plain")
+}
+
+/// Whether `head` is UTF-8 text without control characters other than
+/// whitespace, allowing a character cut off at the end.
+fn is_text(head: &[u8]) -> bool {
+    if head.is_empty() {
+        return false;
+    }
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return false,
+    };
+    text.chars()
+        .all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r' | '\x0c'))
+}
+
+/// The MIME type of the file `path` starting with `head`.
+///
+/// The extension is trusted when it is registered, so that formats built on
+/// top of others (e.g. a `.docx` is a zip archive) are told apart. Otherwise
+/// the type is sniffed from `head`, defaulting to `application/octet-stream`.
+pub fn detect(path: impl AsRef<Path>, head: &[u8]) -> String {
+    from_extension(path)
+        .or_else(|| sniff(head).map(ToOwned::to_owned))
+        .unwrap_or_else(|| OCTET_STREAM.to_owned())
+}
@@ -0,0 +1,94 @@
+//! Records applications keep along with a drive, such as the api tokens of
+//! its users, so that they outlive the process.
+//!
+//! A record of a given kind is kept at `.mibox/records/<kind>/<id>` as a
+//! record file whose header is the kind in brackets:
+//!
+//! ```text
+//! [tokens]
+//! Owner=alice
+//! Scope=read
+//! ```
+//!
+//! Records are stored as they are, secrets should be hashed beforehand.
+use crate::{backend::StorageBackend, error::DriveError, Drive, Result, RESERVED_DIR};
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+const RECORDS_DIR: &str = "records";
+
+/// The fields of a record, keyed by name.
+pub type Fields = HashMap<String, String>;
+
+/// The directory of the records of `kind`.
+fn kind_dir(kind: &str) -> Result<PathBuf> {
+    Ok(Path::new(RESERVED_DIR)
+        .join(RECORDS_DIR)
+        .join(single_component(kind)?))
+}
+
+fn single_component(name: &str) -> Result<&str> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(name),
+        _ => Err(DriveError::EntryNameInvalid(format!(
+            "{:?} cannot name a record",
+            name
+        ))),
+    }
+}
+
+fn header(kind: &str) -> String {
+    format!("[{}]", kind)
+}
+
+impl<T: StorageBackend> Drive<T> {
+    /// Writes the record `id` of `kind`, replacing any.
+    pub async fn put_record(&self, kind: &str, id: &str, fields: &[(&str, &str)]) -> Result<()> {
+        let dir = kind_dir(kind)?;
+        self.create_reserved_dir(&dir).await?;
+        self.write_record(&dir.join(single_component(id)?), &header(kind), fields)
+            .await
+    }
+
+    /// Removes the record `id` of `kind`, returning whether there was one.
+    pub async fn delete_record(&self, kind: &str, id: &str) -> Result<bool> {
+        let path = kind_dir(kind)?.join(single_component(id)?);
+        if !self.exists_reserved(&path).await? {
+            return Ok(false);
+        }
+        self.backend
+            .remove_file(&path)
+            .await
+            .map_err(DriveError::EntryRemove)?;
+        Ok(true)
+    }
+
+    /// The records of `kind` keyed by id, leaving out the unreadable ones.
+    pub async fn records(&self, kind: &str) -> Result<HashMap<String, Fields>> {
+        let dir = kind_dir(kind)?;
+        if !self.exists_reserved(&dir).await? {
+            return Ok(HashMap::new());
+        }
+        let listing = self
+            .backend
+            .list(&dir)
+            .await
+            .map_err(DriveError::EntryWalk)?;
+        let mut records = HashMap::new();
+        for (path, metadata) in listing {
+            let Some(id) = path.file_name().and_then(|id| id.to_str()) else {
+                continue;
+            };
+            if metadata.is_directory() {
+                continue;
+            }
+            if let Some(fields) = self.read_record(&path, &header(kind)).await? {
+                records.insert(id.to_owned(), fields);
+            }
+        }
+        Ok(records)
+    }
+}
@@ -0,0 +1,131 @@
+//! Access to RESERVED_DIR, where a drive keeps what it records about itself,
+//! bypassing the checks that keep it out of reach of the drive's users.
+//!
+//! Records are small text files in the spirit of the freedesktop.org trash
+//! info files, a header followed by `key=value` lines whose values are
+//! percent encoded.
+use crate::{backend::StorageBackend, error::DriveError, Drive, Result};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio_stream::StreamExt;
+
+/// The characters percent encoded in the values of records.
+const VALUE_ENCODE_SET: &AsciiSet = &CONTROLS.add(b' ').add(b'%').add(b'=');
+
+/// A record made of `fields` under `header`, e.g. `[Trash Info]`.
+pub(crate) fn format_record(header: &str, fields: &[(&str, &str)]) -> String {
+    let mut record = format!("{}\n", header);
+    for (key, value) in fields {
+        record.push_str(&format!(
+            "{}={}\n",
+            key,
+            utf8_percent_encode(value, VALUE_ENCODE_SET)
+        ));
+    }
+    record
+}
+
+/// The fields of a record, `None` if it is not one with `header`.
+pub(crate) fn parse_record(header: &str, record: &str) -> Option<HashMap<String, String>> {
+    let mut lines = record.lines();
+    if lines.next()? != header {
+        return None;
+    }
+    lines
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let value = percent_decode_str(value).decode_utf8().ok()?;
+            Some((key.to_owned(), value.into_owned()))
+        })
+        .collect()
+}
+
+/// A time as recorded, in seconds since the Unix epoch.
+pub(crate) fn format_time(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        .to_string()
+}
+
+pub(crate) fn parse_time(time: &str) -> Option<SystemTime> {
+    Some(UNIX_EPOCH + Duration::from_secs(time.parse().ok()?))
+}
+
+/// An id derived from the current time, which sorts along with it.
+pub(crate) fn time_id() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+impl<T: StorageBackend> Drive<T> {
+    /// Whether `path`, within RESERVED_DIR, exists.
+    pub(crate) async fn exists_reserved(&self, path: &Path) -> Result<bool> {
+        self.backend
+            .stat(path)
+            .await
+            .map(|metadata| metadata.is_some())
+            .map_err(DriveError::EntryMetadata)
+    }
+
+    /// Creates `path`, within RESERVED_DIR, and the directories leading to
+    /// it when missing.
+    pub(crate) async fn create_reserved_dir(&self, path: &Path) -> Result<()> {
+        let mut ancestors = path.ancestors().collect::<Vec<_>>();
+        ancestors.reverse();
+        for ancestor in ancestors {
+            if ancestor.as_os_str().is_empty() || self.exists_reserved(ancestor).await? {
+                continue;
+            }
+            self.backend
+                .mkdir(ancestor)
+                .await
+                .map_err(DriveError::EntryCreate)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the record at `path`, within RESERVED_DIR, `None` if there is
+    /// none.
+    pub(crate) async fn read_record(
+        &self,
+        path: &Path,
+        header: &str,
+    ) -> Result<Option<HashMap<String, String>>> {
+        if !self.exists_reserved(path).await? {
+            return Ok(None);
+        }
+        let mut stream = self
+            .backend
+            .read(path)
+            .await
+            .map_err(DriveError::EntryMetadata)?;
+        let mut record = vec![];
+        while let Some(chunk) = stream.next().await {
+            record.extend_from_slice(&chunk.map_err(DriveError::EntryMetadata)?);
+        }
+        Ok(std::str::from_utf8(&record)
+            .ok()
+            .and_then(|record| parse_record(header, record)))
+    }
+
+    /// Writes a record at `path`, within RESERVED_DIR, replacing any.
+    pub(crate) async fn write_record(
+        &self,
+        path: &Path,
+        header: &str,
+        fields: &[(&str, &str)],
+    ) -> Result<()> {
+        let record = format_record(header, fields);
+        self.backend
+            .write(path, record.as_bytes())
+            .await
+            .map_err(DriveError::EntryWrite)
+    }
+}
@@ -0,0 +1,248 @@
+//! Search of the entries of a drive by name and metadata.
+//!
+//! Names are matched against a glob when the pattern holds one of `*`, `?`
+//! or `[`, and searched for the pattern otherwise, ignoring case either way.
+//! Matches are scored by how closely their name matches (see `SearchHit`).
+//! Indexed drives are searched through their index, others are walked.
+use crate::{
+    backend::StorageBackend,
+    entry::{Entry, Metadata},
+    error::DriveError,
+    Drive, Result,
+};
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// The kinds of entries a search can be restricted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Directory,
+}
+
+/// What a search matches, every entry of the drive by default.
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    /// The pattern names are matched against, an empty one matches any.
+    pub pattern: String,
+    pub kind: Option<EntryKind>,
+    /// The smallest size in bytes of matching entries.
+    pub min_size: Option<u64>,
+    /// The largest size in bytes of matching entries.
+    pub max_size: Option<u64>,
+    pub modified_after: Option<SystemTime>,
+    pub modified_before: Option<SystemTime>,
+    /// The directory searched, the root of the drive when empty.
+    pub within: PathBuf,
+}
+
+/// Whether `pattern` is a glob rather than text to search for.
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Matches the character class opening `pattern`, e.g. `[a-z]` or `[!0-9]`,
+/// against `c`, returning whether it matched and what follows the class.
+/// `None` if the class is not closed.
+fn match_class(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+    let (negated, mut rest) = match pattern {
+        ['!' | '^', rest @ ..] => (true, rest),
+        rest => (false, rest),
+    };
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        match rest {
+            [']', tail @ ..] if !first => return Some((matched != negated, tail)),
+            [low, '-', high, tail @ ..] if *high != ']' => {
+                matched |= (*low..=*high).contains(&c);
+                rest = tail;
+            }
+            [single, tail @ ..] => {
+                matched |= *single == c;
+                rest = tail;
+            }
+            [] => return None,
+        }
+        first = false;
+    }
+}
+
+/// Whether `name` matches the glob `pattern` as a whole.
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (pattern, name);
+    // Where to resume after the last `*` when the rest fails to match.
+    let mut backtrack: Option<(&[char], &[char])> = None;
+    loop {
+        let step = match (p, n) {
+            ([], []) => return true,
+            (['*', rest @ ..], _) => {
+                backtrack = Some((rest, n));
+                p = rest;
+                continue;
+            }
+            (['?', p_rest @ ..], [_, n_rest @ ..]) => Some((p_rest, n_rest)),
+            (['[', class @ ..], [c, n_rest @ ..]) => match match_class(class, *c) {
+                Some((true, p_rest)) => Some((p_rest, n_rest)),
+                Some((false, _)) => None,
+                // An unclosed bracket is matched literally.
+                None => (*c == '[').then_some((class, n_rest)),
+            },
+            ([expected, p_rest @ ..], [c, n_rest @ ..]) if expected == c => Some((p_rest, n_rest)),
+            _ => None,
+        };
+        match (step, backtrack) {
+            (Some((p_rest, n_rest)), _) => (p, n) = (p_rest, n_rest),
+            (None, Some((rest, [_, n_rest @ ..]))) => {
+                backtrack = Some((rest, n_rest));
+                (p, n) = (rest, n_rest);
+            }
+            (None, _) => return false,
+        }
+    }
+}
+
+impl SearchQuery {
+    /// The score of an entry named `name`, `None` if it does not match the
+    /// pattern.
+    fn score(&self, name: &str) -> Option<u32> {
+        if self.pattern.is_empty() {
+            return Some(0);
+        }
+        let pattern = self.pattern.to_lowercase();
+        let lowercase = name.to_lowercase();
+        let exact_case = u32::from(name.contains(&self.pattern));
+        if is_glob(&pattern) {
+            let pattern = pattern.chars().collect::<Vec<_>>();
+            let name = lowercase.chars().collect::<Vec<_>>();
+            return glob_match(&pattern, &name).then_some(40);
+        }
+        let position = lowercase.find(&pattern)?;
+        let score = if lowercase == pattern {
+            100
+        } else if position == 0 {
+            75
+        } else if !lowercase[..position]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric)
+        {
+            60
+        } else {
+            50
+        };
+        Some(score + exact_case * 5)
+    }
+
+    /// Whether `metadata` passes the filters of the query.
+    fn filters(&self, metadata: &Metadata) -> bool {
+        let kind = match metadata.is_directory() {
+            true => EntryKind::Directory,
+            false => EntryKind::File,
+        };
+        self.kind.is_none_or(|expected| expected == kind)
+            && self.min_size.is_none_or(|min| metadata.size() >= min)
+            && self.max_size.is_none_or(|max| metadata.size() <= max)
+            && self.modified_after.is_none_or(|after| {
+                metadata
+                    .modified()
+                    .is_some_and(|modified| modified >= after)
+            })
+            && self.modified_before.is_none_or(|before| {
+                metadata
+                    .modified()
+                    .is_some_and(|modified| modified <= before)
+            })
+    }
+
+    /// The text every matching path contains, ignoring ASCII case, if the
+    /// pattern has any. Lets indexes leave out most entries up front.
+    pub(crate) fn needle(&self) -> Option<String> {
+        (!self.pattern.is_empty() && !is_glob(&self.pattern) && self.pattern.is_ascii())
+            .then(|| self.pattern.to_ascii_lowercase())
+    }
+}
+
+/// An entry matching a search.
+///
+/// Higher scores are closer matches: a name equal to the pattern scores 100,
+/// one starting with it 75, one where it starts a word 60, one merely
+/// holding it 50 and one matching a glob 40. Names holding the pattern with
+/// the same case score 5 more. Every entry scores 0 for an empty pattern.
+#[derive(Debug)]
+pub struct SearchHit {
+    entry: Entry,
+    score: u32,
+}
+
+impl SearchHit {
+    pub fn entry(&self) -> &Entry {
+        &self.entry
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+}
+
+impl<T: StorageBackend> Drive<T> {
+    /// Searches the entries within `query.within` matching `query`, best
+    /// matches first and then shallower entries first.
+    ///
+    /// An error will be returned if `query.within` does not correspond to a
+    /// directory.
+    pub async fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>> {
+        if !query.within.as_os_str().is_empty() {
+            let within = self.stat(&query.within).await?;
+            if !within.is_directory() {
+                return Err(DriveError::EntryUnexpectedType(format!(
+                    "{:?} is not an directory",
+                    within.path()
+                )));
+            }
+        }
+        let candidates = match &self.index {
+            Some(index) => index.search(query).await.map_err(DriveError::EntryIndex)?,
+            None => self.walk_entries(&query.within).await?,
+        };
+        let mut hits = candidates
+            .into_iter()
+            .filter(|(_, metadata)| query.filters(metadata))
+            .filter_map(|(path, metadata)| {
+                let score = query.score(path.file_name()?.to_str()?)?;
+                Some(SearchHit {
+                    entry: Entry::new(path, Some(metadata)),
+                    score,
+                })
+            })
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| {
+                    let depth = |hit: &SearchHit| hit.entry.path().components().count();
+                    depth(a).cmp(&depth(b))
+                })
+                .then_with(|| a.entry.path().cmp(b.entry.path()))
+        });
+        Ok(hits)
+    }
+
+    /// The entries within the directory `path`, found by walking the drive.
+    async fn walk_entries(&self, path: &Path) -> Result<Vec<(PathBuf, Metadata)>> {
+        let mut entries = vec![];
+        let mut pending = vec![path.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in self.entries(&dir).await? {
+                if entry.is_directory() && !entry.is_symlink() {
+                    pending.push(entry.path().clone());
+                }
+                let metadata = entry.metadata().cloned().unwrap_or_default();
+                entries.push((entry.path().clone(), metadata));
+            }
+        }
+        Ok(entries)
+    }
+}
@@ -0,0 +1,360 @@
+//! The trash of a drive, holding removed entries until they are restored or
+//! purged.
+//!
+//! A trashed entry is moved to `.mibox/trash/<id>` next to a
+//! `.mibox/trash/<id>.trashinfo` file recording where it came from and when
+//! it was removed, in the spirit of the freedesktop.org trash specification:
+//!
+//! ```text
+//! [Trash Info]
+//! Path=docs/report%20final.pdf
+//! DeletionDate=1718000000
+//! ```
+//!
+//! where the path is percent encoded and the date is in seconds since the
+//! Unix epoch.
+use crate::{
+    backend::StorageBackend,
+    entry::{Entry, Metadata},
+    error::DriveError,
+    reserved::{format_time, parse_time, time_id},
+    Drive, Result, RESERVED_DIR,
+};
+use std::{
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+
+const TRASH_DIR: &str = "trash";
+
+const INFO_EXTENSION: &str = "trashinfo";
+
+const INFO_HEADER: &str = "[Trash Info]";
+
+/// An entry of the trash.
+#[derive(Debug, Clone)]
+pub struct TrashItem {
+    id: String,
+    path: PathBuf,
+    deleted: SystemTime,
+    metadata: Metadata,
+}
+
+impl TrashItem {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The path the entry was removed from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn deleted(&self) -> SystemTime {
+        self.deleted
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.metadata.is_directory()
+    }
+
+    /// Size in bytes, zero for directories.
+    pub fn size(&self) -> u64 {
+        self.metadata.size()
+    }
+}
+
+fn trash_dir() -> PathBuf {
+    Path::new(RESERVED_DIR).join(TRASH_DIR)
+}
+
+fn info_path(id: &str) -> PathBuf {
+    trash_dir().join(format!("{}.{}", id, INFO_EXTENSION))
+}
+
+fn item_not_found(id: &str) -> DriveError {
+    DriveError::EntryNotFound(format!("{:?} is not in the trash", id))
+}
+
+/// `path` with ` (n)` appended to its file stem, e.g. `report (2).pdf`.
+fn numbered(path: &Path, n: u32) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{} ({}).{}", stem, n, extension.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+impl<T: StorageBackend> Drive<T> {
+    /// Moves a file to the trash.
+    ///
+    /// An error will be returned if the path does not correspond to a file.
+    pub async fn trash_file(&self, path: impl AsRef<Path>) -> Result<TrashItem> {
+        let entry = self.entry(path).await?;
+        if entry.is_directory() {
+            return Err(DriveError::EntryUnexpectedType(format!(
+                "{:?} is not a file",
+                entry.path()
+            )));
+        }
+        self.trash(entry).await
+    }
+
+    /// Moves a directory to the trash along with its contents, when
+    /// `recursive` is not set the directory must be empty.
+    ///
+    /// An error will be returned if the path does not correspond to a
+    /// directory or if it corresponds to the root of the drive.
+    pub async fn trash_directory(
+        &self,
+        path: impl AsRef<Path>,
+        recursive: bool,
+    ) -> Result<TrashItem> {
+        if path.as_ref().as_os_str().is_empty() {
+            return Err(DriveError::EntryNameInvalid(
+                "the root of the drive cannot be removed".to_string(),
+            ));
+        }
+        let entry = self.entry(path).await?;
+        if !entry.is_directory() {
+            return Err(DriveError::EntryUnexpectedType(format!(
+                "{:?} is not an directory",
+                entry.path()
+            )));
+        }
+        if !recursive && !self.entries(entry.path()).await?.is_empty() {
+            return Err(DriveError::EntryNotEmpty(format!(
+                "{:?} is not empty",
+                entry.path()
+            )));
+        }
+        self.trash(entry).await
+    }
+
+    async fn trash(&self, entry: Entry) -> Result<TrashItem> {
+        let path = entry.path().to_str().ok_or_else(|| {
+            DriveError::EntryNameInvalid(format!("{:?} is not valid UTF-8", entry.path()))
+        })?;
+        let deleted = SystemTime::now();
+        self.create_reserved_dir(&trash_dir()).await?;
+
+        // Ids are derived from the deletion time, bumped until unused.
+        let mut id = time_id();
+        while self
+            .exists_reserved(&trash_dir().join(id.to_string()))
+            .await?
+            || self.exists_reserved(&info_path(&id.to_string())).await?
+        {
+            id += 1;
+        }
+        let id = id.to_string();
+
+        // The info is written first so that a trashed entry is never left
+        // without one.
+        let fields = [("Path", path), ("DeletionDate", &format_time(deleted))];
+        self.write_record(&info_path(&id), INFO_HEADER, &fields)
+            .await?;
+        if let Err(e) = self
+            .backend
+            .rename(entry.path(), &trash_dir().join(&id))
+            .await
+        {
+            let _ = self.backend.remove_file(&info_path(&id)).await;
+            return Err(DriveError::EntryRename(e));
+        }
+        self.unindex(entry.path()).await?;
+        Ok(TrashItem {
+            id,
+            path: entry.path().clone(),
+            deleted,
+            metadata: entry.metadata().cloned().unwrap_or_default(),
+        })
+    }
+
+    /// Lists the entries of the trash, most recently removed first.
+    pub async fn trash_items(&self) -> Result<Vec<TrashItem>> {
+        if !self.exists_reserved(&trash_dir()).await? {
+            return Ok(vec![]);
+        }
+        let listing = self
+            .backend
+            .list(&trash_dir())
+            .await
+            .map_err(DriveError::EntryWalk)?;
+        let mut items = vec![];
+        for (path, metadata) in &listing {
+            if path
+                .extension()
+                .is_some_and(|extension| extension == INFO_EXTENSION)
+            {
+                continue;
+            }
+            let Some(id) = path.file_name().and_then(|id| id.to_str()) else {
+                continue;
+            };
+            if let Some(item) = self.read_item(id, metadata.clone()).await? {
+                items.push(item);
+            }
+        }
+        items.sort_by(|a, b| b.deleted.cmp(&a.deleted).then_with(|| b.id.cmp(&a.id)));
+        Ok(items)
+    }
+
+    /// Returns the entry `id` of the trash.
+    pub async fn trash_item(&self, id: &str) -> Result<TrashItem> {
+        let mut components = Path::new(id).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) || id.ends_with(INFO_EXTENSION)
+        {
+            return Err(item_not_found(id));
+        }
+        let metadata = self
+            .backend
+            .stat(&trash_dir().join(id))
+            .await
+            .map_err(DriveError::EntryMetadata)?
+            .ok_or_else(|| item_not_found(id))?;
+        self.read_item(id, metadata)
+            .await?
+            .ok_or_else(|| item_not_found(id))
+    }
+
+    /// Reads the info of the trashed entry `id`, `None` if it has none.
+    async fn read_item(&self, id: &str, metadata: Metadata) -> Result<Option<TrashItem>> {
+        let Some(info) = self.read_record(&info_path(id), INFO_HEADER).await? else {
+            return Ok(None);
+        };
+        let (Some(path), Some(deleted)) = (
+            info.get("Path"),
+            info.get("DeletionDate")
+                .and_then(|deleted| parse_time(deleted)),
+        ) else {
+            return Ok(None);
+        };
+        Ok(Some(TrashItem {
+            id: id.to_owned(),
+            path: PathBuf::from(path),
+            deleted,
+            metadata,
+        }))
+    }
+
+    /// Moves the entry `id` of the trash back to where it was removed from,
+    /// or to `to` when given, returning the path it was restored to.
+    ///
+    /// When an entry took the original path in the meantime the restored one
+    /// is renamed, e.g. to `report (1).pdf`, and the directories leading to
+    /// it are created again if they were removed. A given `to` must not
+    /// exist.
+    pub async fn restore(&self, id: &str, to: Option<&Path>) -> Result<PathBuf> {
+        let item = self.trash_item(id).await?;
+        let to = match to {
+            Some(to) => self.entry_non_existant(to).await?,
+            None => self.free_path(&item.path).await?,
+        };
+        if let Some(parent) = to.parent() {
+            self.create_ancestors(parent).await?;
+        }
+        self.backend
+            .rename(&trash_dir().join(id), &to)
+            .await
+            .map_err(DriveError::EntryRename)?;
+        let _ = self.backend.remove_file(&info_path(id)).await;
+        if item.is_directory() {
+            self.forget_versions(&to).await?;
+        }
+        self.index_tree(&to).await?;
+        Ok(to)
+    }
+
+    /// `path` if it is free, otherwise the first free numbered variant of it.
+    async fn free_path(&self, path: &Path) -> Result<PathBuf> {
+        let mut n = 0;
+        loop {
+            let candidate = if n == 0 {
+                path.to_path_buf()
+            } else {
+                numbered(path, n)
+            };
+            match self.entry_non_existant(&candidate).await {
+                Ok(path) => return Ok(path),
+                Err(DriveError::EntryExists(_)) => n += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Creates `path` and the directories leading to it when missing.
+    async fn create_ancestors(&self, path: &Path) -> Result<()> {
+        let mut ancestors = path.ancestors().collect::<Vec<_>>();
+        ancestors.reverse();
+        for ancestor in ancestors {
+            if ancestor.as_os_str().is_empty() {
+                continue;
+            }
+            let ancestor = self.entry_valid(ancestor).await?;
+            match self.backend.stat(&ancestor).await {
+                Ok(Some(metadata)) if metadata.is_directory() => {}
+                Ok(Some(_)) => {
+                    return Err(DriveError::EntryUnexpectedType(format!(
+                        "{:?} is not an directory",
+                        ancestor
+                    )))
+                }
+                Ok(None) => {
+                    self.backend
+                        .mkdir(&ancestor)
+                        .await
+                        .map_err(DriveError::EntryCreate)?;
+                    self.forget_versions(&ancestor).await?;
+                    self.index_entry(&ancestor, None).await?;
+                }
+                Err(e) => return Err(DriveError::EntryMetadata(e)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Permanently removes the entry `id` of the trash.
+    pub async fn purge(&self, id: &str) -> Result<()> {
+        let item = self.trash_item(id).await?;
+        let path = trash_dir().join(id);
+        if item.is_directory() {
+            self.backend.remove_dir(&path, true).await
+        } else {
+            self.backend.remove_file(&path).await
+        }
+        .map_err(DriveError::EntryRemove)?;
+        self.backend
+            .remove_file(&info_path(id))
+            .await
+            .map_err(DriveError::EntryRemove)
+    }
+
+    /// Permanently removes everything in the trash.
+    pub async fn empty_trash(&self) -> Result<()> {
+        if !self.exists_reserved(&trash_dir()).await? {
+            return Ok(());
+        }
+        self.backend
+            .remove_dir(&trash_dir(), true)
+            .await
+            .map_err(DriveError::EntryRemove)
+    }
+
+    /// Permanently removes the entries of the trash removed before
+    /// `removed_before`, returning how many were removed.
+    pub async fn expire_trash(&self, removed_before: SystemTime) -> Result<usize> {
+        let mut expired = 0;
+        for item in self.trash_items().await? {
+            if item.deleted < removed_before {
+                self.purge(&item.id).await?;
+                expired += 1;
+            }
+        }
+        Ok(expired)
+    }
+}
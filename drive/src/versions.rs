@@ -0,0 +1,395 @@
+//! Prior versions of the files of a drive, kept when files are overwritten
+//! by a drive with versioning (see `Drive::with_versioning`).
+//!
+//! The versions of `path` are kept in `.mibox/versions/<path>/`, each as an
+//! `<id>` file holding its contents next to an `<id>.versioninfo` record of
+//! who wrote it and when:
+//!
+//! ```text
+//! [Version Info]
+//! Author=alice
+//! Modified=1718000000
+//! Archived=1718003600
+//! ```
+//!
+//! The author of the current contents of `path` is recorded in
+//! `current.versioninfo` until they are overwritten in turn. The versions of
+//! `path` are dropped when a directory takes its place.
+//!
+//! The contents are copied before a file is overwritten but their record is
+//! only written once the new contents are, versions without a record are
+//! not listed.
+use crate::{
+    backend::{ByteStream, StorageBackend},
+    error::DriveError,
+    reserved::{format_time, parse_time, time_id},
+    Drive, Result, RESERVED_DIR,
+};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::pin;
+
+const VERSIONS_DIR: &str = "versions";
+
+const INFO_EXTENSION: &str = "versioninfo";
+
+const INFO_HEADER: &str = "[Version Info]";
+
+/// The record of the author of the current contents.
+const CURRENT: &str = "current";
+
+/// Which prior versions of a file are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionPolicy {
+    /// How many prior versions of a file are kept, none are when zero.
+    pub keep_last: usize,
+    /// How long prior versions are kept once superseded, forever when not
+    /// set.
+    pub keep_for: Option<Duration>,
+    /// The size in bytes above which files are overwritten without keeping
+    /// a version, whatever their size when not set.
+    pub max_size: Option<u64>,
+}
+
+impl Default for VersionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_last: 10,
+            keep_for: None,
+            max_size: Some(100 * 1024 * 1024),
+        }
+    }
+}
+
+/// The contents of a file copied before it is overwritten, which become a
+/// version once the new contents are written (see `Drive::record_version`).
+pub(crate) struct Snapshot {
+    id: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+/// A prior version of a file.
+#[derive(Debug, Clone)]
+pub struct Version {
+    id: String,
+    size: u64,
+    modified: Option<SystemTime>,
+    archived: SystemTime,
+    author: Option<String>,
+}
+
+impl Version {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// When the contents of the version were written.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// When the version was superseded.
+    pub fn archived(&self) -> SystemTime {
+        self.archived
+    }
+
+    /// Who wrote the contents of the version, if known.
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+}
+
+fn versions_dir(path: &Path) -> PathBuf {
+    Path::new(RESERVED_DIR).join(VERSIONS_DIR).join(path)
+}
+
+fn info_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.{}", id, INFO_EXTENSION))
+}
+
+fn is_version_id(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+fn version_not_found(path: &Path, id: &str) -> DriveError {
+    DriveError::EntryNotFound(format!("{:?} has no version {:?}", path, id))
+}
+
+impl<T: StorageBackend> Drive<T> {
+    /// Copies the current contents of `path` before they are overwritten,
+    /// `None` if there are none to keep.
+    pub(crate) async fn keep_version(
+        &self,
+        path: &Path,
+        policy: &VersionPolicy,
+    ) -> Result<Option<Snapshot>> {
+        if policy.keep_last == 0 {
+            return Ok(None);
+        }
+        let metadata = match self.backend.stat(path).await {
+            Ok(Some(metadata)) if !metadata.is_directory() => metadata,
+            Ok(_) => return Ok(None),
+            Err(e) => return Err(DriveError::EntryMetadata(e)),
+        };
+        if policy
+            .max_size
+            .is_some_and(|max_size| metadata.size() > max_size)
+        {
+            return Ok(None);
+        }
+        let dir = versions_dir(path);
+        self.create_reserved_dir(&dir).await?;
+        let mut id = time_id();
+        while self.exists_reserved(&dir.join(id.to_string())).await?
+            || self
+                .exists_reserved(&info_path(&dir, &id.to_string()))
+                .await?
+        {
+            id += 1;
+        }
+        let id = id.to_string();
+
+        let content = self
+            .backend
+            .read(path)
+            .await
+            .map_err(DriveError::EntryMetadata)?;
+        pin! {
+            let reader = tokio_util::io::StreamReader::new(content);
+        };
+        self.backend
+            .write(&dir.join(&id), reader)
+            .await
+            .map_err(DriveError::EntryWrite)?;
+
+        let author = self
+            .read_record(&info_path(&dir, CURRENT), INFO_HEADER)
+            .await?
+            .and_then(|mut current| current.remove("Author"));
+        let fields = [
+            author.map(|author| ("Author", author)),
+            metadata
+                .modified()
+                .map(|modified| ("Modified", format_time(modified))),
+            Some(("Archived", format_time(SystemTime::now()))),
+        ];
+        Ok(Some(Snapshot {
+            id,
+            fields: fields.into_iter().flatten().collect(),
+        }))
+    }
+
+    /// Removes the copy of the contents of `path` taken by `keep_version`,
+    /// when they could not be overwritten.
+    pub(crate) async fn discard_version(&self, path: &Path, snapshot: Snapshot) -> Result<()> {
+        self.backend
+            .remove_file(&versions_dir(path).join(snapshot.id))
+            .await
+            .map_err(DriveError::EntryRemove)
+    }
+
+    /// Makes a version of the contents `snapshot` copied before `path` was
+    /// overwritten, records the author of the contents just written and
+    /// drops the versions `policy` no longer keeps.
+    pub(crate) async fn record_version(
+        &self,
+        path: &Path,
+        policy: &VersionPolicy,
+        snapshot: Option<Snapshot>,
+    ) -> Result<()> {
+        let dir = versions_dir(path);
+        if let Some(snapshot) = snapshot {
+            let fields = snapshot
+                .fields
+                .iter()
+                .map(|(name, value)| (*name, value.as_str()))
+                .collect::<Vec<_>>();
+            self.write_record(&info_path(&dir, &snapshot.id), INFO_HEADER, &fields)
+                .await?;
+        }
+        match &self.author {
+            Some(author) => {
+                self.create_reserved_dir(&dir).await?;
+                let fields = [("Author", author.as_str())];
+                self.write_record(&info_path(&dir, CURRENT), INFO_HEADER, &fields)
+                    .await?;
+            }
+            None if self.exists_reserved(&info_path(&dir, CURRENT)).await? => {
+                self.backend
+                    .remove_file(&info_path(&dir, CURRENT))
+                    .await
+                    .map_err(DriveError::EntryRemove)?;
+            }
+            None => {}
+        }
+        self.prune_versions(&dir, policy).await?;
+        Ok(())
+    }
+
+    /// Drops the versions kept for the file formerly at `path` once a
+    /// directory takes its place, so that they are not mixed with those of
+    /// a later file. The versions of the entries within are left alone.
+    pub(crate) async fn forget_versions(&self, path: &Path) -> Result<()> {
+        let dir = versions_dir(path);
+        if !self.exists_reserved(&dir).await? {
+            return Ok(());
+        }
+        let listing = self
+            .backend
+            .list(&dir)
+            .await
+            .map_err(DriveError::EntryWalk)?;
+        for (path, metadata) in listing {
+            if metadata.is_directory() {
+                continue;
+            }
+            self.backend
+                .remove_file(&path)
+                .await
+                .map_err(DriveError::EntryRemove)?;
+        }
+        Ok(())
+    }
+
+    /// The versions kept in `dir`, most recent first.
+    async fn versions_in(&self, dir: &Path) -> Result<Vec<Version>> {
+        if !self.exists_reserved(dir).await? {
+            return Ok(vec![]);
+        }
+        let listing = self
+            .backend
+            .list(dir)
+            .await
+            .map_err(DriveError::EntryWalk)?;
+        let mut versions = vec![];
+        for (path, metadata) in listing {
+            let Some(id) = path.file_name().and_then(|id| id.to_str()) else {
+                continue;
+            };
+            if metadata.is_directory() || !is_version_id(id) {
+                continue;
+            }
+            let Some(mut info) = self.read_record(&info_path(dir, id), INFO_HEADER).await? else {
+                continue;
+            };
+            let Some(archived) = info.get("Archived").and_then(|time| parse_time(time)) else {
+                continue;
+            };
+            versions.push(Version {
+                id: id.to_owned(),
+                size: metadata.size(),
+                modified: info.get("Modified").and_then(|time| parse_time(time)),
+                archived,
+                author: info.remove("Author"),
+            });
+        }
+        versions.sort_by_key(|version| std::cmp::Reverse(version.id.parse::<u128>().ok()));
+        Ok(versions)
+    }
+
+    /// Removes the versions in `dir` that `policy` no longer keeps,
+    /// returning how many were removed.
+    async fn prune_versions(&self, dir: &Path, policy: &VersionPolicy) -> Result<usize> {
+        let now = SystemTime::now();
+        let mut pruned = 0;
+        for (index, version) in self.versions_in(dir).await?.iter().enumerate() {
+            let expired = policy
+                .keep_for
+                .is_some_and(|keep_for| version.archived + keep_for < now);
+            if index < policy.keep_last && !expired {
+                continue;
+            }
+            self.backend
+                .remove_file(&dir.join(&version.id))
+                .await
+                .map_err(DriveError::EntryRemove)?;
+            self.backend
+                .remove_file(&info_path(dir, &version.id))
+                .await
+                .map_err(DriveError::EntryRemove)?;
+            pruned += 1;
+        }
+        Ok(pruned)
+    }
+
+    /// Lists the prior versions of a file, most recent first.
+    ///
+    /// The versions of a file are kept by path, they outlive the file when
+    /// it is removed and are listed for whatever file later takes its path,
+    /// unless a directory took it in between.
+    pub async fn versions(&self, path: impl AsRef<Path>) -> Result<Vec<Version>> {
+        let path = self.entry_valid(path).await?;
+        self.versions_in(&versions_dir(&path)).await
+    }
+
+    /// Returns the version `id` of a file.
+    pub async fn version(&self, path: impl AsRef<Path>, id: &str) -> Result<Version> {
+        let path = self.entry_valid(path).await?;
+        if !is_version_id(id) {
+            return Err(version_not_found(&path, id));
+        }
+        self.versions_in(&versions_dir(&path))
+            .await?
+            .into_iter()
+            .find(|version| version.id == id)
+            .ok_or_else(|| version_not_found(&path, id))
+    }
+
+    /// Reads the version `id` of a file as a stream.
+    pub async fn read_version(&self, path: impl AsRef<Path>, id: &str) -> Result<ByteStream> {
+        let version = self.version(path.as_ref(), id).await?;
+        let dir = versions_dir(path.as_ref());
+        self.backend
+            .read(&dir.join(version.id()))
+            .await
+            .map_err(DriveError::EntryMetadata)
+    }
+
+    /// Makes the version `id` of a file its current contents.
+    ///
+    /// The contents it replaces are kept as a version in turn, so that
+    /// restoring can be undone.
+    pub async fn restore_version(&self, path: impl AsRef<Path>, id: &str) -> Result<()> {
+        let content = self.read_version(path.as_ref(), id).await?;
+        self.write(content, path).await
+    }
+
+    /// Removes the versions of every file that the versioning policy no
+    /// longer keeps, returning how many were removed.
+    ///
+    /// Versions are otherwise only pruned when their file is written, which
+    /// leaves the versions of files that are no longer written past the
+    /// time they are kept for.
+    pub async fn expire_versions(&self) -> Result<usize> {
+        let Some(policy) = &self.versioning else {
+            return Ok(0);
+        };
+        let root = Path::new(RESERVED_DIR).join(VERSIONS_DIR);
+        if !self.exists_reserved(&root).await? {
+            return Ok(0);
+        }
+        let mut pruned = 0;
+        let mut pending = vec![root];
+        while let Some(dir) = pending.pop() {
+            let listing = self
+                .backend
+                .list(&dir)
+                .await
+                .map_err(DriveError::EntryWalk)?;
+            pending.extend(
+                listing
+                    .into_iter()
+                    .filter(|(_, metadata)| metadata.is_directory())
+                    .map(|(path, _)| path),
+            );
+            pruned += self.prune_versions(&dir, policy).await?;
+        }
+        Ok(pruned)
+    }
+}
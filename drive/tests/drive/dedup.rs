@@ -0,0 +1,188 @@
+use drive::{
+    backend::{Backend, DedupBackend, MemoryBackend, StorageBackend, INLINE_LENGTH},
+    Drive,
+};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tokio_stream::StreamExt;
+
+fn content(bytes: &[u8]) -> impl futures_core::Stream<Item = std::io::Result<bytes::Bytes>> {
+    tokio_stream::iter(vec![Ok(bytes::Bytes::copy_from_slice(bytes))])
+}
+
+fn large(byte: u8) -> Vec<u8> {
+    vec![byte; INLINE_LENGTH as usize * 4]
+}
+
+async fn read<T: StorageBackend>(drive: &Drive<T>, path: &str) -> Vec<u8> {
+    let mut stream = drive.read(path).await.unwrap();
+    let mut content = vec![];
+    while let Some(chunk) = stream.next().await {
+        content.extend_from_slice(&chunk.unwrap());
+    }
+    content
+}
+
+/// The blobs kept in the blob store of `backend`.
+async fn blobs(backend: &MemoryBackend) -> usize {
+    let blobs_dir = Path::new(".mibox/blobs");
+    if backend.stat(blobs_dir).await.unwrap().is_none() {
+        return 0;
+    }
+    let mut blobs = 0;
+    for (shard, metadata) in backend.list(blobs_dir).await.unwrap() {
+        if !metadata.is_directory() || shard.ends_with("staging") {
+            continue;
+        }
+        for (path, _) in backend.list(&shard).await.unwrap() {
+            if path.extension().is_none() {
+                blobs += 1;
+            }
+        }
+    }
+    blobs
+}
+
+#[tokio::test]
+async fn identical_files_are_stored_once() {
+    let storage = MemoryBackend::new();
+    let drive = Drive::with_backend(DedupBackend::new(storage.clone()));
+    drive.create_directory("dir").await.unwrap();
+    drive.write(content(&large(1)), "a.bin").await.unwrap();
+    drive.write(content(&large(1)), "dir/b.bin").await.unwrap();
+    drive.copy_file("a.bin", "dir/c.bin").await.unwrap();
+    assert_eq!(blobs(&storage).await, 1);
+
+    for path in ["a.bin", "dir/b.bin", "dir/c.bin"] {
+        assert_eq!(read(&drive, path).await, large(1));
+        assert_eq!(
+            drive.stat(path).await.unwrap().size(),
+            large(1).len() as u64
+        );
+    }
+    let mut stream = drive.read_range("dir/b.bin", 10..20).await.unwrap();
+    let mut range = vec![];
+    while let Some(chunk) = stream.next().await {
+        range.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(range, vec![1; 10]);
+}
+
+#[tokio::test]
+async fn blobs_are_removed_with_their_last_reference() {
+    let storage = MemoryBackend::new();
+    let drive = Drive::with_backend(DedupBackend::new(storage.clone()));
+    drive.create_directory("dir").await.unwrap();
+    drive.write(content(&large(1)), "a.bin").await.unwrap();
+    drive.write(content(&large(1)), "dir/b.bin").await.unwrap();
+
+    drive.remove_file("a.bin").await.unwrap();
+    assert_eq!(blobs(&storage).await, 1);
+    assert_eq!(read(&drive, "dir/b.bin").await, large(1));
+    drive.remove_directory("dir", true).await.unwrap();
+    assert_eq!(blobs(&storage).await, 0);
+}
+
+#[tokio::test]
+async fn overwriting_a_file_releases_its_previous_contents() {
+    let storage = MemoryBackend::new();
+    let drive = Drive::with_backend(DedupBackend::new(storage.clone()));
+    drive.write(content(&large(1)), "a.bin").await.unwrap();
+    drive.write(content(&large(2)), "a.bin").await.unwrap();
+    assert_eq!(blobs(&storage).await, 1);
+    assert_eq!(read(&drive, "a.bin").await, large(2));
+
+    drive.write(content(b"small"), "a.bin").await.unwrap();
+    assert_eq!(blobs(&storage).await, 0);
+    assert_eq!(read(&drive, "a.bin").await, b"small");
+}
+
+#[tokio::test]
+async fn small_files_are_stored_as_they_are() {
+    let storage = MemoryBackend::new();
+    let drive = Drive::with_backend(DedupBackend::new(storage.clone()));
+    drive.write(content(b"hello"), "a.txt").await.unwrap();
+    assert_eq!(blobs(&storage).await, 0);
+    assert_eq!(read(&Drive::with_backend(storage), "a.txt").await, b"hello");
+
+    // Contents which could be taken for a pointer go to the blob store.
+    let pointer = format!("[Blob]\nHash={}\nSize=5\n", "0".repeat(64));
+    drive
+        .write(content(pointer.as_bytes()), "b.txt")
+        .await
+        .unwrap();
+    assert_eq!(read(&drive, "b.txt").await, pointer.as_bytes());
+}
+
+#[tokio::test]
+async fn scoped_backends_share_the_blob_store() {
+    let storage = MemoryBackend::new();
+    let backend = Backend::Memory(storage.clone()).deduplicated();
+    backend.mkdir(Path::new("alice")).await.unwrap();
+    backend.mkdir(Path::new("bob")).await.unwrap();
+    let alice = Drive::with_backend(backend.scoped("alice"));
+    let bob = Drive::with_backend(backend.scoped("bob"));
+    alice.write(content(&large(1)), "a.bin").await.unwrap();
+    bob.write(content(&large(1)), "b.bin").await.unwrap();
+    assert_eq!(blobs(&storage).await, 1);
+    assert_eq!(read(&bob, "b.bin").await, large(1));
+
+    alice.remove_file("a.bin").await.unwrap();
+    assert_eq!(read(&bob, "b.bin").await, large(1));
+}
+
+#[tokio::test]
+async fn garbage_collection_removes_unreferenced_blobs() {
+    let storage = MemoryBackend::new();
+    let backend = DedupBackend::new(storage.clone());
+    let drive = Drive::with_backend(backend.clone());
+    drive.write(content(&large(1)), "a.bin").await.unwrap();
+    drive.write(content(&large(2)), "b.bin").await.unwrap();
+    // Removing a pointer behind the backend's back leaves its blob behind.
+    storage.remove_file(Path::new("b.bin")).await.unwrap();
+    assert_eq!(blobs(&storage).await, 2);
+
+    assert_eq!(backend.collect_garbage().await.unwrap(), 1);
+    assert_eq!(blobs(&storage).await, 1);
+    assert_eq!(read(&drive, "a.bin").await, large(1));
+}
+
+#[tokio::test]
+async fn pointers_cannot_be_forged_to_read_the_blobs_of_other_homes() {
+    let storage = MemoryBackend::new();
+    let backend = Backend::Memory(storage.clone()).deduplicated();
+    backend.mkdir(Path::new("alice")).await.unwrap();
+    backend.mkdir(Path::new("bob")).await.unwrap();
+    let alice = Drive::with_backend(backend.scoped("alice"));
+    let bob = Drive::with_backend(backend.scoped("bob"));
+    bob.write(content(&large(7)), "secret.bin").await.unwrap();
+
+    // Files written behind the backend's back, e.g. before it was
+    // deduplicated, may look like pointers to the blobs of others.
+    let hash = format!("{:x}", Sha256::digest(large(7)));
+    let size = large(7).len();
+    let forged = [
+        format!("[Blob]\nHash={}\nSize={}\n", hash, size),
+        format!(
+            "[Blob]\nHash={}\nSize={}\nKey={}\n",
+            hash,
+            size,
+            "a".repeat(32)
+        ),
+    ];
+    for (index, forged) in forged.iter().enumerate() {
+        let path = format!("forged-{}.txt", index);
+        storage
+            .write(&Path::new("alice").join(&path), forged.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(read(&alice, &path).await, forged.as_bytes());
+        assert_eq!(alice.stat(&path).await.unwrap().size(), forged.len() as u64);
+        alice.remove_file(&path).await.unwrap();
+    }
+    let Backend::Dedup(dedup) = &backend else {
+        panic!("the backend is deduplicated");
+    };
+    assert_eq!(dedup.collect_garbage().await.unwrap(), 0);
+    assert_eq!(read(&bob, "secret.bin").await, large(7));
+}
@@ -0,0 +1,85 @@
+use crate::helpers::UnreachableBackend;
+use drive::{error::DriveError, is_valid_path, Drive};
+
+const INVALID_PATHS: [&str; 5] = ["/", "/etc", "..", "a/../../b", "./a"];
+
+#[test]
+fn path_walks_are_not_valid_paths() {
+    for path in INVALID_PATHS {
+        assert!(!is_valid_path(path));
+    }
+    assert!(is_valid_path("a/b.txt"));
+}
+
+#[tokio::test]
+async fn create_directory_rejects_path_walks() {
+    let drive = Drive::with_backend(UnreachableBackend);
+    for path in INVALID_PATHS {
+        let result = drive.create_directory(path).await;
+        assert!(matches!(result, Err(DriveError::EntryNameInvalid(_))));
+    }
+}
+
+#[tokio::test]
+async fn entries_rejects_path_walks() {
+    let drive = Drive::with_backend(UnreachableBackend);
+    for path in INVALID_PATHS {
+        let result = drive.entries(path).await;
+        assert!(matches!(result, Err(DriveError::EntryNameInvalid(_))));
+    }
+}
+
+#[tokio::test]
+async fn read_rejects_path_walks() {
+    let drive = Drive::with_backend(UnreachableBackend);
+    for path in INVALID_PATHS {
+        let result = drive.read(path).await;
+        assert!(matches!(result, Err(DriveError::EntryNameInvalid(_))));
+    }
+}
+
+#[tokio::test]
+async fn read_range_rejects_path_walks() {
+    let drive = Drive::with_backend(UnreachableBackend);
+    for path in INVALID_PATHS {
+        let result = drive.read_range(path, 0..1).await;
+        assert!(matches!(result, Err(DriveError::EntryNameInvalid(_))));
+    }
+}
+
+#[tokio::test]
+async fn write_rejects_path_walks() {
+    let drive = Drive::with_backend(UnreachableBackend);
+    for path in INVALID_PATHS {
+        let stream = tokio_stream::iter(vec![Ok::<_, std::io::Error>(bytes::Bytes::new())]);
+        let result = drive.write(stream, path).await;
+        assert!(matches!(result, Err(DriveError::EntryNameInvalid(_))));
+    }
+}
+
+#[tokio::test]
+async fn rename_directory_rejects_path_walks() {
+    let drive = Drive::with_backend(UnreachableBackend);
+    for path in INVALID_PATHS {
+        let result = drive.rename_directory(path, "valid").await;
+        assert!(matches!(result, Err(DriveError::EntryNameInvalid(_))));
+    }
+}
+
+#[tokio::test]
+async fn remove_file_rejects_path_walks() {
+    let drive = Drive::with_backend(UnreachableBackend);
+    for path in INVALID_PATHS {
+        let result = drive.remove_file(path).await;
+        assert!(matches!(result, Err(DriveError::EntryNameInvalid(_))));
+    }
+}
+
+#[tokio::test]
+async fn remove_directory_rejects_path_walks_and_the_root() {
+    let drive = Drive::with_backend(UnreachableBackend);
+    for path in INVALID_PATHS.into_iter().chain([""]) {
+        let result = drive.remove_directory(path, true).await;
+        assert!(matches!(result, Err(DriveError::EntryNameInvalid(_))));
+    }
+}
@@ -0,0 +1,209 @@
+//! A minimal S3 compatible server covering the API used by `S3Backend`.
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{any, get},
+    Router,
+};
+use drive::backend::{S3Backend, S3Options};
+use percent_encoding::percent_decode_str;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+#[derive(Default)]
+struct Bucket {
+    objects: BTreeMap<String, Bytes>,
+    uploads: HashMap<String, BTreeMap<usize, Bytes>>,
+    next_upload: usize,
+}
+
+type SharedBucket = Arc<Mutex<Bucket>>;
+
+/// Starts a fake server and returns a backend storing its drive under
+/// `prefix` in the `mibox` bucket.
+pub async fn spawn_s3(prefix: &str) -> S3Backend {
+    let bucket = SharedBucket::default();
+    let router = Router::new()
+        .route("/:bucket", get(list_objects))
+        .route("/:bucket/*key", any(object))
+        .with_state(bucket);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    let options = S3Options {
+        region: Some("us-east-1".to_string()),
+        endpoint: Some(format!("http://{}", address)),
+        access_key_id: Some("mibox".to_string()),
+        secret_access_key: Some("mibox-secret".to_string()),
+        allow_http: true,
+    };
+    S3Backend::from_url(&format!("s3://mibox/{}", prefix), &options).unwrap()
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn etag(content: &[u8]) -> String {
+    format!("\"{:x}\"", content.len())
+}
+
+async fn list_objects(
+    State(bucket): State<SharedBucket>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let bucket = bucket.lock().unwrap();
+    let prefix = query.get("prefix").cloned().unwrap_or_default();
+    let delimiter = query.get("delimiter");
+    let mut contents = String::new();
+    let mut prefixes = Vec::<String>::new();
+    for (key, content) in bucket.objects.range(prefix.clone()..) {
+        let Some(rest) = key.strip_prefix(&prefix) else {
+            break;
+        };
+        match (delimiter, rest.find('/')) {
+            (Some(_), Some(index)) => {
+                let common = format!("{}{}", prefix, &rest[..=index]);
+                if !prefixes.contains(&common) {
+                    prefixes.push(common);
+                }
+            }
+            _ => contents.push_str(&format!(
+                "<Contents><Key>{}</Key><Size>{}</Size>\
+                 <LastModified>2024-01-01T00:00:00.000Z</LastModified></Contents>",
+                escape(key),
+                content.len()
+            )),
+        }
+    }
+    let prefixes = prefixes
+        .iter()
+        .map(|prefix| {
+            format!(
+                "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+                escape(prefix)
+            )
+        })
+        .collect::<String>();
+    format!(
+        "<ListBucketResult>{}{}</ListBucketResult>",
+        contents, prefixes
+    )
+    .into_response()
+}
+
+async fn object(
+    State(bucket): State<SharedBucket>,
+    method: Method,
+    Path((_, key)): Path<(String, String)>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let mut bucket = bucket.lock().unwrap();
+    match method {
+        Method::GET | Method::HEAD => match bucket.objects.get(&key) {
+            Some(content) => get_object(content, &headers),
+            None => StatusCode::NOT_FOUND.into_response(),
+        },
+        Method::PUT => {
+            if let Some(source) = headers.get("x-amz-copy-source") {
+                let source = percent_decode_str(source.to_str().unwrap()).decode_utf8_lossy();
+                let (_, source) = source.split_once('/').unwrap();
+                let Some(content) = bucket.objects.get(source).cloned() else {
+                    return StatusCode::NOT_FOUND.into_response();
+                };
+                bucket.objects.insert(key, content);
+                return "<CopyObjectResult></CopyObjectResult>".into_response();
+            }
+            let tag = etag(&body);
+            match (query.get("uploadId"), query.get("partNumber")) {
+                (Some(upload), Some(part)) => {
+                    let Some(parts) = bucket.uploads.get_mut(upload) else {
+                        return StatusCode::NOT_FOUND.into_response();
+                    };
+                    parts.insert(part.parse().unwrap(), body);
+                }
+                _ => {
+                    bucket.objects.insert(key, body);
+                }
+            }
+            ([(header::ETAG, tag)], StatusCode::OK).into_response()
+        }
+        Method::POST if query.contains_key("uploads") => {
+            bucket.next_upload += 1;
+            let upload = bucket.next_upload.to_string();
+            bucket.uploads.insert(upload.clone(), BTreeMap::new());
+            format!(
+                "<InitiateMultipartUploadResult><UploadId>{}</UploadId></InitiateMultipartUploadResult>",
+                upload
+            )
+            .into_response()
+        }
+        Method::POST => {
+            let Some(parts) = query
+                .get("uploadId")
+                .and_then(|id| bucket.uploads.remove(id))
+            else {
+                return StatusCode::NOT_FOUND.into_response();
+            };
+            let content = parts.into_values().flatten().collect::<Vec<u8>>();
+            let tag = etag(&content);
+            bucket.objects.insert(key, content.into());
+            format!(
+                "<CompleteMultipartUploadResult><ETag>{}</ETag></CompleteMultipartUploadResult>",
+                tag
+            )
+            .into_response()
+        }
+        Method::DELETE => {
+            match query.get("uploadId") {
+                Some(upload) => {
+                    bucket.uploads.remove(upload);
+                }
+                None => {
+                    bucket.objects.remove(&key);
+                }
+            }
+            StatusCode::NO_CONTENT.into_response()
+        }
+        _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+    }
+}
+
+fn get_object(content: &Bytes, headers: &HeaderMap) -> Response {
+    let tag = etag(content);
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok())
+        .and_then(|range| range.strip_prefix("bytes="))
+        .and_then(|range| range.split_once('-'));
+    let Some((start, end)) = range else {
+        return ([(header::ETAG, tag)], content.clone()).into_response();
+    };
+    let start = start.parse::<usize>().unwrap_or(0);
+    let end = end
+        .parse::<usize>()
+        .map(|end| end.min(content.len() - 1))
+        .unwrap_or(content.len() - 1);
+    (
+        StatusCode::PARTIAL_CONTENT,
+        [
+            (header::ETAG, tag),
+            (
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, content.len()),
+            ),
+        ],
+        content.slice(start..=end),
+    )
+        .into_response()
+}
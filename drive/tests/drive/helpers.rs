@@ -0,0 +1,51 @@
+use drive::{
+    backend::{ByteStream, StorageBackend},
+    entry::Metadata,
+};
+use std::{
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use tokio::io::AsyncRead;
+
+/// Backend that fails the test whenever the drive reaches the storage layer.
+pub struct UnreachableBackend;
+
+impl StorageBackend for UnreachableBackend {
+    async fn list(&self, path: &Path) -> io::Result<Vec<(PathBuf, Metadata)>> {
+        panic!("backend reached listing {:?}", path)
+    }
+
+    async fn stat(&self, path: &Path) -> io::Result<Option<Metadata>> {
+        panic!("backend reached stat of {:?}", path)
+    }
+
+    async fn read(&self, path: &Path) -> io::Result<ByteStream> {
+        panic!("backend reached read of {:?}", path)
+    }
+
+    async fn read_range(&self, path: &Path, _range: Range<u64>) -> io::Result<ByteStream> {
+        panic!("backend reached read of {:?}", path)
+    }
+
+    async fn write<R: AsyncRead + Send + Unpin>(&self, path: &Path, _reader: R) -> io::Result<()> {
+        panic!("backend reached write of {:?}", path)
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        panic!("backend reached rename of {:?} to {:?}", from, to)
+    }
+
+    async fn remove_file(&self, path: &Path) -> io::Result<()> {
+        panic!("backend reached removal of {:?}", path)
+    }
+
+    async fn remove_dir(&self, path: &Path, _recursive: bool) -> io::Result<()> {
+        panic!("backend reached removal of {:?}", path)
+    }
+
+    async fn mkdir(&self, path: &Path) -> io::Result<()> {
+        panic!("backend reached creation of {:?}", path)
+    }
+}
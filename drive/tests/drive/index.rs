@@ -0,0 +1,174 @@
+use drive::{
+    backend::{MemoryBackend, StorageBackend},
+    error::DriveError,
+    index::Index,
+    Drive,
+};
+use std::path::Path;
+
+fn content(bytes: &[u8]) -> impl futures_core::Stream<Item = std::io::Result<bytes::Bytes>> {
+    tokio_stream::iter(vec![Ok(bytes::Bytes::copy_from_slice(bytes))])
+}
+
+fn indexed_drive() -> (MemoryBackend, Drive<MemoryBackend>) {
+    let storage = MemoryBackend::new();
+    let drive = Drive::with_backend(storage.clone()).with_index(Index::open_in_memory().unwrap());
+    (storage, drive)
+}
+
+async fn names(drive: &Drive<MemoryBackend>, path: &str) -> Vec<String> {
+    let mut names = drive
+        .entries(path)
+        .await
+        .unwrap()
+        .iter()
+        .filter_map(|entry| entry.name())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn written_files_are_indexed_with_their_hash_and_type() {
+    let (_, drive) = indexed_drive();
+    drive.write(content(b"hello"), "a.txt").await.unwrap();
+    drive
+        .write(content(b"\x89PNG\r\n\x1a\n...."), "picture")
+        .await
+        .unwrap();
+
+    let entry = drive.stat("a.txt").await.unwrap();
+    assert_eq!(entry.size(), 5);
+    assert!(entry.modified().is_some());
+    assert_eq!(
+        entry.hash(),
+        Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+    );
+    assert_eq!(entry.mime_type().as_deref(), Some("text/plain"));
+    let entry = drive.stat("picture").await.unwrap();
+    assert_eq!(entry.mime_type().as_deref(), Some("image/png"));
+}
+
+#[tokio::test]
+async fn listings_are_served_from_the_index() {
+    let (storage, drive) = indexed_drive();
+    drive.create_directory("dir").await.unwrap();
+    drive.write(content(b"a"), "dir/a.txt").await.unwrap();
+    drive.write(content(b"b"), "dir/b.txt").await.unwrap();
+    // Changes behind the back of the drive are not seen...
+    storage.remove_file(Path::new("dir/b.txt")).await.unwrap();
+    assert_eq!(names(&drive, "dir").await, ["a.txt", "b.txt"]);
+    assert!(drive.stat("dir/b.txt").await.is_ok());
+
+    // ...until the index is rebuilt.
+    assert_eq!(drive.rebuild_index().await.unwrap(), 2);
+    assert_eq!(names(&drive, "dir").await, ["a.txt"]);
+    assert!(matches!(
+        drive.stat("dir/b.txt").await,
+        Err(DriveError::EntryNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn renames_move_the_entries_within() {
+    let (_, drive) = indexed_drive();
+    drive.create_directory("dir").await.unwrap();
+    drive.create_directory("dir/sub").await.unwrap();
+    drive.write(content(b"a"), "dir/sub/a.txt").await.unwrap();
+    drive.rename_directory("dir", "moved").await.unwrap();
+    drive
+        .rename_file("moved/sub/a.txt", "moved/b.txt")
+        .await
+        .unwrap();
+
+    assert_eq!(names(&drive, "").await, ["moved"]);
+    assert_eq!(names(&drive, "moved").await, ["b.txt", "sub"]);
+    assert!(names(&drive, "moved/sub").await.is_empty());
+    assert!(drive.stat("dir/sub").await.is_err());
+}
+
+#[tokio::test]
+async fn removals_and_copies_are_indexed() {
+    let (_, drive) = indexed_drive();
+    drive.create_directory("dir").await.unwrap();
+    drive.create_directory("dir/sub").await.unwrap();
+    drive.write(content(b"a"), "dir/sub/a.txt").await.unwrap();
+    drive.copy_directory("dir", "copy").await.unwrap();
+    assert_eq!(names(&drive, "copy/sub").await, ["a.txt"]);
+    assert_eq!(
+        drive.stat("copy/sub/a.txt").await.unwrap().hash(),
+        drive.stat("dir/sub/a.txt").await.unwrap().hash()
+    );
+
+    drive.remove_file("copy/sub/a.txt").await.unwrap();
+    assert!(names(&drive, "copy/sub").await.is_empty());
+    drive.remove_directory("dir", true).await.unwrap();
+    assert_eq!(names(&drive, "").await, ["copy"]);
+    assert!(drive.stat("dir/sub/a.txt").await.is_err());
+}
+
+#[tokio::test]
+async fn trashed_entries_leave_the_index_until_restored() {
+    let (_, drive) = indexed_drive();
+    drive.create_directory("dir").await.unwrap();
+    drive.write(content(b"a"), "dir/a.txt").await.unwrap();
+    let item = drive.trash_directory("dir", true).await.unwrap();
+    assert!(names(&drive, "").await.is_empty());
+
+    drive.restore(item.id(), None).await.unwrap();
+    assert_eq!(names(&drive, "dir").await, ["a.txt"]);
+    assert!(drive.stat("dir/a.txt").await.unwrap().hash().is_some());
+}
+
+#[tokio::test]
+async fn scoped_indexes_are_kept_apart() {
+    let index = Index::open_in_memory().unwrap();
+    let storage = MemoryBackend::new();
+    storage.mkdir(Path::new("alice")).await.unwrap();
+    storage.mkdir(Path::new("bob")).await.unwrap();
+    let alice = Drive::with_backend(storage.scoped("alice")).with_index(index.scoped("alice"));
+    let bob = Drive::with_backend(storage.scoped("bob")).with_index(index.scoped("bob"));
+    alice.write(content(b"a"), "a.txt").await.unwrap();
+    bob.write(content(b"b"), "b.txt").await.unwrap();
+
+    assert_eq!(names(&alice, "").await, ["a.txt"]);
+    assert_eq!(names(&bob, "").await, ["b.txt"]);
+}
+
+#[tokio::test]
+async fn indexes_persist_in_their_database() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.sqlite3");
+    let storage = MemoryBackend::new();
+    let drive = Drive::with_backend(storage.clone()).with_index(Index::open(&path).unwrap());
+    assert!(drive.index().unwrap().is_empty().await.unwrap());
+    drive.write(content(b"a"), "a.txt").await.unwrap();
+
+    let index = Index::open(&path).unwrap();
+    assert!(!index.is_empty().await.unwrap());
+    let drive = Drive::with_backend(storage).with_index(index);
+    assert_eq!(names(&drive, "").await, ["a.txt"]);
+}
+
+#[tokio::test]
+async fn linked_directories_are_indexed_once() {
+    let base = tempfile::tempdir().unwrap();
+    let drive = Drive::new(base.path()).with_index(Index::open_in_memory().unwrap());
+    drive.create_directory("dir").await.unwrap();
+    drive.write(content(b"a"), "dir/a.txt").await.unwrap();
+    std::os::unix::fs::symlink(".", base.path().join("dir/loop")).unwrap();
+    std::os::unix::fs::symlink("dir", base.path().join("shortcut")).unwrap();
+
+    assert_eq!(drive.rebuild_index().await.unwrap(), 4);
+    let mut names = drive
+        .entries("shortcut")
+        .await
+        .unwrap()
+        .iter()
+        .filter_map(|entry| entry.name())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["a.txt", "loop"]);
+    assert_eq!(drive.stat("shortcut/a.txt").await.unwrap().size(), 1);
+    assert_eq!(drive.stat("dir/loop/loop/a.txt").await.unwrap().size(), 1);
+}
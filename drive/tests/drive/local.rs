@@ -0,0 +1,189 @@
+use drive::{
+    backend::{LocalBackend, SymlinkPolicy},
+    error::DriveError,
+    Drive,
+};
+use tokio_stream::StreamExt;
+
+#[tokio::test]
+async fn entries_report_metadata_and_symlinks() {
+    let base = tempfile::tempdir().unwrap();
+    std::fs::write(base.path().join("file.txt"), b"hello").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(base.path().join("file.txt"), base.path().join("link.txt")).unwrap();
+    let drive = Drive::new(base.path());
+
+    let entries = drive.entries("").await.unwrap();
+    let file = entries
+        .iter()
+        .find(|entry| entry.name().as_deref() == Some("file.txt"))
+        .unwrap();
+    assert!(!file.is_directory());
+    assert!(!file.is_symlink());
+    assert_eq!(file.size(), 5);
+    assert!(file.modified().is_some());
+    assert_eq!(file.mime_type().as_deref(), Some("text/plain"));
+    #[cfg(unix)]
+    assert!(file.permissions().is_some());
+
+    #[cfg(unix)]
+    {
+        let link = entries
+            .iter()
+            .find(|entry| entry.name().as_deref() == Some("link.txt"))
+            .unwrap();
+        assert!(link.is_symlink());
+        assert_eq!(link.size(), 5);
+    }
+}
+
+#[tokio::test]
+async fn read_range_returns_the_bytes_within_the_range() {
+    let base = tempfile::tempdir().unwrap();
+    std::fs::write(base.path().join("file.txt"), b"hello world").unwrap();
+    let drive = Drive::new(base.path());
+
+    let mut stream = drive.read_range("file.txt", 4..7).await.unwrap();
+    let mut range = vec![];
+    while let Some(chunk) = stream.next().await {
+        range.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(range, b"o w");
+}
+
+/// Plants links inside the drive at `base` pointing into the drive, to a
+/// directory outside of it, to a file outside of it, to the parent of the
+/// drive and to nowhere.
+#[cfg(unix)]
+fn plant_symlinks(base: &std::path::Path, outside: &std::path::Path) {
+    use std::os::unix::fs::symlink;
+    std::fs::create_dir(base.join("inside")).unwrap();
+    std::fs::write(base.join("inside/file.txt"), b"inside").unwrap();
+    std::fs::write(outside.join("secret.txt"), b"secret").unwrap();
+    symlink(base.join("inside"), base.join("to_inside")).unwrap();
+    symlink(outside, base.join("to_outside")).unwrap();
+    symlink(outside.join("secret.txt"), base.join("secret.txt")).unwrap();
+    symlink("..", base.join("to_parent")).unwrap();
+    symlink(outside.join("missing"), base.join("dangling")).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn symlinks_resolving_outside_of_the_drive_are_rejected() {
+    let base = tempfile::tempdir().unwrap();
+    let outside = tempfile::tempdir().unwrap();
+    plant_symlinks(base.path(), outside.path());
+    let drive = Drive::new(base.path());
+
+    for path in ["secret.txt", "to_outside/secret.txt"] {
+        assert!(matches!(
+            drive.read(path).await,
+            Err(DriveError::EntryNameInvalid(_))
+        ));
+    }
+    assert!(matches!(
+        drive.entries("to_outside").await,
+        Err(DriveError::EntryNameInvalid(_))
+    ));
+    assert!(matches!(
+        drive.entries("to_parent").await,
+        Err(DriveError::EntryNameInvalid(_))
+    ));
+    let content = tokio_stream::iter(vec![Ok::<_, std::io::Error>(&b"hostile"[..])]);
+    assert!(matches!(
+        drive.write(content, "to_outside/planted.txt").await,
+        Err(DriveError::EntryNameInvalid(_))
+    ));
+    assert!(!outside.path().join("planted.txt").exists());
+    let content = tokio_stream::iter(vec![Ok::<_, std::io::Error>(&b"hostile"[..])]);
+    assert!(matches!(
+        drive.write(content, "dangling").await,
+        Err(DriveError::EntryNameInvalid(_))
+    ));
+    assert!(!outside.path().join("missing").exists());
+    assert!(matches!(
+        drive.remove_directory("to_outside", true).await,
+        Err(DriveError::EntryNameInvalid(_))
+    ));
+    assert!(outside.path().join("secret.txt").exists());
+
+    let mut stream = drive.read("to_inside/file.txt").await.unwrap();
+    assert_eq!(stream.next().await.unwrap().unwrap(), &b"inside"[..]);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn symlink_policy_controls_which_links_are_followed() {
+    let base = tempfile::tempdir().unwrap();
+    let outside = tempfile::tempdir().unwrap();
+    plant_symlinks(base.path(), outside.path());
+
+    let drive = Drive::with_backend(
+        LocalBackend::new(base.path()).with_symlink_policy(SymlinkPolicy::Deny),
+    );
+    assert!(matches!(
+        drive.read("to_inside/file.txt").await,
+        Err(DriveError::EntryNameInvalid(_))
+    ));
+    assert!(drive.read("inside/file.txt").await.is_ok());
+
+    let drive = Drive::with_backend(
+        LocalBackend::new(base.path()).with_symlink_policy(SymlinkPolicy::FollowAll),
+    );
+    let mut stream = drive.read("to_outside/secret.txt").await.unwrap();
+    assert_eq!(stream.next().await.unwrap().unwrap(), &b"secret"[..]);
+}
+
+#[tokio::test]
+async fn failed_writes_leave_the_destination_untouched() {
+    let base = tempfile::tempdir().unwrap();
+    std::fs::write(base.path().join("file.txt"), b"hello").unwrap();
+    let drive = Drive::new(base.path());
+
+    let interrupted = tokio_stream::iter(vec![
+        Ok(&b"partial"[..]),
+        Err(std::io::Error::other("connection reset")),
+    ]);
+    assert!(matches!(
+        drive.write(interrupted, "file.txt").await,
+        Err(DriveError::EntryWrite(_))
+    ));
+    assert_eq!(
+        std::fs::read(base.path().join("file.txt")).unwrap(),
+        b"hello"
+    );
+    let files = std::fs::read_dir(base.path()).unwrap().count();
+    assert_eq!(files, 1, "the temporary file was not cleaned up");
+
+    let content = tokio_stream::iter(vec![Ok::<_, std::io::Error>(&b"bye"[..])]);
+    drive.write(content, "file.txt").await.unwrap();
+    assert_eq!(std::fs::read(base.path().join("file.txt")).unwrap(), b"bye");
+    assert_eq!(drive.entries("").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn scoped_backends_only_see_their_directory() {
+    let base = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(base.path().join("alice")).unwrap();
+    std::fs::create_dir_all(base.path().join("bob")).unwrap();
+    std::fs::write(base.path().join("bob/secret.txt"), b"secret").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(base.path().join("bob"), base.path().join("alice/bob")).unwrap();
+
+    let alice = Drive::with_backend(LocalBackend::new(base.path()).scoped("alice"));
+    let content = tokio_stream::iter(vec![Ok::<_, std::io::Error>(&b"hello"[..])]);
+    alice.write(content, "hello.txt").await.unwrap();
+    assert_eq!(
+        std::fs::read(base.path().join("alice/hello.txt")).unwrap(),
+        b"hello"
+    );
+    assert!(matches!(
+        alice.read("../bob/secret.txt").await,
+        Err(DriveError::EntryNameInvalid(_))
+    ));
+    #[cfg(unix)]
+    assert!(matches!(
+        alice.read("bob/secret.txt").await,
+        Err(DriveError::EntryNameInvalid(_))
+    ));
+}
@@ -0,0 +1,13 @@
+mod dedup;
+mod entry_valid;
+mod fake_s3;
+mod helpers;
+mod index;
+mod local;
+mod memory;
+mod mime;
+mod records;
+mod s3;
+mod search;
+mod trash;
+mod versions;
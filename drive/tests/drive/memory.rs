@@ -0,0 +1,244 @@
+use drive::{backend::MemoryBackend, error::DriveError, Drive};
+use tokio_stream::StreamExt;
+
+fn content(
+    bytes: &'static [u8],
+) -> impl futures_core::Stream<Item = std::io::Result<&'static [u8]>> {
+    tokio_stream::iter(vec![Ok(bytes)])
+}
+
+async fn read_to_vec(drive: &Drive<MemoryBackend>, path: &str) -> Vec<u8> {
+    let mut stream = drive.read(path).await.expect("failed to read file");
+    let mut content = vec![];
+    while let Some(chunk) = stream.next().await {
+        content.extend_from_slice(&chunk.unwrap());
+    }
+    content
+}
+
+#[tokio::test]
+async fn write_then_read_returns_the_same_content() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    drive.write(content(b"hello"), "hello.txt").await.unwrap();
+    assert_eq!(read_to_vec(&drive, "hello.txt").await, b"hello");
+
+    drive.write(content(b"bye"), "hello.txt").await.unwrap();
+    assert_eq!(read_to_vec(&drive, "hello.txt").await, b"bye");
+}
+
+#[tokio::test]
+async fn entries_lists_only_direct_children() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    drive.create_directory("a").await.unwrap();
+    drive.create_directory("a/b").await.unwrap();
+    drive.write(content(b"c"), "a/c.txt").await.unwrap();
+    drive.write(content(b"d"), "a/b/d.txt").await.unwrap();
+
+    let root = drive.entries("").await.unwrap();
+    assert_eq!(root.len(), 1);
+    assert!(root[0].is_directory());
+
+    let mut names = drive
+        .entries("a")
+        .await
+        .unwrap()
+        .iter()
+        .map(|entry| (entry.name().unwrap(), entry.is_directory()))
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![("b".to_string(), true), ("c.txt".to_string(), false)]
+    );
+}
+
+#[tokio::test]
+async fn create_directory_fails_when_entry_exists() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    drive.create_directory("a").await.unwrap();
+    let result = drive.create_directory("a").await;
+    assert!(matches!(result, Err(DriveError::EntryExists(_))));
+}
+
+#[tokio::test]
+async fn rename_directory_moves_its_contents() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    drive.create_directory("a").await.unwrap();
+    drive.write(content(b"c"), "a/c.txt").await.unwrap();
+
+    drive.rename_directory("a", "b").await.unwrap();
+    assert!(matches!(
+        drive.entries("a").await,
+        Err(DriveError::EntryNotFound(_))
+    ));
+    assert_eq!(read_to_vec(&drive, "b/c.txt").await, b"c");
+}
+
+#[tokio::test]
+async fn rename_file_moves_a_file() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    drive.create_directory("a").await.unwrap();
+    drive.write(content(b"c"), "c.txt").await.unwrap();
+    drive.write(content(b"d"), "d.txt").await.unwrap();
+
+    drive.rename_file("c.txt", "a/c.txt").await.unwrap();
+    assert_eq!(read_to_vec(&drive, "a/c.txt").await, b"c");
+    assert!(matches!(
+        drive.stat("c.txt").await,
+        Err(DriveError::EntryNotFound(_))
+    ));
+    assert!(matches!(
+        drive.rename_file("d.txt", "a/c.txt").await,
+        Err(DriveError::EntryExists(_))
+    ));
+    assert!(matches!(
+        drive.rename_file("a", "b").await,
+        Err(DriveError::EntryUnexpectedType(_))
+    ));
+}
+
+#[tokio::test]
+async fn copy_operations_duplicate_entries() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    drive.create_directory("a").await.unwrap();
+    drive.create_directory("a/b").await.unwrap();
+    drive.write(content(b"c"), "a/c.txt").await.unwrap();
+    drive.write(content(b"d"), "a/b/d.txt").await.unwrap();
+
+    drive.copy_file("a/c.txt", "c.txt").await.unwrap();
+    assert_eq!(read_to_vec(&drive, "c.txt").await, b"c");
+    drive.copy_directory("a", "copy").await.unwrap();
+    assert_eq!(read_to_vec(&drive, "copy/c.txt").await, b"c");
+    assert_eq!(read_to_vec(&drive, "copy/b/d.txt").await, b"d");
+    assert_eq!(read_to_vec(&drive, "a/b/d.txt").await, b"d");
+
+    assert!(matches!(
+        drive.copy_directory("a", "copy").await,
+        Err(DriveError::EntryExists(_))
+    ));
+    assert!(matches!(
+        drive.copy_directory("a", "a/b/a").await,
+        Err(DriveError::EntryNameInvalid(_))
+    ));
+    assert!(matches!(
+        drive.copy_file("a", "e.txt").await,
+        Err(DriveError::EntryUnexpectedType(_))
+    ));
+}
+
+#[tokio::test]
+async fn remove_operations_delete_entries() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    drive.create_directory("a").await.unwrap();
+    drive.write(content(b"c"), "a/c.txt").await.unwrap();
+    drive.write(content(b"d"), "d.txt").await.unwrap();
+
+    drive.remove_file("d.txt").await.unwrap();
+    drive.remove_directory("a", true).await.unwrap();
+    assert!(drive.entries("").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn remove_operations_check_the_entry_type() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    drive.create_directory("a").await.unwrap();
+    drive.write(content(b"c"), "a/c.txt").await.unwrap();
+
+    assert!(matches!(
+        drive.remove_file("a").await,
+        Err(DriveError::EntryUnexpectedType(_))
+    ));
+    assert!(matches!(
+        drive.remove_directory("a/c.txt", true).await,
+        Err(DriveError::EntryUnexpectedType(_))
+    ));
+    assert!(matches!(
+        drive.remove_directory("a", false).await,
+        Err(DriveError::EntryNotEmpty(_))
+    ));
+
+    drive.remove_file("a/c.txt").await.unwrap();
+    drive.remove_directory("a", false).await.unwrap();
+    assert!(drive.entries("").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn clones_share_the_same_storage() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    let other = drive.clone();
+    drive.create_directory("a").await.unwrap();
+    assert_eq!(other.entries("").await.unwrap().len(), 1);
+
+    let isolated = Drive::with_backend(MemoryBackend::new());
+    assert!(isolated.entries("").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn read_range_returns_the_bytes_within_the_range() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    drive
+        .write(content(b"hello world"), "hello.txt")
+        .await
+        .unwrap();
+
+    let mut stream = drive.read_range("hello.txt", 6..11).await.unwrap();
+    let mut range = vec![];
+    while let Some(chunk) = stream.next().await {
+        range.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(range, b"world");
+
+    let result = drive.read_range("hello.txt", 6..12).await;
+    assert!(matches!(
+        result,
+        Err(DriveError::EntryRangeNotSatisfiable(_))
+    ));
+}
+
+#[tokio::test]
+async fn failed_writes_leave_the_destination_untouched() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    drive.write(content(b"hello"), "hello.txt").await.unwrap();
+
+    let interrupted = tokio_stream::iter(vec![
+        Ok(&b"partial"[..]),
+        Err(std::io::Error::other("connection reset")),
+    ]);
+    assert!(matches!(
+        drive.write(interrupted, "hello.txt").await,
+        Err(DriveError::EntryWrite(_))
+    ));
+    assert_eq!(read_to_vec(&drive, "hello.txt").await, b"hello");
+}
+
+#[tokio::test]
+async fn scoped_backends_only_see_their_directory() {
+    let backend = MemoryBackend::new();
+    let root = Drive::with_backend(backend.clone());
+    root.create_directory("alice").await.unwrap();
+    root.create_directory("bob").await.unwrap();
+    root.write(content(b"secret"), "bob/secret.txt")
+        .await
+        .unwrap();
+
+    let alice = Drive::with_backend(backend.scoped("alice"));
+    assert!(alice.entries("").await.unwrap().is_empty());
+    alice.create_directory("docs").await.unwrap();
+    alice
+        .write(content(b"hello"), "docs/hello.txt")
+        .await
+        .unwrap();
+    let entries = alice.entries("docs").await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].path().to_str(), Some("docs/hello.txt"));
+    assert_eq!(read_to_vec(&root, "alice/docs/hello.txt").await, b"hello");
+
+    assert!(matches!(
+        alice.read("bob/secret.txt").await,
+        Err(DriveError::EntryNotFound(_))
+    ));
+    assert!(matches!(
+        alice.read("../bob/secret.txt").await,
+        Err(DriveError::EntryNameInvalid(_))
+    ));
+}
@@ -0,0 +1,70 @@
+use drive::{backend::MemoryBackend, mime, Drive};
+
+fn content(
+    bytes: &'static [u8],
+) -> impl futures_core::Stream<Item = std::io::Result<&'static [u8]>> {
+    tokio_stream::iter(vec![Ok(bytes)])
+}
+
+#[test]
+fn signatures_are_sniffed() {
+    assert_eq!(mime::sniff(b"\x89PNG\r\n\x1a\n\0\0"), Some("image/png"));
+    assert_eq!(mime::sniff(b"%PDF-1.7\n"), Some("application/pdf"));
+    assert_eq!(mime::sniff(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+    assert_eq!(mime::sniff(b"\0\0\0\x18ftypmp42"), Some("video/mp4"));
+    let mut tar = vec![0; 512];
+    tar[257..262].copy_from_slice(b"ustar");
+    assert_eq!(mime::sniff(&tar), Some("application/x-tar"));
+}
+
+#[test]
+fn text_is_sniffed_unless_it_has_control_characters() {
+    assert_eq!(
+        mime::sniff("héllo\r\n\tworld".as_bytes()),
+        Some("text/plain")
+    );
+    // A multi-byte character cut off by the sniffed length.
+    assert_eq!(mime::sniff(&"hé".as_bytes()[..2]), Some("text/plain"));
+    assert_eq!(mime::sniff(b"hello\0world"), None);
+    assert_eq!(mime::sniff(b"\xff\xfe\xfd"), None);
+    assert_eq!(mime::sniff(b""), None);
+}
+
+#[test]
+fn registered_extensions_win_over_sniffing() {
+    assert_eq!(
+        mime::detect("report.docx", b"PK\x03\x04"),
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+    );
+    assert_eq!(mime::detect("image", b"GIF89a"), "image/gif");
+    assert_eq!(mime::detect("blob.unknown", b"\0\x01"), mime::OCTET_STREAM);
+}
+
+#[tokio::test]
+async fn mime_type_sniffs_files_without_a_known_extension() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    drive.create_directory("docs").await.unwrap();
+    drive
+        .write(content(b"%PDF-1.4"), "docs/scan")
+        .await
+        .unwrap();
+    drive
+        .write(content(b"# notes"), "docs/README")
+        .await
+        .unwrap();
+    drive
+        .write(content(b"%PDF-1.4"), "docs/a.html")
+        .await
+        .unwrap();
+
+    for (path, expected) in [
+        ("docs/scan", Some("application/pdf")),
+        ("docs/README", Some("text/plain")),
+        ("docs/a.html", Some("text/html")),
+        ("docs", None),
+    ] {
+        let entry = drive.stat(path).await.unwrap();
+        let mime_type = drive.mime_type(&entry).await.unwrap();
+        assert_eq!(mime_type.as_deref(), expected, "{}", path);
+    }
+}
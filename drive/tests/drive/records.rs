@@ -0,0 +1,38 @@
+use drive::{backend::MemoryBackend, error::DriveError, Drive};
+
+#[tokio::test]
+async fn records_are_kept_by_kind() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    assert!(drive.records("tokens").await.unwrap().is_empty());
+    drive
+        .put_record("tokens", "a", &[("Owner", "alice"), ("Name", "a b=c")])
+        .await
+        .unwrap();
+    drive
+        .put_record("grants", "b", &[("Owner", "bob")])
+        .await
+        .unwrap();
+
+    let records = drive.records("tokens").await.unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records["a"]["Owner"], "alice");
+    assert_eq!(records["a"]["Name"], "a b=c");
+    // Records are out of reach of the users of the drive.
+    assert!(drive.entries("").await.unwrap().is_empty());
+
+    assert!(drive.delete_record("tokens", "a").await.unwrap());
+    assert!(!drive.delete_record("tokens", "a").await.unwrap());
+    assert!(drive.records("tokens").await.unwrap().is_empty());
+    assert_eq!(drive.records("grants").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn records_cannot_be_named_out_of_their_kind() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    for (kind, id) in [("tokens", "../a"), ("../tokens", "a"), ("tokens", "")] {
+        assert!(matches!(
+            drive.put_record(kind, id, &[]).await,
+            Err(DriveError::EntryNameInvalid(_))
+        ));
+    }
+}
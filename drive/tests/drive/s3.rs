@@ -0,0 +1,113 @@
+use crate::fake_s3::spawn_s3;
+use drive::{backend::S3Backend, error::DriveError, Drive};
+use tokio_stream::StreamExt;
+
+fn content(
+    bytes: &'static [u8],
+) -> impl futures_core::Stream<Item = std::io::Result<&'static [u8]>> {
+    tokio_stream::iter(vec![Ok(bytes)])
+}
+
+async fn read_to_vec(drive: &Drive<S3Backend>, path: &str) -> Vec<u8> {
+    let mut stream = drive.read(path).await.expect("failed to read file");
+    let mut content = vec![];
+    while let Some(chunk) = stream.next().await {
+        content.extend_from_slice(&chunk.unwrap());
+    }
+    content
+}
+
+#[tokio::test]
+async fn write_then_read_returns_the_same_content() {
+    let drive = Drive::with_backend(spawn_s3("drive").await);
+    drive.write(content(b"hello"), "hello.txt").await.unwrap();
+    assert_eq!(read_to_vec(&drive, "hello.txt").await, b"hello");
+}
+
+#[tokio::test]
+async fn empty_directories_are_listed() {
+    let drive = Drive::with_backend(spawn_s3("drive").await);
+    assert!(drive.entries("").await.unwrap().is_empty());
+    drive.create_directory("a").await.unwrap();
+    drive.create_directory("a/b").await.unwrap();
+    drive.write(content(b"c"), "a/c.txt").await.unwrap();
+
+    let mut names = drive
+        .entries("a")
+        .await
+        .unwrap()
+        .iter()
+        .map(|entry| (entry.name().unwrap(), entry.is_directory()))
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![("b".to_string(), true), ("c.txt".to_string(), false)]
+    );
+}
+
+#[tokio::test]
+async fn write_fails_when_parent_does_not_exist() {
+    let drive = Drive::with_backend(spawn_s3("").await);
+    let result = drive.write(content(b"c"), "a/c.txt").await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn rename_directory_moves_its_contents() {
+    let drive = Drive::with_backend(spawn_s3("drive").await);
+    drive.create_directory("a").await.unwrap();
+    drive.write(content(b"c"), "a/c.txt").await.unwrap();
+
+    drive.rename_directory("a", "b").await.unwrap();
+    assert!(matches!(
+        drive.entries("a").await,
+        Err(DriveError::EntryNotFound(_))
+    ));
+    assert_eq!(read_to_vec(&drive, "b/c.txt").await, b"c");
+}
+
+#[tokio::test]
+async fn remove_operations_delete_entries() {
+    let drive = Drive::with_backend(spawn_s3("drive").await);
+    drive.create_directory("a").await.unwrap();
+    drive.write(content(b"c"), "a/c.txt").await.unwrap();
+    drive.write(content(b"d"), "d.txt").await.unwrap();
+
+    drive.remove_file("d.txt").await.unwrap();
+    drive.remove_directory("a", true).await.unwrap();
+    assert!(drive.entries("").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn read_range_returns_the_bytes_within_the_range() {
+    let drive = Drive::with_backend(spawn_s3("drive").await);
+    drive
+        .write(content(b"hello world"), "hello.txt")
+        .await
+        .unwrap();
+
+    let mut stream = drive.read_range("hello.txt", 2..5).await.unwrap();
+    let mut range = vec![];
+    while let Some(chunk) = stream.next().await {
+        range.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(range, b"llo");
+}
+
+#[tokio::test]
+async fn scoped_backends_only_see_their_prefix() {
+    let backend = spawn_s3("drive").await;
+    let root = Drive::with_backend(backend.clone());
+    root.create_directory("alice").await.unwrap();
+    root.write(content(b"secret"), "secret.txt").await.unwrap();
+
+    let alice = Drive::with_backend(backend.scoped("alice"));
+    assert!(alice.entries("").await.unwrap().is_empty());
+    alice.write(content(b"hello"), "hello.txt").await.unwrap();
+    assert_eq!(read_to_vec(&root, "alice/hello.txt").await, b"hello");
+    assert!(matches!(
+        alice.read("secret.txt").await,
+        Err(DriveError::EntryNotFound(_))
+    ));
+}
@@ -0,0 +1,167 @@
+use drive::{
+    backend::MemoryBackend,
+    error::DriveError,
+    index::Index,
+    search::{EntryKind, SearchQuery},
+    Drive,
+};
+use std::time::{Duration, SystemTime};
+
+fn content(bytes: &[u8]) -> impl futures_core::Stream<Item = std::io::Result<bytes::Bytes>> {
+    tokio_stream::iter(vec![Ok(bytes::Bytes::copy_from_slice(bytes))])
+}
+
+/// A walked and an indexed drive holding the same entries.
+async fn drives() -> [Drive<MemoryBackend>; 2] {
+    let walked = Drive::with_backend(MemoryBackend::new());
+    let indexed =
+        Drive::with_backend(MemoryBackend::new()).with_index(Index::open_in_memory().unwrap());
+    for drive in [&walked, &indexed] {
+        drive.create_directory("docs").await.unwrap();
+        drive.create_directory("docs/reports").await.unwrap();
+        drive.create_directory("photos").await.unwrap();
+        drive.write(content(b"report"), "report.txt").await.unwrap();
+        drive
+            .write(content(b"annual report"), "docs/reports/Annual-Report.pdf")
+            .await
+            .unwrap();
+        drive
+            .write(content(b"quarterly"), "docs/reports/quarterly.txt")
+            .await
+            .unwrap();
+        drive
+            .write(content(b"a photo of the beach"), "photos/beach.jpg")
+            .await
+            .unwrap();
+        drive
+            .write(content(b"unreported"), "photos/unreported.png")
+            .await
+            .unwrap();
+    }
+    [walked, indexed]
+}
+
+async fn search(drive: &Drive<MemoryBackend>, query: &SearchQuery) -> Vec<(String, u32)> {
+    drive
+        .search(query)
+        .await
+        .unwrap()
+        .iter()
+        .map(|hit| (hit.entry().path().display().to_string(), hit.score()))
+        .collect()
+}
+
+fn named(pattern: &str) -> SearchQuery {
+    SearchQuery {
+        pattern: pattern.to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn names_are_matched_best_first() {
+    for drive in drives().await {
+        assert_eq!(
+            search(&drive, &named("report")).await,
+            [
+                ("report.txt".to_string(), 80),
+                ("docs/reports".to_string(), 80),
+                ("docs/reports/Annual-Report.pdf".to_string(), 60),
+                ("photos/unreported.png".to_string(), 55),
+            ]
+        );
+    }
+}
+
+#[tokio::test]
+async fn globs_match_whole_names() {
+    for drive in drives().await {
+        let paths =
+            |hits: Vec<(String, u32)>| hits.into_iter().map(|(path, _)| path).collect::<Vec<_>>();
+        assert_eq!(
+            paths(search(&drive, &named("*.TXT")).await),
+            ["report.txt", "docs/reports/quarterly.txt"]
+        );
+        assert_eq!(
+            paths(search(&drive, &named("[a-c]*.?[!x]?")).await),
+            ["photos/beach.jpg", "docs/reports/Annual-Report.pdf"]
+        );
+        assert!(search(&drive, &named("report")).await.len() > 1);
+        assert!(search(&drive, &named("repor?")).await.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn metadata_filters_narrow_the_results() {
+    for drive in drives().await {
+        let query = SearchQuery {
+            kind: Some(EntryKind::Directory),
+            ..Default::default()
+        };
+        assert_eq!(search(&drive, &query).await.len(), 3);
+
+        let query = SearchQuery {
+            kind: Some(EntryKind::File),
+            min_size: Some(10),
+            max_size: Some(15),
+            ..Default::default()
+        };
+        let hits = search(&drive, &query).await;
+        assert_eq!(
+            hits,
+            [
+                ("photos/unreported.png".to_string(), 0),
+                ("docs/reports/Annual-Report.pdf".to_string(), 0),
+            ]
+        );
+
+        let now = SystemTime::now();
+        let query = SearchQuery {
+            kind: Some(EntryKind::File),
+            modified_after: Some(now - Duration::from_secs(3600)),
+            modified_before: Some(now + Duration::from_secs(3600)),
+            ..Default::default()
+        };
+        assert_eq!(search(&drive, &query).await.len(), 5);
+        let query = SearchQuery {
+            modified_after: Some(now + Duration::from_secs(3600)),
+            ..Default::default()
+        };
+        assert!(search(&drive, &query).await.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn searches_stay_within_their_directory() {
+    for drive in drives().await {
+        let query = SearchQuery {
+            pattern: "report".to_string(),
+            within: "docs".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            search(&drive, &query).await,
+            [
+                ("docs/reports".to_string(), 80),
+                ("docs/reports/Annual-Report.pdf".to_string(), 60),
+            ]
+        );
+
+        let query = SearchQuery {
+            within: "report.txt".into(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            drive.search(&query).await,
+            Err(DriveError::EntryUnexpectedType(_))
+        ));
+        let query = SearchQuery {
+            within: "missing".into(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            drive.search(&query).await,
+            Err(DriveError::EntryNotFound(_))
+        ));
+    }
+}
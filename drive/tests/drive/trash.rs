@@ -0,0 +1,139 @@
+use drive::{backend::MemoryBackend, error::DriveError, Drive, RESERVED_DIR};
+use std::{
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+fn content(
+    bytes: &'static [u8],
+) -> impl futures_core::Stream<Item = std::io::Result<&'static [u8]>> {
+    tokio_stream::iter(vec![Ok(bytes)])
+}
+
+async fn drive_with_files() -> Drive<MemoryBackend> {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    drive.create_directory("docs").await.unwrap();
+    drive
+        .write(content(b"report"), "docs/report.pdf")
+        .await
+        .unwrap();
+    drive
+        .write(content(b"notes"), "docs/notes.txt")
+        .await
+        .unwrap();
+    drive
+}
+
+#[tokio::test]
+async fn trashed_entries_are_listed_with_their_original_path() {
+    let drive = drive_with_files().await;
+    let file = drive.trash_file("docs/report.pdf").await.unwrap();
+    let directory = drive.trash_directory("docs", true).await.unwrap();
+
+    assert!(matches!(
+        drive.stat("docs").await,
+        Err(DriveError::EntryNotFound(_))
+    ));
+    let items = drive.trash_items().await.unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].id(), directory.id());
+    assert_eq!(items[0].path(), Path::new("docs"));
+    assert!(items[0].is_directory());
+    assert_eq!(items[1].id(), file.id());
+    assert_eq!(items[1].path(), Path::new("docs/report.pdf"));
+    assert_eq!(items[1].size(), 6);
+    assert!(items[1].deleted() <= SystemTime::now());
+}
+
+#[tokio::test]
+async fn the_trash_is_not_reachable_through_the_drive() {
+    let drive = drive_with_files().await;
+    drive.trash_file("docs/notes.txt").await.unwrap();
+
+    let root = drive.entries("").await.unwrap();
+    assert_eq!(root.len(), 1);
+    assert!(matches!(
+        drive.entries(RESERVED_DIR).await,
+        Err(DriveError::EntryNameInvalid(_))
+    ));
+    assert!(matches!(
+        drive.create_directory(RESERVED_DIR).await,
+        Err(DriveError::EntryNameInvalid(_))
+    ));
+}
+
+#[tokio::test]
+async fn restore_renames_entries_whose_path_was_taken() {
+    let drive = drive_with_files().await;
+    let first = drive.trash_file("docs/report.pdf").await.unwrap();
+    drive
+        .write(content(b"new"), "docs/report.pdf")
+        .await
+        .unwrap();
+    let second = drive.trash_file("docs/report.pdf").await.unwrap();
+    drive
+        .write(content(b"newer"), "docs/report.pdf")
+        .await
+        .unwrap();
+
+    let restored = drive.restore(first.id(), None).await.unwrap();
+    assert_eq!(restored, Path::new("docs/report (1).pdf"));
+    let restored = drive.restore(second.id(), None).await.unwrap();
+    assert_eq!(restored, Path::new("docs/report (2).pdf"));
+    assert_eq!(drive.stat("docs/report (2).pdf").await.unwrap().size(), 3);
+    assert!(drive.trash_items().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn restore_recreates_the_removed_parents() {
+    let drive = drive_with_files().await;
+    let file = drive.trash_file("docs/notes.txt").await.unwrap();
+    drive.remove_directory("docs", true).await.unwrap();
+
+    let restored = drive.restore(file.id(), None).await.unwrap();
+    assert_eq!(restored, Path::new("docs/notes.txt"));
+    assert!(drive.stat("docs").await.unwrap().is_directory());
+
+    let file = drive.trash_file("docs/notes.txt").await.unwrap();
+    let to = Path::new("notes.txt");
+    assert_eq!(drive.restore(file.id(), Some(to)).await.unwrap(), to);
+}
+
+#[tokio::test]
+async fn purged_entries_are_gone() {
+    let drive = drive_with_files().await;
+    let file = drive.trash_file("docs/notes.txt").await.unwrap();
+    drive.trash_file("docs/report.pdf").await.unwrap();
+
+    drive.purge(file.id()).await.unwrap();
+    assert_eq!(drive.trash_items().await.unwrap().len(), 1);
+    assert!(matches!(
+        drive.restore(file.id(), None).await,
+        Err(DriveError::EntryNotFound(_))
+    ));
+    assert!(matches!(
+        drive.purge("../docs").await,
+        Err(DriveError::EntryNotFound(_))
+    ));
+
+    drive.empty_trash().await.unwrap();
+    assert!(drive.trash_items().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn expire_trash_only_purges_older_entries() {
+    let drive = drive_with_files().await;
+    drive.trash_file("docs/notes.txt").await.unwrap();
+    let expired = drive
+        .expire_trash(SystemTime::now() - Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert_eq!(expired, 0);
+
+    let expired = drive
+        .expire_trash(SystemTime::now() + Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert_eq!(expired, 1);
+    assert!(drive.trash_items().await.unwrap().is_empty());
+}
@@ -0,0 +1,166 @@
+use drive::{
+    backend::MemoryBackend,
+    error::DriveError,
+    versions::{Version, VersionPolicy},
+    Drive,
+};
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+fn content(bytes: &[u8]) -> impl futures_core::Stream<Item = std::io::Result<bytes::Bytes>> {
+    tokio_stream::iter(vec![Ok(bytes::Bytes::copy_from_slice(bytes))])
+}
+
+fn versioned_drive(keep_last: usize) -> Drive<MemoryBackend> {
+    Drive::with_backend(MemoryBackend::new()).with_versioning(VersionPolicy {
+        keep_last,
+        keep_for: None,
+        max_size: None,
+    })
+}
+
+async fn read_version(drive: &Drive<MemoryBackend>, path: &str, version: &Version) -> Vec<u8> {
+    let mut stream = drive.read_version(path, version.id()).await.unwrap();
+    let mut content = vec![];
+    while let Some(chunk) = stream.next().await {
+        content.extend_from_slice(&chunk.unwrap());
+    }
+    content
+}
+
+#[tokio::test]
+async fn overwritten_contents_are_kept_as_versions() {
+    let drive = versioned_drive(10);
+    drive
+        .clone()
+        .with_author("alice")
+        .write(content(b"one"), "a.txt")
+        .await
+        .unwrap();
+    let bob = drive.clone().with_author("bob");
+    bob.write(content(b"two"), "a.txt").await.unwrap();
+    bob.write(content(b"three"), "a.txt").await.unwrap();
+
+    let versions = drive.versions("a.txt").await.unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(read_version(&drive, "a.txt", &versions[0]).await, b"two");
+    assert_eq!(versions[0].author(), Some("bob"));
+    assert_eq!(versions[0].size(), 3);
+    assert!(versions[0].modified().is_some());
+    assert_eq!(read_version(&drive, "a.txt", &versions[1]).await, b"one");
+    assert_eq!(versions[1].author(), Some("alice"));
+}
+
+#[tokio::test]
+async fn failed_writes_keep_no_versions() {
+    let drive = versioned_drive(10);
+    drive.write(content(b"one"), "a.txt").await.unwrap();
+
+    let cut_off = tokio_stream::iter(vec![
+        Ok(bytes::Bytes::from_static(b"tw")),
+        Err(std::io::Error::new(
+            std::io::ErrorKind::ConnectionReset,
+            "connection reset",
+        )),
+    ]);
+    assert!(drive.write(cut_off, "a.txt").await.is_err());
+    assert!(drive.versions("a.txt").await.unwrap().is_empty());
+
+    drive.write(content(b"two"), "a.txt").await.unwrap();
+    let versions = drive.versions("a.txt").await.unwrap();
+    assert_eq!(versions.len(), 1);
+    assert_eq!(read_version(&drive, "a.txt", &versions[0]).await, b"one");
+}
+
+#[tokio::test]
+async fn drives_without_versioning_keep_no_versions() {
+    let drive = Drive::with_backend(MemoryBackend::new());
+    drive.write(content(b"one"), "a.txt").await.unwrap();
+    drive.write(content(b"two"), "a.txt").await.unwrap();
+    assert!(drive.versions("a.txt").await.unwrap().is_empty());
+    assert_eq!(drive.entries("").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn only_the_last_versions_are_kept() {
+    let drive = versioned_drive(2);
+    for text in ["one", "two", "three", "four"] {
+        drive
+            .write(content(text.as_bytes()), "a.txt")
+            .await
+            .unwrap();
+    }
+    let versions = drive.versions("a.txt").await.unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(read_version(&drive, "a.txt", &versions[0]).await, b"three");
+    assert_eq!(read_version(&drive, "a.txt", &versions[1]).await, b"two");
+}
+
+#[tokio::test]
+async fn versions_are_expired_after_the_time_they_are_kept_for() {
+    let drive = versioned_drive(10);
+    drive.create_directory("dir").await.unwrap();
+    drive.write(content(b"one"), "dir/a.txt").await.unwrap();
+    drive.write(content(b"two"), "dir/a.txt").await.unwrap();
+    assert_eq!(drive.expire_versions().await.unwrap(), 0);
+
+    let drive = drive.with_versioning(VersionPolicy {
+        keep_last: 10,
+        keep_for: Some(Duration::ZERO),
+        max_size: None,
+    });
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(drive.expire_versions().await.unwrap(), 1);
+    assert!(drive.versions("dir/a.txt").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn restoring_a_version_keeps_the_replaced_contents() {
+    let drive = versioned_drive(10);
+    drive.write(content(b"one"), "a.txt").await.unwrap();
+    drive.write(content(b"two"), "a.txt").await.unwrap();
+    let version = &drive.versions("a.txt").await.unwrap()[0];
+
+    drive.restore_version("a.txt", version.id()).await.unwrap();
+    assert_eq!(drive.stat("a.txt").await.unwrap().size(), 3);
+    let versions = drive.versions("a.txt").await.unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(read_version(&drive, "a.txt", &versions[0]).await, b"two");
+
+    assert!(matches!(
+        drive.restore_version("a.txt", "../b").await,
+        Err(DriveError::EntryNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn files_above_the_size_limit_keep_no_versions() {
+    let drive = Drive::with_backend(MemoryBackend::new()).with_versioning(VersionPolicy {
+        max_size: Some(3),
+        ..VersionPolicy::default()
+    });
+    drive.write(content(b"one"), "a.txt").await.unwrap();
+    drive.write(content(b"large"), "a.txt").await.unwrap();
+    drive.write(content(b"two"), "a.txt").await.unwrap();
+
+    let versions = drive.versions("a.txt").await.unwrap();
+    assert_eq!(versions.len(), 1);
+    assert_eq!(read_version(&drive, "a.txt", &versions[0]).await, b"one");
+}
+
+#[tokio::test]
+async fn versions_are_dropped_when_a_directory_takes_the_path() {
+    let drive = versioned_drive(10);
+    drive.write(content(b"one"), "a").await.unwrap();
+    drive.write(content(b"two"), "a").await.unwrap();
+    drive.remove_file("a").await.unwrap();
+    drive.create_directory("a").await.unwrap();
+    drive.write(content(b"three"), "a/b.txt").await.unwrap();
+    drive.write(content(b"four"), "a/b.txt").await.unwrap();
+    assert!(drive.versions("a").await.unwrap().is_empty());
+    assert_eq!(drive.versions("a/b.txt").await.unwrap().len(), 1);
+
+    drive.remove_directory("a", true).await.unwrap();
+    drive.write(content(b"five"), "a").await.unwrap();
+    assert!(drive.versions("a").await.unwrap().is_empty());
+}
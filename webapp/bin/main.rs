@@ -1,8 +1,32 @@
-use webapp::{configuration, server::Server, telemetry};
+use secrecy::{ExposeSecret, Secret};
+use webapp::{authentication::hash_password, configuration, server::Server, telemetry};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    // `mibox-webapp hash-password` reads a password from stdin and prints the
+    // hash to configure for a user.
+    if std::env::args().nth(1).as_deref() == Some("hash-password") {
+        let mut password = String::new();
+        std::io::stdin().read_line(&mut password)?;
+        let password = Secret::new(password.trim_end_matches(['\r', '\n']).to_string());
+        println!("{}", hash_password(&password)?.expose_secret());
+        return Ok(());
+    }
+
     let settings = configuration::get_configuration().expect("failed to read configuration");
+
+    // `mibox-webapp rebuild-index` rebuilds the index of the home drives from
+    // what they hold, e.g. after they were changed behind the server's back.
+    if std::env::args().nth(1).as_deref() == Some("rebuild-index") {
+        if settings.application.index.is_none() {
+            anyhow::bail!("no index is configured, there is none to rebuild");
+        }
+        let server = Server::without_indexing(settings).await?;
+        let indexed = server.application().rebuild_index().await?;
+        println!("indexed {} entries", indexed);
+        return Ok(());
+    }
+
     let subscriber = telemetry::get_subscriber(
         &settings.application.app_name,
         &settings.application.log_level,
@@ -0,0 +1,229 @@
+//! Access control lists letting users grant others access to parts of their
+//! home.
+use drive::{backend::Backend, error::DriveError, records::Fields, Drive};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The kind of the records the grants are kept in (see `Acl::with_records`).
+const RECORD_KIND: &str = "grants";
+
+/// The access a grant gives, write access includes read access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    Read,
+    Write,
+}
+
+impl Permission {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+        }
+    }
+
+    fn parse(permission: &str) -> Option<Self> {
+        match permission {
+            "read" => Some(Self::Read),
+            "write" => Some(Self::Write),
+            _ => None,
+        }
+    }
+}
+
+/// Who a grant is given to.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Grantee {
+    User(String),
+    Group(String),
+}
+
+impl Grantee {
+    fn includes(&self, username: &str, groups: &[String]) -> bool {
+        match self {
+            Grantee::User(user) => user == username,
+            Grantee::Group(group) => groups.contains(group),
+        }
+    }
+}
+
+/// Access to the entries of the home of `owner` below `path_prefix`.
+#[derive(Debug, Clone)]
+pub struct Grant {
+    pub id: String,
+    pub owner: String,
+    pub grantee: Grantee,
+    pub permission: Permission,
+    pub path_prefix: PathBuf,
+    pub created_at: SystemTime,
+}
+
+impl Grant {
+    fn from_record(id: &str, fields: &Fields) -> Option<Self> {
+        let grantee = match (fields.get("User"), fields.get("Group")) {
+            (Some(user), None) => Grantee::User(user.clone()),
+            (None, Some(group)) => Grantee::Group(group.clone()),
+            _ => return None,
+        };
+        let created_at = fields.get("CreatedAt")?.parse().ok()?;
+        Some(Self {
+            id: id.to_owned(),
+            owner: fields.get("Owner")?.clone(),
+            grantee,
+            permission: Permission::parse(fields.get("Permission")?)?,
+            path_prefix: PathBuf::from(fields.get("PathPrefix")?),
+            created_at: UNIX_EPOCH + Duration::from_secs(created_at),
+        })
+    }
+}
+
+/// The grants of every user, keyed by id.
+#[derive(Clone, Default)]
+pub struct Acl {
+    grants: Arc<RwLock<HashMap<String, Grant>>>,
+    /// Keeps the grants across restarts when set.
+    records: Option<Drive<Backend>>,
+}
+
+impl Acl {
+    /// Keeps the grants in the records of `drive`, starting from the ones
+    /// it holds.
+    pub async fn with_records(mut self, drive: Drive<Backend>) -> Result<Self, DriveError> {
+        let mut grants = HashMap::new();
+        for (id, fields) in drive.records(RECORD_KIND).await? {
+            match Grant::from_record(&id, &fields) {
+                Some(grant) => {
+                    grants.insert(id, grant);
+                }
+                None => tracing::warn!("ignoring the invalid record of grant {}", id),
+            }
+        }
+        self.grants = Arc::new(RwLock::new(grants));
+        self.records = Some(drive);
+        Ok(self)
+    }
+
+    /// Records `grant`, when the grants are kept in records.
+    async fn persist(&self, grant: &Grant) -> Result<(), DriveError> {
+        let Some(drive) = &self.records else {
+            return Ok(());
+        };
+        let created_at = grant
+            .created_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string();
+        let path_prefix = grant.path_prefix.to_string_lossy();
+        let grantee = match &grant.grantee {
+            Grantee::User(user) => ("User", user.as_str()),
+            Grantee::Group(group) => ("Group", group.as_str()),
+        };
+        let fields = [
+            ("Owner", grant.owner.as_str()),
+            grantee,
+            ("Permission", grant.permission.as_str()),
+            ("PathPrefix", path_prefix.as_ref()),
+            ("CreatedAt", created_at.as_str()),
+        ];
+        drive.put_record(RECORD_KIND, &grant.id, &fields).await
+    }
+
+    /// Grants `grantee` access to the entries of the home of `owner` below
+    /// `path_prefix`.
+    pub async fn grant(
+        &self,
+        owner: String,
+        grantee: Grantee,
+        permission: Permission,
+        path_prefix: PathBuf,
+    ) -> Result<Grant, DriveError> {
+        let grant = Grant {
+            id: rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(16)
+                .map(char::from)
+                .collect(),
+            owner,
+            grantee,
+            permission,
+            path_prefix,
+            created_at: SystemTime::now(),
+        };
+        self.persist(&grant).await?;
+        let mut grants = self.grants.write().expect("acl lock poisoned");
+        grants.insert(grant.id.clone(), grant.clone());
+        Ok(grant)
+    }
+
+    /// Returns the grants given by `owner`.
+    pub fn granted_by(&self, owner: &str) -> Vec<Grant> {
+        self.find(|grant| grant.owner == owner)
+    }
+
+    /// Returns the grants given to `username`, directly or through one of
+    /// its `groups`.
+    pub fn granted_to(&self, username: &str, groups: &[String]) -> Vec<Grant> {
+        self.find(|grant| grant.grantee.includes(username, groups))
+    }
+
+    fn find(&self, filter: impl Fn(&Grant) -> bool) -> Vec<Grant> {
+        let grants = self.grants.read().expect("acl lock poisoned");
+        let mut found = grants
+            .values()
+            .filter(|grant| filter(grant))
+            .cloned()
+            .collect::<Vec<_>>();
+        found.sort_by_key(|grant| grant.created_at);
+        found
+    }
+
+    /// Revokes a grant given by `owner`, returning whether it existed.
+    pub async fn revoke(&self, owner: &str, id: &str) -> Result<bool, DriveError> {
+        let revoked = {
+            let mut grants = self.grants.write().expect("acl lock poisoned");
+            match grants.get(id) {
+                Some(grant) if grant.owner == owner => grants.remove(id).is_some(),
+                _ => false,
+            }
+        };
+        if let (true, Some(drive)) = (revoked, &self.records) {
+            drive.delete_record(RECORD_KIND, id).await?;
+        }
+        Ok(revoked)
+    }
+
+    /// Whether `username`, member of `groups`, has `permission` on `path` of
+    /// the home of `owner`.
+    ///
+    /// Owners have every permission on their home. Paths are compared
+    /// component-wise without being normalized, the drive rejects the paths
+    /// walking out of a prefix.
+    pub fn permits(
+        &self,
+        owner: &str,
+        username: &str,
+        groups: &[String],
+        path: &Path,
+        permission: Permission,
+    ) -> bool {
+        if owner == username {
+            return true;
+        }
+        let grants = self.grants.read().expect("acl lock poisoned");
+        grants.values().any(|grant| {
+            grant.owner == owner
+                && grant.permission >= permission
+                && grant.grantee.includes(username, groups)
+                && path.starts_with(&grant.path_prefix)
+        })
+    }
+}
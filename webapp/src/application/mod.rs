@@ -1,13 +1,194 @@
-use std::path::PathBuf;
+use crate::{
+    acl::Acl, archive::ExtractLimits, authentication::Authentication, error::MiboxError,
+    handlers::dav::Locks, sharing::Shares, uploads::Uploads,
+};
+use anyhow::anyhow;
+use axum::extract::FromRef;
+use axum_extra::extract::cookie::Key;
+use drive::{
+    backend::Backend, error::DriveError, index::Index, versions::VersionPolicy, Drive, RESERVED_DIR,
+};
+use std::{
+    path::{Component, Path},
+    time::{Duration, SystemTime},
+};
+
+/// How long removed entries stay in the trash unless configured otherwise.
+pub const DEFAULT_TRASH_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Fails unless `username` can name a home directory: a single path
+/// component other than the directory reserved by the drive.
+pub fn validate_username(username: &str) -> Result<(), MiboxError> {
+    let mut components = Path::new(username).components();
+    let valid = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    );
+    if !valid || username == RESERVED_DIR {
+        return Err(MiboxError::ValidationError(format!(
+            "{:?} cannot be used as a home directory name",
+            username
+        )));
+    }
+    Ok(())
+}
 
 #[derive(Clone)]
 pub struct Application {
     pub base_url: String,
-    pub drive: PathBuf,
+    pub drive: Drive<Backend>,
+    pub authentication: Authentication,
+    pub shares: Shares,
+    pub acl: Acl,
+    /// The WebDAV locks.
+    pub locks: Locks,
+    pub uploads: Uploads,
+    pub extract_limits: ExtractLimits,
+    /// How long removed entries stay in the trash.
+    pub trash_retention: Duration,
+    /// Which prior versions of overwritten files are kept.
+    pub versioning: VersionPolicy,
+    /// Indexes the metadata of the home drives, each under its user.
+    pub index: Option<Index>,
 }
 
 impl Application {
-    pub fn new(base_url: String, drive: PathBuf) -> Self {
-        Self { base_url, drive }
+    pub fn new(
+        base_url: String,
+        drive: Drive<Backend>,
+        authentication: Authentication,
+        shares: Shares,
+    ) -> Self {
+        Self {
+            base_url,
+            drive,
+            authentication,
+            shares,
+            acl: Acl::default(),
+            locks: Locks::default(),
+            uploads: Uploads::temporary(),
+            extract_limits: ExtractLimits::default(),
+            trash_retention: DEFAULT_TRASH_RETENTION,
+            versioning: VersionPolicy::default(),
+            index: None,
+        }
+    }
+
+    /// Checks the access of users to the homes of others with `acl`, e.g.
+    /// grants kept in records.
+    pub fn with_acl(mut self, acl: Acl) -> Self {
+        self.acl = acl;
+        self
+    }
+
+    /// Keeps the prior versions of overwritten files following `versioning`.
+    pub fn with_versioning(mut self, versioning: VersionPolicy) -> Self {
+        self.versioning = versioning;
+        self
+    }
+
+    /// Indexes the metadata of the home drives in `index`.
+    pub fn with_index(mut self, index: Index) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Keeps removed entries in the trash for `trash_retention`.
+    pub fn with_trash_retention(mut self, trash_retention: Duration) -> Self {
+        self.trash_retention = trash_retention;
+        self
+    }
+
+    /// Limits what uploaded archives may extract.
+    pub fn with_extract_limits(mut self, extract_limits: ExtractLimits) -> Self {
+        self.extract_limits = extract_limits;
+        self
+    }
+
+    /// Stages resumable uploads with `uploads` rather than in a directory of
+    /// their own within the temporary directory.
+    pub fn with_uploads(mut self, uploads: Uploads) -> Self {
+        self.uploads = uploads;
+        self
+    }
+
+    /// Returns the home drive of a user, rooted at the directory named after
+    /// them, which is created on first use.
+    ///
+    /// Every path handled by the home drive is relative to that directory so
+    /// that users cannot reach each other's files.
+    pub async fn home(&self, username: &str) -> Result<Drive<Backend>, MiboxError> {
+        validate_username(username)?;
+        match self.drive.create_directory(username).await {
+            Ok(()) | Err(DriveError::EntryExists(_)) => {}
+            Err(e) => return Err(e.into()),
+        }
+        let home = Drive::with_backend(self.drive.backend().scoped(username))
+            .with_versioning(self.versioning);
+        Ok(match &self.index {
+            Some(index) => home.with_index(index.scoped(username)),
+            None => home,
+        })
+    }
+
+    /// The home drives found at the root of the drive.
+    async fn homes(&self) -> Result<Vec<Drive<Backend>>, MiboxError> {
+        let mut homes = vec![];
+        for home in self.drive.entries("").await? {
+            let Some(username) = home.name().filter(|_| home.is_directory()) else {
+                continue;
+            };
+            homes.push(self.home(&username).await?);
+        }
+        Ok(homes)
+    }
+
+    /// Purges the entries that stayed in the trash of the home drives longer
+    /// than the retention period, returning how many were purged.
+    pub async fn expire_trash(&self) -> Result<usize, MiboxError> {
+        let removed_before = SystemTime::now() - self.trash_retention;
+        let mut expired = 0;
+        for home in self.homes().await? {
+            expired += home.expire_trash(removed_before).await?;
+        }
+        Ok(expired)
+    }
+
+    /// Rebuilds the index of every home drive from what they hold, returning
+    /// how many entries were indexed.
+    pub async fn rebuild_index(&self) -> Result<usize, MiboxError> {
+        let mut indexed = 0;
+        for home in self.homes().await? {
+            indexed += home.rebuild_index().await?;
+        }
+        Ok(indexed)
+    }
+
+    /// Removes the blobs no file references anymore when the drive is
+    /// deduplicated, returning how many were removed.
+    pub async fn collect_garbage(&self) -> Result<usize, MiboxError> {
+        match self.drive.backend() {
+            Backend::Dedup(backend) => backend
+                .collect_garbage()
+                .await
+                .map_err(|e| anyhow!(e).into()),
+            _ => Ok(0),
+        }
+    }
+
+    /// Removes the versions of the files of the home drives that the
+    /// versioning policy no longer keeps, returning how many were removed.
+    pub async fn expire_versions(&self) -> Result<usize, MiboxError> {
+        let mut expired = 0;
+        for home in self.homes().await? {
+            expired += home.expire_versions().await?;
+        }
+        Ok(expired)
+    }
+}
+
+impl FromRef<Application> for Key {
+    fn from_ref(application: &Application) -> Self {
+        application.authentication.key().clone()
     }
 }
@@ -0,0 +1,663 @@
+//! Archives of directory trees, written on the fly while being sent, and
+//! extraction of uploaded archives.
+use crate::error::MiboxError;
+use anyhow::Context;
+use async_compression::tokio::{bufread::GzipDecoder, write::GzipEncoder};
+use async_zip::{base::write::ZipFileWriter, Compression, ZipDateTime, ZipEntryBuilder};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use drive::{backend::Backend, entry::Entry, error::DriveError, Drive};
+use futures::{future, AsyncWriteExt as _, Stream, StreamExt, TryStreamExt};
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    io,
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, Take};
+use tokio_util::{compat::FuturesAsyncReadCompatExt, io::ReaderStream};
+
+/// How many bytes of an archive are buffered between the task writing it
+/// and the response.
+const ARCHIVE_BUFFER: usize = 64 * 1024;
+
+const TAR_BLOCK: usize = 512;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    #[default]
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar")]
+    Tar,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+}
+
+impl ArchiveFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+        }
+    }
+
+    /// The format of an archive named `name`, after its extension.
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else {
+            None
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "application/zip",
+            ArchiveFormat::Tar => "application/x-tar",
+            ArchiveFormat::TarGz => "application/gzip",
+        }
+    }
+}
+
+/// Streams an archive of the directory `root` of `drive`, whose `entries`
+/// have already been listed.
+///
+/// The archive is written by a background task as it is read, entries are
+/// named after their path relative to `root`. A failure halfway fails the
+/// stream, so that the client does not mistake a truncated archive for a
+/// complete one.
+pub fn stream_archive(
+    drive: Drive<Backend>,
+    root: PathBuf,
+    entries: Vec<Entry>,
+    format: ArchiveFormat,
+) -> impl Stream<Item = io::Result<Bytes>> {
+    let (writer, reader) = tokio::io::duplex(ARCHIVE_BUFFER);
+    let archive = tokio::spawn(async move {
+        let tree = Tree::new(drive, root, entries);
+        let result = match format {
+            ArchiveFormat::Zip => write_zip(tree, writer).await,
+            ArchiveFormat::Tar => write_tar(tree, writer).await,
+            ArchiveFormat::TarGz => write_tar(tree, GzipEncoder::new(writer)).await,
+        };
+        if let Err(e) = &result {
+            tracing::error!("failed to write archive: {:?}", e);
+        }
+        result
+    });
+    let outcome = futures::stream::once(async move {
+        match archive.await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(Err(io::Error::other(e))),
+            Err(e) => Some(Err(io::Error::other(e))),
+        }
+    })
+    .filter_map(future::ready);
+    ReaderStream::new(reader).chain(outcome)
+}
+
+/// Walks a directory tree depth first, in the order of the paths.
+struct Tree {
+    drive: Drive<Backend>,
+    root: PathBuf,
+    /// The entries left to walk, the next one last.
+    pending: Vec<Entry>,
+}
+
+impl Tree {
+    fn new(drive: Drive<Backend>, root: PathBuf, mut entries: Vec<Entry>) -> Self {
+        entries.sort_by(|a, b| b.path().cmp(a.path()));
+        Self {
+            drive,
+            root,
+            pending: entries,
+        }
+    }
+
+    /// The next entry of the tree along with its path relative to the root.
+    ///
+    /// Links to directories are skipped, as they may lead back up the tree.
+    async fn next(&mut self) -> anyhow::Result<Option<(String, Entry)>> {
+        let entry = loop {
+            match self.pending.pop() {
+                Some(entry) if entry.is_directory() && entry.is_symlink() => continue,
+                Some(entry) => break entry,
+                None => return Ok(None),
+            }
+        };
+        if entry.is_directory() {
+            let mut children = self.drive.entries(entry.path()).await?;
+            children.sort_by(|a, b| b.path().cmp(a.path()));
+            self.pending.append(&mut children);
+        }
+        let name = entry
+            .path()
+            .strip_prefix(&self.root)
+            .unwrap_or(entry.path())
+            .iter()
+            .map(|segment| segment.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        Ok(Some((name, entry)))
+    }
+}
+
+async fn write_zip(mut tree: Tree, writer: impl AsyncWrite + Unpin) -> anyhow::Result<()> {
+    let mut zip = ZipFileWriter::with_tokio(writer);
+    while let Some((name, entry)) = tree.next().await? {
+        let modified = entry.modified().map(DateTime::<Utc>::from);
+        if entry.is_directory() {
+            let mut builder =
+                ZipEntryBuilder::new(format!("{}/", name).into(), Compression::Stored)
+                    .unix_permissions(0o755);
+            if let Some(modified) = &modified {
+                builder = builder.last_modification_date(ZipDateTime::from_chrono(modified));
+            }
+            zip.write_entry_whole(builder, &[]).await?;
+            continue;
+        }
+        let mut builder =
+            ZipEntryBuilder::new(name.into(), Compression::Deflate).unix_permissions(0o644);
+        if let Some(modified) = &modified {
+            builder = builder.last_modification_date(ZipDateTime::from_chrono(modified));
+        }
+        let mut content = tree.drive.read(entry.path()).await?;
+        let mut file = zip.write_entry_stream(builder).await?;
+        while let Some(bytes) = content.try_next().await? {
+            file.write_all(&bytes).await?;
+        }
+        file.close().await?;
+    }
+    zip.close().await?;
+    Ok(())
+}
+
+async fn write_tar(mut tree: Tree, mut tar: impl AsyncWrite + Unpin) -> anyhow::Result<()> {
+    while let Some((name, entry)) = tree.next().await? {
+        let name = if entry.is_directory() {
+            format!("{}/", name)
+        } else {
+            name
+        };
+        tar.write_all(&tar_header(&name, &entry)?).await?;
+        if entry.is_directory() {
+            continue;
+        }
+        let mut content = tree.drive.read(entry.path()).await?;
+        let mut written = 0;
+        while let Some(bytes) = content.try_next().await? {
+            written += bytes.len() as u64;
+            tar.write_all(&bytes).await?;
+        }
+        anyhow::ensure!(
+            written == entry.size(),
+            "{:?} changed while being archived",
+            entry.path()
+        );
+        tar.write_all(&padding(written)).await?;
+    }
+    // The end of an archive is marked by two empty blocks.
+    tar.write_all(&[0; 2 * TAR_BLOCK]).await?;
+    tar.shutdown().await?;
+    Ok(())
+}
+
+/// The zeroes completing the last block of `size` bytes.
+fn padding(size: u64) -> Vec<u8> {
+    let remainder = (size % TAR_BLOCK as u64) as usize;
+    vec![0; (TAR_BLOCK - remainder) % TAR_BLOCK]
+}
+
+/// The header blocks of an entry, names that do not fit in the header are
+/// preceded by a GNU long name entry.
+fn tar_header(name: &str, entry: &Entry) -> anyhow::Result<Vec<u8>> {
+    let mut blocks = vec![];
+    let mut header = tar::Header::new_gnu();
+    if name.len() > 100 {
+        let mut long_name = tar::Header::new_gnu();
+        long_name.set_entry_type(tar::EntryType::GNULongName);
+        long_name.as_gnu_mut().context("gnu header")?.name[..13].copy_from_slice(b"././@LongLink");
+        long_name.set_mode(0o644);
+        long_name.set_size(name.len() as u64 + 1);
+        long_name.set_cksum();
+        blocks.extend_from_slice(long_name.as_bytes());
+        blocks.extend_from_slice(name.as_bytes());
+        blocks.push(0);
+        blocks.extend(padding(name.len() as u64 + 1));
+        header.as_gnu_mut().context("gnu header")?.name[..]
+            .copy_from_slice(&name.as_bytes()[..100]);
+    } else {
+        header.set_path(Path::new(name))?;
+    }
+    if entry.is_directory() {
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+    } else {
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_mode(0o644);
+        header.set_size(entry.size());
+    }
+    let modified = entry
+        .modified()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_secs());
+    header.set_mtime(modified);
+    header.set_cksum();
+    blocks.extend_from_slice(header.as_bytes());
+    Ok(blocks)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ArchiveError {
+    #[error("{0}")]
+    UnsupportedArchive(String),
+    #[error("{0}")]
+    InvalidArchive(String),
+    #[error("{0}")]
+    UnsafeEntry(String),
+    #[error("{0}")]
+    ArchiveTooLarge(String),
+}
+
+/// Limits on what an uploaded archive may extract, against zip bombs.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractLimits {
+    /// The total size of the extracted files.
+    pub max_size: u64,
+    pub max_entries: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_size: 4 * 1024 * 1024 * 1024,
+            max_entries: 10_000,
+        }
+    }
+}
+
+/// What an entry of an uploaded archive is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryKind {
+    File,
+    Directory,
+    /// Links and special files, which are not extracted.
+    Other,
+}
+
+/// The path an entry of an archive is extracted to, relative to the target
+/// directory, or `None` for the entry of the target directory itself.
+///
+/// Leading `.` components, as in `./src/main.rs`, are dropped. Otherwise the
+/// path must be valid in a drive (see `drive::is_valid_path`), which rejects
+/// absolute paths and `..` that would escape the target directory.
+fn entry_path(name: &str) -> Result<Option<PathBuf>, ArchiveError> {
+    let path = Path::new(name)
+        .components()
+        .skip_while(|component| matches!(component, Component::CurDir))
+        .collect::<PathBuf>();
+    if !drive::is_valid_path(&path) {
+        return Err(ArchiveError::UnsafeEntry(format!(
+            "{:?} would be extracted outside of the target directory",
+            name
+        )));
+    }
+    Ok((!path.as_os_str().is_empty()).then_some(path))
+}
+
+/// Checks the entries of an archive against `limits` before anything is
+/// extracted, so that a rejected archive leaves the drive untouched.
+#[derive(Default)]
+struct Budget {
+    size: u64,
+    entries: u64,
+}
+
+impl Budget {
+    fn check(
+        &mut self,
+        limits: &ExtractLimits,
+        name: &str,
+        kind: EntryKind,
+        size: u64,
+    ) -> Result<(), ArchiveError> {
+        if kind == EntryKind::Other {
+            return Ok(());
+        }
+        entry_path(name)?;
+        self.entries += 1;
+        if kind == EntryKind::File {
+            self.size = self.size.saturating_add(size);
+        }
+        if self.entries > limits.max_entries {
+            return Err(ArchiveError::ArchiveTooLarge(format!(
+                "archives are limited to {} entries",
+                limits.max_entries
+            )));
+        }
+        if self.size > limits.max_size {
+            return Err(ArchiveError::ArchiveTooLarge(format!(
+                "archives are limited to {} extracted bytes",
+                limits.max_size
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Extracts entries into a directory of a drive, creating the directories
+/// leading to them.
+struct Extraction<'a> {
+    drive: &'a Drive<Backend>,
+    target: &'a Path,
+    directories: HashSet<PathBuf>,
+    extracted: u64,
+}
+
+impl<'a> Extraction<'a> {
+    fn new(drive: &'a Drive<Backend>, target: &'a Path) -> Self {
+        Self {
+            drive,
+            target,
+            directories: HashSet::new(),
+            extracted: 0,
+        }
+    }
+
+    async fn create_directory(&mut self, path: &Path) -> Result<(), MiboxError> {
+        let mut ancestors = path.ancestors().collect::<Vec<_>>();
+        ancestors.reverse();
+        for ancestor in ancestors {
+            if ancestor.as_os_str().is_empty() || self.directories.contains(ancestor) {
+                continue;
+            }
+            match self
+                .drive
+                .create_directory(self.target.join(ancestor))
+                .await
+            {
+                Ok(()) | Err(DriveError::EntryExists(_)) => {}
+                Err(e) => return Err(e.into()),
+            }
+            self.directories.insert(ancestor.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Extracts the entry `name` of an archive, reading the content of files
+    /// from `content`.
+    async fn extract(
+        &mut self,
+        name: &str,
+        kind: EntryKind,
+        content: impl AsyncRead + Send + Unpin,
+    ) -> Result<(), MiboxError> {
+        // Links and special files are skipped before their names are
+        // checked, as `Budget::check` does not check them.
+        if kind == EntryKind::Other {
+            tracing::warn!(
+                "skipped {:?}, which is neither a file nor a directory",
+                name
+            );
+            return Ok(());
+        }
+        let Some(path) = entry_path(name)? else {
+            return Ok(());
+        };
+        match kind {
+            EntryKind::Directory => self.create_directory(&path).await?,
+            EntryKind::File => {
+                if let Some(parent) = path.parent() {
+                    self.create_directory(parent).await?;
+                }
+                let content = ReaderStream::new(content);
+                self.drive.write(content, self.target.join(&path)).await?;
+            }
+            EntryKind::Other => return Ok(()),
+        }
+        self.extracted += 1;
+        Ok(())
+    }
+}
+
+/// Extracts the archive staged at `archive` into the directory `target` of
+/// `drive`, returning how many entries were extracted.
+///
+/// The whole archive is checked first, archives with entries that would
+/// escape `target` or exceeding `limits` are rejected without extracting
+/// anything. Links and special files are skipped. Existing files are
+/// overwritten.
+pub async fn extract_archive(
+    drive: &Drive<Backend>,
+    target: &Path,
+    archive: &Path,
+    format: ArchiveFormat,
+    limits: &ExtractLimits,
+) -> Result<u64, MiboxError> {
+    if !drive.stat(target).await?.is_directory() {
+        return Err(MiboxError::ValidationError(format!(
+            "{:?} is not a directory",
+            target
+        )));
+    }
+    match format {
+        ArchiveFormat::Zip => extract_zip(drive, target, archive, limits).await,
+        ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+            let mut budget = Budget::default();
+            let mut tar = open_tar(archive, format).await?;
+            while let Some(entry) = tar.next_entry().await? {
+                budget.check(limits, &entry.name, entry.kind, entry.size)?;
+            }
+
+            let mut extraction = Extraction::new(drive, target);
+            let mut tar = open_tar(archive, format).await?;
+            while let Some(entry) = tar.next_entry().await? {
+                if entry.kind == EntryKind::File {
+                    let content = tar.content();
+                    extraction.extract(&entry.name, entry.kind, content).await?;
+                } else {
+                    let content = tokio::io::empty();
+                    extraction.extract(&entry.name, entry.kind, content).await?;
+                }
+            }
+            Ok(extraction.extracted)
+        }
+    }
+}
+
+fn invalid_archive(e: impl std::fmt::Display) -> ArchiveError {
+    ArchiveError::InvalidArchive(format!("invalid archive: {}", e))
+}
+
+async fn extract_zip(
+    drive: &Drive<Backend>,
+    target: &Path,
+    archive: &Path,
+    limits: &ExtractLimits,
+) -> Result<u64, MiboxError> {
+    let zip = async_zip::tokio::read::fs::ZipFileReader::new(archive)
+        .await
+        .map_err(invalid_archive)?;
+    let mut entries = vec![];
+    let mut budget = Budget::default();
+    for entry in zip.file().entries() {
+        let name = entry.filename().as_str().map_err(invalid_archive)?;
+        let kind = if entry.dir().map_err(invalid_archive)? {
+            EntryKind::Directory
+        } else if entry.unix_permissions().is_some_and(|mode| {
+            // Some archivers only record the permission bits of files.
+            let file_type = u32::from(mode) & 0o170000;
+            file_type != 0 && file_type != 0o100000
+        }) {
+            EntryKind::Other
+        } else {
+            EntryKind::File
+        };
+        budget.check(limits, name, kind, entry.uncompressed_size())?;
+        entries.push((name.to_owned(), kind, entry.uncompressed_size()));
+    }
+
+    let mut extraction = Extraction::new(drive, target);
+    for (index, (name, kind, size)) in entries.into_iter().enumerate() {
+        let content = zip
+            .reader_without_entry(index)
+            .await
+            .map_err(invalid_archive)?;
+        // Whatever follows the size declared by the archive is ignored, so
+        // that lying about it does not get past the limits.
+        let content = content.compat().take(size);
+        extraction.extract(&name, kind, content).await?;
+    }
+    Ok(extraction.extracted)
+}
+
+async fn open_tar(
+    archive: &Path,
+    format: ArchiveFormat,
+) -> Result<TarReader<Box<dyn AsyncRead + Send + Unpin>>, MiboxError> {
+    let file = tokio::fs::File::open(archive)
+        .await
+        .context("failed to open staged archive")?;
+    let reader: Box<dyn AsyncRead + Send + Unpin> = match format {
+        ArchiveFormat::TarGz => Box::new(GzipDecoder::new(BufReader::new(file))),
+        _ => Box::new(BufReader::new(file)),
+    };
+    Ok(TarReader::new(reader))
+}
+
+/// An entry of a tar archive.
+struct TarEntry {
+    name: String,
+    kind: EntryKind,
+    size: u64,
+}
+
+/// Reads a tar archive sequentially.
+///
+/// Both GNU long names and pax paths are understood.
+struct TarReader<R> {
+    inner: R,
+    /// The bytes of the current entry left to read.
+    remaining: u64,
+    /// The padding following the content of the current entry.
+    padding: u64,
+}
+
+/// The largest long name or pax header read.
+const MAX_TAR_EXTENSION: u64 = 64 * 1024;
+
+impl<R: AsyncRead + Unpin> TarReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            remaining: 0,
+            padding: 0,
+        }
+    }
+
+    /// Skips to the next entry, which is `None` at the end of the archive.
+    async fn next_entry(&mut self) -> Result<Option<TarEntry>, MiboxError> {
+        let mut name = None;
+        loop {
+            let skipped = self.remaining + self.padding;
+            self.remaining = 0;
+            self.padding = 0;
+            let mut rest = (&mut self.inner).take(skipped);
+            tokio::io::copy(&mut rest, &mut tokio::io::sink())
+                .await
+                .map_err(invalid_archive)?;
+
+            let mut block = [0; TAR_BLOCK];
+            match self.inner.read_exact(&mut block).await {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(invalid_archive(e).into()),
+            }
+            if block.iter().all(|byte| *byte == 0) {
+                return Ok(None);
+            }
+            let header = tar::Header::from_byte_slice(&block);
+            let checksum = block[..148]
+                .iter()
+                .chain(&[b' '; 8])
+                .chain(&block[156..])
+                .map(|byte| u32::from(*byte))
+                .sum::<u32>();
+            if header.cksum().map_err(invalid_archive)? != checksum {
+                return Err(invalid_archive("corrupted header").into());
+            }
+            let size = header.entry_size().map_err(invalid_archive)?;
+            self.remaining = size;
+            self.padding = padding(size).len() as u64;
+
+            let kind = match header.entry_type() {
+                tar::EntryType::GNULongName => {
+                    let long_name = self.read_extension().await?;
+                    name = Some(long_name.trim_end_matches('\0').to_owned());
+                    continue;
+                }
+                tar::EntryType::XHeader => {
+                    let records = self.read_extension().await?;
+                    if let Some(path) = pax_path(&records) {
+                        name = Some(path.to_owned());
+                    }
+                    continue;
+                }
+                tar::EntryType::XGlobalHeader => continue,
+                tar::EntryType::Regular | tar::EntryType::Continuous => EntryKind::File,
+                tar::EntryType::Directory => EntryKind::Directory,
+                _ => EntryKind::Other,
+            };
+            let name = match name {
+                Some(name) => name,
+                None => String::from_utf8_lossy(&header.path_bytes()).into_owned(),
+            };
+            return Ok(Some(TarEntry { name, kind, size }));
+        }
+    }
+
+    /// The content of the current entry.
+    fn content(&mut self) -> Take<&mut R> {
+        let content = (&mut self.inner).take(self.remaining);
+        self.remaining = 0;
+        content
+    }
+
+    /// Reads the content of a long name or pax header entry.
+    async fn read_extension(&mut self) -> Result<String, MiboxError> {
+        if self.remaining > MAX_TAR_EXTENSION {
+            return Err(invalid_archive("extended header too large").into());
+        }
+        let mut content = vec![];
+        self.content()
+            .read_to_end(&mut content)
+            .await
+            .map_err(invalid_archive)?;
+        String::from_utf8(content).map_err(|e| invalid_archive(e).into())
+    }
+}
+
+/// The path of pax extended header records, each formatted as
+/// `<length> <key>=<value>\n`.
+fn pax_path(records: &str) -> Option<&str> {
+    let mut records = records;
+    while let Some((length, rest)) = records.split_once(' ') {
+        let length = length.parse::<usize>().ok()?;
+        let record = records.get(..length)?;
+        let (key, value) = record.get(record.len() - rest.len()..)?.split_once('=')?;
+        if key == "path" {
+            return Some(value.trim_end_matches('\n'));
+        }
+        records = &records[length..];
+    }
+    None
+}
@@ -0,0 +1,285 @@
+mod password;
+pub use password::*;
+mod session;
+pub use session::*;
+mod token;
+pub use token::*;
+
+use crate::{application::Application, error::MiboxError};
+use anyhow::{anyhow, Context};
+use axum::{
+    extract::{Query, Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::{
+    extract::cookie::{Cookie, Key, SameSite, SignedCookieJar},
+    headers::{
+        authorization::{Basic, Bearer},
+        Authorization, HeaderMapExt,
+    },
+};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha512};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+/// Query parameters naming entries of the drive, checked against the path
+/// prefix of an api token.
+const PATH_PARAMETERS: [&str; 3] = ["path", "from", "to"];
+
+/// The users allowed to log in and their open sessions.
+#[derive(Clone)]
+pub struct Authentication {
+    users: Arc<HashMap<String, Secret<String>>>,
+    groups: Arc<HashMap<String, Vec<String>>>,
+    sessions: Sessions,
+    tokens: ApiTokens,
+    key: Key,
+    session_ttl: Duration,
+    secure_cookies: bool,
+}
+
+impl Authentication {
+    /// Creates the authentication state, session cookies are signed with a
+    /// key derived from `hmac_secret`.
+    pub fn new(
+        hmac_secret: &Secret<String>,
+        users: impl IntoIterator<Item = (String, Secret<String>)>,
+        session_ttl: Duration,
+    ) -> Self {
+        let key = Sha512::digest(hmac_secret.expose_secret().as_bytes());
+        Self {
+            users: Arc::new(users.into_iter().collect()),
+            groups: Arc::default(),
+            sessions: Sessions::default(),
+            tokens: ApiTokens::default(),
+            key: Key::from(&key),
+            session_ttl,
+            secure_cookies: false,
+        }
+    }
+
+    /// Restricts session cookies to https, to be set when the application
+    /// is served over https.
+    pub fn with_secure_cookies(mut self, secure_cookies: bool) -> Self {
+        self.secure_cookies = secure_cookies;
+        self
+    }
+
+    /// Authenticates api tokens with `tokens`, e.g. tokens kept in records.
+    pub fn with_tokens(mut self, tokens: ApiTokens) -> Self {
+        self.tokens = tokens;
+        self
+    }
+
+    /// Sets the groups each user belongs to.
+    pub fn with_groups(mut self, groups: impl IntoIterator<Item = (String, Vec<String>)>) -> Self {
+        self.groups = Arc::new(groups.into_iter().collect());
+        self
+    }
+
+    pub fn has_user(&self, username: &str) -> bool {
+        self.users.contains_key(username)
+    }
+
+    /// The groups `username` belongs to.
+    pub fn groups(&self, username: &str) -> &[String] {
+        self.groups.get(username).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    pub fn sessions(&self) -> &Sessions {
+        &self.sessions
+    }
+
+    pub fn tokens(&self) -> &ApiTokens {
+        &self.tokens
+    }
+
+    /// Checks the credentials of a user and opens a session for them,
+    /// returning its id.
+    pub async fn login(
+        &self,
+        username: &str,
+        password: Secret<String>,
+    ) -> Result<String, MiboxError> {
+        self.verify(username, password).await?;
+        Ok(self.sessions.create(username.to_owned(), self.session_ttl))
+    }
+
+    /// Checks the password of a user.
+    pub async fn verify(&self, username: &str, password: Secret<String>) -> Result<(), MiboxError> {
+        let expected = self.users.get(username).cloned();
+        let verified =
+            tokio::task::spawn_blocking(move || verify_password(expected.as_ref(), &password))
+                .await
+                .context("failed to spawn password verification")??;
+        if !verified {
+            return Err(MiboxError::AuthError(anyhow!(
+                "invalid credentials for {:?}",
+                username
+            )));
+        }
+        Ok(())
+    }
+
+    /// The identity of `Authorization: Basic` credentials, as sent by
+    /// WebDAV clients which know neither cookies nor bearer tokens.
+    ///
+    /// The password is either the password of the user or one of their api
+    /// tokens.
+    pub async fn basic(&self, basic: &Basic) -> Result<Identity, MiboxError> {
+        if basic.password().starts_with(TOKEN_PREFIX) {
+            return self
+                .tokens
+                .authenticate(basic.password())
+                .filter(|token| token.owner == basic.username())
+                .map(Identity::from)
+                .ok_or_else(|| MiboxError::AuthError(anyhow!("unknown api token")));
+        }
+        self.verify(basic.username(), Secret::new(basic.password().to_owned()))
+            .await?;
+        Ok(Identity {
+            username: basic.username().to_owned(),
+            scope: Scope::Admin,
+            path_prefix: None,
+        })
+    }
+
+    /// The cookie handing the session id to the client.
+    pub fn session_cookie(&self, id: String) -> Cookie<'static> {
+        Cookie::build((SESSION_COOKIE, id))
+            .path("/")
+            .http_only(true)
+            .secure(self.secure_cookies)
+            .same_site(SameSite::Lax)
+            .build()
+    }
+}
+
+/// Who a request is made on behalf of and what it is allowed to do,
+/// handlers behind the authentication middleware can extract it with
+/// `Extension<Identity>`.
+///
+/// Sessions are granted every scope on the whole home of their user, api
+/// tokens only their own scope and path prefix.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub username: String,
+    pub scope: Scope,
+    pub path_prefix: Option<PathBuf>,
+}
+
+impl From<Session> for Identity {
+    fn from(session: Session) -> Self {
+        Self {
+            username: session.username,
+            scope: Scope::Admin,
+            path_prefix: None,
+        }
+    }
+}
+
+impl From<ApiToken> for Identity {
+    fn from(token: ApiToken) -> Self {
+        Self {
+            username: token.owner,
+            scope: token.scope,
+            path_prefix: token.path_prefix,
+        }
+    }
+}
+
+impl Identity {
+    /// Fails unless the identity was granted `scope`.
+    pub fn require(&self, scope: Scope) -> Result<(), MiboxError> {
+        if self.scope < scope {
+            return Err(MiboxError::InsufficientScope(format!(
+                "{:?} scope required",
+                scope
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fails unless `path` lies within the path prefix of the identity.
+    pub fn authorize_path(&self, path: impl AsRef<Path>) -> Result<(), MiboxError> {
+        match &self.path_prefix {
+            Some(prefix) if !path.as_ref().starts_with(prefix) => {
+                Err(MiboxError::InsufficientScope(format!(
+                    "{:?} is outside of {:?}",
+                    path.as_ref(),
+                    prefix
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The scope required by a request, reading requests only require the read
+/// scope.
+fn required_scope(method: &Method) -> Scope {
+    match *method {
+        Method::GET | Method::HEAD | Method::OPTIONS => Scope::Read,
+        _ if method.as_str() == "PROPFIND" => Scope::Read,
+        _ => Scope::Write,
+    }
+}
+
+/// Middleware rejecting requests that carry neither a valid session, a valid
+/// api token as `Authorization: Bearer` nor valid `Authorization: Basic`
+/// credentials, or whose method or paths are not allowed to the token.
+///
+/// The identity and the home drive of its user are made available to the
+/// handlers as request extensions.
+pub async fn require_authentication(
+    State(application): State<Application>,
+    jar: SignedCookieJar,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, MiboxError> {
+    let headers = request.headers();
+    let identity: Identity =
+        if let Some(Authorization(bearer)) = headers.typed_get::<Authorization<Bearer>>() {
+            application
+                .authentication
+                .tokens()
+                .authenticate(bearer.token())
+                .ok_or_else(|| MiboxError::InvalidToken(anyhow!("unknown api token")))?
+                .into()
+        } else if let Some(Authorization(basic)) = headers.typed_get::<Authorization<Basic>>() {
+            application.authentication.basic(&basic).await?
+        } else {
+            jar.get(SESSION_COOKIE)
+                .and_then(|cookie| application.authentication.sessions().get(cookie.value()))
+                .ok_or_else(|| MiboxError::AuthError(anyhow!("missing or expired session")))?
+                .into()
+        };
+    identity.require(required_scope(request.method()))?;
+    if identity.path_prefix.is_some() {
+        let Query(parameters) = Query::<Vec<(String, String)>>::try_from_uri(request.uri())?;
+        for (_, path) in parameters
+            .iter()
+            .filter(|(name, _)| PATH_PARAMETERS.contains(&name.as_str()))
+        {
+            identity.authorize_path(path)?;
+        }
+    }
+    let drive = application
+        .home(&identity.username)
+        .await?
+        .with_author(&identity.username);
+    request.extensions_mut().insert(identity);
+    request.extensions_mut().insert(drive);
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,53 @@
+use anyhow::Context;
+use argon2::{
+    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
+    PasswordVerifier, Version,
+};
+use once_cell::sync::Lazy;
+use secrecy::{ExposeSecret, Secret};
+
+/// Hash verified when the user is unknown, so that the time it takes to
+/// reject a login does not reveal which users exist.
+static UNKNOWN_USER_HASH: Lazy<Secret<String>> = Lazy::new(|| {
+    hash_password(&Secret::new(uuid::Uuid::new_v4().to_string()))
+        .expect("failed to hash the unknown user password")
+});
+
+fn hasher() -> Argon2<'static> {
+    Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(15000, 2, 1, None).expect("invalid argon2 parameters"),
+    )
+}
+
+/// Hashes a password into a PHC string, the format expected by the
+/// `password_hash` of a configured user.
+pub fn hash_password(password: &Secret<String>) -> anyhow::Result<Secret<String>> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let hash = hasher()
+        .hash_password(password.expose_secret().as_bytes(), &salt)
+        .map_err(anyhow::Error::msg)
+        .context("failed to hash password")?;
+    Ok(Secret::new(hash.to_string()))
+}
+
+/// Verifies a password against a PHC string, the parameters of the hash are
+/// taken from the string itself.
+///
+/// When `expected` is `None` a dummy hash is verified instead and the
+/// verification fails.
+pub fn verify_password(
+    expected: Option<&Secret<String>>,
+    password: &Secret<String>,
+) -> anyhow::Result<bool> {
+    let known = expected.is_some();
+    let expected = expected.unwrap_or(&UNKNOWN_USER_HASH);
+    let expected = PasswordHash::new(expected.expose_secret())
+        .map_err(anyhow::Error::msg)
+        .context("failed to parse password hash")?;
+    let verified = hasher()
+        .verify_password(password.expose_secret().as_bytes(), &expected)
+        .is_ok();
+    Ok(known && verified)
+}
@@ -0,0 +1,64 @@
+use rand::{distributions::Alphanumeric, Rng};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+/// Name of the signed cookie carrying the session id.
+pub const SESSION_COOKIE: &str = "mibox_session";
+
+/// A logged in user.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub username: String,
+    pub expires_at: SystemTime,
+}
+
+impl Session {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= SystemTime::now()
+    }
+}
+
+/// The sessions currently open, keyed by session id.
+///
+/// Sessions are kept on the server so that logging out or expiring a session
+/// invalidates its cookie even if a client keeps sending it.
+#[derive(Debug, Clone, Default)]
+pub struct Sessions {
+    sessions: Arc<RwLock<HashMap<String, Session>>>,
+}
+
+impl Sessions {
+    /// Opens a session for `username` valid for `ttl` and returns its id.
+    pub fn create(&self, username: String, ttl: Duration) -> String {
+        let id = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect::<String>();
+        let session = Session {
+            username,
+            expires_at: SystemTime::now() + ttl,
+        };
+        let mut sessions = self.sessions.write().expect("sessions lock poisoned");
+        sessions.retain(|_, session| !session.is_expired());
+        sessions.insert(id.clone(), session);
+        id
+    }
+
+    /// Returns the session with the given id unless it expired.
+    pub fn get(&self, id: &str) -> Option<Session> {
+        let sessions = self.sessions.read().expect("sessions lock poisoned");
+        sessions
+            .get(id)
+            .filter(|session| !session.is_expired())
+            .cloned()
+    }
+
+    pub fn remove(&self, id: &str) {
+        let mut sessions = self.sessions.write().expect("sessions lock poisoned");
+        sessions.remove(id);
+    }
+}
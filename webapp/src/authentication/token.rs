@@ -0,0 +1,217 @@
+use drive::{backend::Backend, error::DriveError, records::Fields, Drive};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Prefix of the api tokens, making them easy to spot in logs and secret
+/// scanners.
+pub const TOKEN_PREFIX: &str = "mibox_";
+
+/// The kind of the records the tokens are kept in (see
+/// `ApiTokens::with_records`).
+const RECORD_KIND: &str = "tokens";
+
+/// What an api token allows, each scope includes the ones below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Listing and downloading.
+    Read,
+    /// Uploading, creating, moving and removing as well.
+    Write,
+    /// Managing api tokens as well.
+    Admin,
+}
+
+impl Scope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Self::Read),
+            "write" => Ok(Self::Write),
+            "admin" => Ok(Self::Admin),
+            other => Err(format!(
+                "{} is not a supported scope. Use either `read`, `write` or `admin`.",
+                other
+            )),
+        }
+    }
+}
+
+/// A personal access token, only the digest of the token itself is kept.
+#[derive(Debug, Clone)]
+pub struct ApiToken {
+    pub id: String,
+    pub owner: String,
+    pub name: String,
+    pub scope: Scope,
+    /// The only part of the home of the owner the token gives access to.
+    pub path_prefix: Option<PathBuf>,
+    pub created_at: SystemTime,
+    digest: String,
+}
+
+impl ApiToken {
+    fn from_record(id: &str, fields: &Fields) -> Option<Self> {
+        let created_at = fields.get("CreatedAt")?.parse().ok()?;
+        Some(Self {
+            id: id.to_owned(),
+            owner: fields.get("Owner")?.clone(),
+            name: fields.get("Name")?.clone(),
+            scope: fields.get("Scope")?.parse().ok()?,
+            path_prefix: fields.get("PathPrefix").map(PathBuf::from),
+            created_at: UNIX_EPOCH + Duration::from_secs(created_at),
+            digest: fields.get("Digest")?.clone(),
+        })
+    }
+}
+
+/// The api tokens of every user, keyed by id.
+///
+/// Tokens are random, so a plain digest is enough to store them and to look
+/// them up without keeping them around.
+#[derive(Clone, Default)]
+pub struct ApiTokens {
+    tokens: Arc<RwLock<HashMap<String, ApiToken>>>,
+    /// Keeps the tokens across restarts when set.
+    records: Option<Drive<Backend>>,
+}
+
+fn digest(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+impl ApiTokens {
+    /// Keeps the tokens in the records of `drive`, starting from the ones
+    /// it holds.
+    pub async fn with_records(mut self, drive: Drive<Backend>) -> Result<Self, DriveError> {
+        let mut tokens = HashMap::new();
+        for (id, fields) in drive.records(RECORD_KIND).await? {
+            match ApiToken::from_record(&id, &fields) {
+                Some(token) => {
+                    tokens.insert(id, token);
+                }
+                None => tracing::warn!("ignoring the invalid record of api token {}", id),
+            }
+        }
+        self.tokens = Arc::new(RwLock::new(tokens));
+        self.records = Some(drive);
+        Ok(self)
+    }
+
+    /// Records `token`, when the tokens are kept in records.
+    async fn persist(&self, token: &ApiToken) -> Result<(), DriveError> {
+        let Some(drive) = &self.records else {
+            return Ok(());
+        };
+        let created_at = token
+            .created_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string();
+        let path_prefix = token
+            .path_prefix
+            .as_ref()
+            .map(|prefix| prefix.to_string_lossy());
+        let mut fields = vec![
+            ("Owner", token.owner.as_str()),
+            ("Name", token.name.as_str()),
+            ("Scope", token.scope.as_str()),
+            ("CreatedAt", created_at.as_str()),
+            ("Digest", token.digest.as_str()),
+        ];
+        if let Some(path_prefix) = &path_prefix {
+            fields.push(("PathPrefix", path_prefix));
+        }
+        drive.put_record(RECORD_KIND, &token.id, &fields).await
+    }
+
+    /// Creates a token for `owner` and returns it along with the token
+    /// itself, which cannot be retrieved afterwards.
+    pub async fn create(
+        &self,
+        owner: String,
+        name: String,
+        scope: Scope,
+        path_prefix: Option<PathBuf>,
+    ) -> Result<(ApiToken, String), DriveError> {
+        let token = format!("{}{}", TOKEN_PREFIX, random_string(40));
+        let api_token = ApiToken {
+            id: random_string(16),
+            owner,
+            name,
+            scope,
+            path_prefix,
+            created_at: SystemTime::now(),
+            digest: digest(&token),
+        };
+        self.persist(&api_token).await?;
+        let mut tokens = self.tokens.write().expect("api tokens lock poisoned");
+        tokens.insert(api_token.id.clone(), api_token.clone());
+        Ok((api_token, token))
+    }
+
+    /// Returns the tokens of `owner`.
+    pub fn list(&self, owner: &str) -> Vec<ApiToken> {
+        let tokens = self.tokens.read().expect("api tokens lock poisoned");
+        let mut owned = tokens
+            .values()
+            .filter(|token| token.owner == owner)
+            .cloned()
+            .collect::<Vec<_>>();
+        owned.sort_by_key(|token| token.created_at);
+        owned
+    }
+
+    /// Revokes a token of `owner`, returning whether it existed.
+    pub async fn revoke(&self, owner: &str, id: &str) -> Result<bool, DriveError> {
+        let revoked = {
+            let mut tokens = self.tokens.write().expect("api tokens lock poisoned");
+            match tokens.get(id) {
+                Some(token) if token.owner == owner => tokens.remove(id).is_some(),
+                _ => false,
+            }
+        };
+        if let (true, Some(drive)) = (revoked, &self.records) {
+            drive.delete_record(RECORD_KIND, id).await?;
+        }
+        Ok(revoked)
+    }
+
+    /// Returns the api token matching `token`.
+    pub fn authenticate(&self, token: &str) -> Option<ApiToken> {
+        let digest = digest(token);
+        let tokens = self.tokens.read().expect("api tokens lock poisoned");
+        tokens
+            .values()
+            .find(|api_token| api_token.digest == digest)
+            .cloned()
+    }
+}
@@ -1,8 +1,12 @@
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 
+use crate::{application::DEFAULT_TRASH_RETENTION, archive::ExtractLimits};
 use config::Config;
-use secrecy::Secret;
-use serde_aux::field_attributes::deserialize_number_from_string;
+use drive::{backend::S3Options, versions::VersionPolicy};
+use secrecy::{ExposeSecret, Secret};
+use serde_aux::field_attributes::{
+    deserialize_number_from_string, deserialize_option_number_from_string,
+};
 
 enum Environment {
     Local,
@@ -33,6 +37,129 @@ pub struct ApplicationSettings {
     pub log_level: String,
     pub hmac_secret: Secret<String>,
     pub drive: String,
+    /// How symbolic links inside a local drive are treated, either `deny`,
+    /// `follow-within-base` (the default) or `follow-all`.
+    pub symlinks: Option<String>,
+    pub s3: Option<S3Settings>,
+    /// Stores the contents of identical files once, in a blob store within
+    /// the drive.
+    #[serde(default)]
+    pub dedup: bool,
+    /// The SQLite database indexing the metadata of the home drives, they
+    /// are not indexed when not set.
+    pub index: Option<String>,
+    /// The users allowed to log in.
+    #[serde(default)]
+    pub users: Vec<UserSettings>,
+    /// Seconds a session stays valid after logging in.
+    #[serde(
+        default = "default_session_ttl",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub session_ttl: u64,
+    /// The directory resumable uploads are staged in until complete,
+    /// defaults to `.mibox/uploads` in local drives and to a directory of
+    /// the instance's own within the temporary directory otherwise. Files
+    /// left there by a previous run are removed on startup, so it must not
+    /// be shared between instances.
+    pub upload_staging: Option<String>,
+    /// Seconds removed files and directories stay in the trash.
+    #[serde(
+        default = "default_trash_retention",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub trash_retention: u64,
+    /// Which prior versions of overwritten files are kept.
+    #[serde(default)]
+    pub versions: VersionSettings,
+    /// Limits on what uploaded archives may extract.
+    #[serde(default)]
+    pub extract: ExtractSettings,
+}
+
+/// The retention of the prior versions of overwritten files.
+#[derive(serde::Deserialize, Clone)]
+pub struct VersionSettings {
+    /// How many prior versions of a file are kept, none are when zero.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub keep_last: usize,
+    /// Days prior versions are kept once superseded, forever when not set.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub keep_days: Option<u64>,
+    /// The size in bytes above which files keep no versions, whatever their
+    /// size when set to null.
+    #[serde(
+        default = "default_version_max_size",
+        deserialize_with = "deserialize_option_number_from_string"
+    )]
+    pub max_size: Option<u64>,
+}
+
+impl Default for VersionSettings {
+    fn default() -> Self {
+        let policy = VersionPolicy::default();
+        Self {
+            keep_last: policy.keep_last,
+            keep_days: policy
+                .keep_for
+                .map(|keep_for| keep_for.as_secs() / (24 * 60 * 60)),
+            max_size: policy.max_size,
+        }
+    }
+}
+
+impl VersionSettings {
+    pub fn policy(&self) -> VersionPolicy {
+        VersionPolicy {
+            keep_last: self.keep_last,
+            keep_for: self
+                .keep_days
+                .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+            max_size: self.max_size,
+        }
+    }
+}
+
+/// Limits applied when extracting uploaded archives, against zip bombs.
+#[derive(serde::Deserialize, Clone)]
+pub struct ExtractSettings {
+    /// The total size in bytes of the files extracted from an archive.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_size: u64,
+    /// How many files and directories an archive may hold.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_entries: u64,
+}
+
+impl Default for ExtractSettings {
+    fn default() -> Self {
+        let limits = ExtractLimits::default();
+        Self {
+            max_size: limits.max_size,
+            max_entries: limits.max_entries,
+        }
+    }
+}
+
+impl ExtractSettings {
+    pub fn limits(&self) -> ExtractLimits {
+        ExtractLimits {
+            max_size: self.max_size,
+            max_entries: self.max_entries,
+        }
+    }
+}
+
+fn default_trash_retention() -> u64 {
+    DEFAULT_TRASH_RETENTION.as_secs()
+}
+
+fn default_version_max_size() -> Option<u64> {
+    VersionPolicy::default().max_size
+}
+
+fn default_session_ttl() -> u64 {
+    24 * 60 * 60
 }
 
 impl ApplicationSettings {
@@ -41,6 +168,43 @@ impl ApplicationSettings {
     }
 }
 
+/// A user allowed to log in, `password_hash` is a PHC string as printed by
+/// `mibox-webapp hash-password`.
+#[derive(serde::Deserialize, Clone)]
+pub struct UserSettings {
+    pub username: String,
+    pub password_hash: Secret<String>,
+    /// Groups the user belongs to, access can be granted to a whole group.
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+/// Connection settings used when `drive` is a `s3://bucket/prefix` url.
+#[derive(serde::Deserialize, Clone)]
+pub struct S3Settings {
+    pub region: Option<String>,
+    pub endpoint: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<Secret<String>>,
+    #[serde(default)]
+    pub allow_http: bool,
+}
+
+impl S3Settings {
+    pub fn options(&self) -> S3Options {
+        S3Options {
+            region: self.region.clone(),
+            endpoint: self.endpoint.clone(),
+            access_key_id: self.access_key_id.clone(),
+            secret_access_key: self
+                .secret_access_key
+                .as_ref()
+                .map(|secret| secret.expose_secret().clone()),
+            allow_http: self.allow_http,
+        }
+    }
+}
+
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     // Add configuration values from a file named `configuration`. // It will look for any top-level file with an extension
     // that `config` knows how to parse: yaml, json, etc.
@@ -1,8 +1,12 @@
+use crate::{archive::ArchiveError, sharing::ShareError, uploads::UploadError};
 use axum::{
     extract::rejection::{JsonRejection, QueryRejection},
     http::{header::WWW_AUTHENTICATE, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
+    Json,
 };
+use drive::error::DriveError;
+use serde_json::json;
 
 #[derive(thiserror::Error)]
 pub enum MiboxError {
@@ -12,10 +16,28 @@ pub enum MiboxError {
     ValidationError(String),
     #[error("Authentication error")]
     AuthError(#[source] anyhow::Error),
+    #[error("Invalid token")]
+    InvalidToken(#[source] anyhow::Error),
+    #[error("{0}")]
+    InsufficientScope(String),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
     #[error(transparent)]
     JsonRejection(#[from] JsonRejection),
+    #[error("{0}")]
+    PreconditionFailed(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error(transparent)]
+    DriveError(#[from] DriveError),
+    #[error(transparent)]
+    ShareError(#[from] ShareError),
+    #[error(transparent)]
+    UploadError(#[from] UploadError),
+    #[error(transparent)]
+    ArchiveError(#[from] ArchiveError),
 }
 
 impl std::fmt::Debug for MiboxError {
@@ -37,37 +59,164 @@ pub fn error_chain_fmt(
     Ok(())
 }
 
+/// Maps a drive error onto its status code and the machine-readable code
+/// reported in the error body.
+///
+/// Failures of the storage itself are not caused by the request, so their
+/// details are kept out of the response.
+fn drive_error_status(error: &DriveError) -> (StatusCode, &'static str) {
+    match error {
+        DriveError::EntryNotFound(_) => (StatusCode::NOT_FOUND, "entry_not_found"),
+        DriveError::EntryExists(_) => (StatusCode::CONFLICT, "entry_exists"),
+        DriveError::EntryNotEmpty(_) => (StatusCode::CONFLICT, "entry_not_empty"),
+        DriveError::EntryNameInvalid(_) => (StatusCode::BAD_REQUEST, "entry_name_invalid"),
+        DriveError::EntryUnexpectedType(_) => {
+            (StatusCode::UNPROCESSABLE_ENTITY, "entry_unexpected_type")
+        }
+        DriveError::EntryRangeNotSatisfiable(_) => (
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            "entry_range_not_satisfiable",
+        ),
+        DriveError::EntryMetadata(_)
+        | DriveError::EntryRename(_)
+        | DriveError::EntryWalk(_)
+        | DriveError::EntryCreate(_)
+        | DriveError::EntryRemove(_)
+        | DriveError::EntryWrite(_)
+        | DriveError::EntryIndex(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+    }
+}
+
+/// Maps a share error onto its status code and the machine-readable code
+/// reported in the error body.
+fn share_error_status(error: &ShareError) -> (StatusCode, &'static str) {
+    match error {
+        ShareError::ShareNotFound(_) => (StatusCode::NOT_FOUND, "share_not_found"),
+        ShareError::ShareExpired(_) => (StatusCode::GONE, "share_expired"),
+        ShareError::ShareExhausted(_) => (StatusCode::GONE, "share_exhausted"),
+    }
+}
+
+/// Maps an upload error onto its status code and the machine-readable code
+/// reported in the error body.
+fn upload_error_status(error: &UploadError) -> (StatusCode, &'static str) {
+    match error {
+        UploadError::UploadNotFound(_) => (StatusCode::NOT_FOUND, "upload_not_found"),
+        UploadError::OffsetMismatch(_) => (StatusCode::CONFLICT, "offset_mismatch"),
+        UploadError::UploadBusy(_) => (StatusCode::CONFLICT, "upload_busy"),
+        UploadError::UploadTooLarge(_) => (StatusCode::PAYLOAD_TOO_LARGE, "upload_too_large"),
+    }
+}
+
+/// Maps an archive error onto its status code and the machine-readable code
+/// reported in the error body.
+fn archive_error_status(error: &ArchiveError) -> (StatusCode, &'static str) {
+    match error {
+        ArchiveError::UnsupportedArchive(_) => (StatusCode::BAD_REQUEST, "unsupported_archive"),
+        ArchiveError::InvalidArchive(_) => (StatusCode::BAD_REQUEST, "invalid_archive"),
+        ArchiveError::UnsafeEntry(_) => (StatusCode::BAD_REQUEST, "unsafe_archive_entry"),
+        ArchiveError::ArchiveTooLarge(_) => (StatusCode::PAYLOAD_TOO_LARGE, "archive_too_large"),
+    }
+}
+
+/// An error response with a JSON body carrying a machine-readable code.
+fn json_error(status: StatusCode, code: &str, message: String) -> Response {
+    let body = json!({
+        "error": {
+            "code": code,
+            "message": message,
+        }
+    });
+    (status, Json(body)).into_response()
+}
+
+/// An error response of `json_error` challenging the client to authenticate
+/// as described by `challenge`.
+fn challenge_error(
+    status: StatusCode,
+    challenge: &'static str,
+    code: &str,
+    message: String,
+) -> Response {
+    let mut header_map = HeaderMap::new();
+    header_map.insert(WWW_AUTHENTICATE, HeaderValue::from_static(challenge));
+    (header_map, json_error(status, code, message)).into_response()
+}
+
 impl IntoResponse for MiboxError {
     fn into_response(self) -> Response {
         tracing::error!("{:?}", self);
         match self {
-            MiboxError::JsonRejection(_) => {
-                (StatusCode::BAD_REQUEST, format!("{}", self)).into_response()
+            MiboxError::JsonRejection(rejection) => json_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_body",
+                rejection.body_text(),
+            ),
+            MiboxError::AuthError(_) => challenge_error(
+                StatusCode::UNAUTHORIZED,
+                r#"Basic realm="publish""#,
+                "unauthenticated",
+                self.to_string(),
+            ),
+            MiboxError::InvalidToken(_) => challenge_error(
+                StatusCode::UNAUTHORIZED,
+                r#"Bearer realm="publish", error="invalid_token""#,
+                "invalid_token",
+                self.to_string(),
+            ),
+            MiboxError::InsufficientScope(message) => challenge_error(
+                StatusCode::FORBIDDEN,
+                r#"Bearer realm="publish", error="insufficient_scope""#,
+                "insufficient_scope",
+                message,
+            ),
+            MiboxError::ValidationError(message) => {
+                json_error(StatusCode::BAD_REQUEST, "invalid_request", message)
+            }
+            MiboxError::PreconditionFailed(message) => json_error(
+                StatusCode::PRECONDITION_FAILED,
+                "precondition_failed",
+                message,
+            ),
+            MiboxError::Forbidden(message) => {
+                json_error(StatusCode::FORBIDDEN, "forbidden", message)
+            }
+            MiboxError::NotFound(message) => {
+                json_error(StatusCode::NOT_FOUND, "not_found", message)
+            }
+            MiboxError::DriveError(error) => {
+                let (status, code) = drive_error_status(&error);
+                let message = if status.is_server_error() {
+                    "Something went wrong".to_owned()
+                } else {
+                    error.to_string()
+                };
+                json_error(status, code, message)
+            }
+            MiboxError::ShareError(error) => {
+                let (status, code) = share_error_status(&error);
+                json_error(status, code, error.to_string())
             }
-            MiboxError::AuthError(_) => {
-                let header_value = HeaderValue::from_str(r#"Basic realm="publish""#).unwrap();
-                let mut header_map = HeaderMap::new();
-                header_map.insert(WWW_AUTHENTICATE, header_value);
-                (StatusCode::UNAUTHORIZED, header_map, format!("{}", self)).into_response()
+            MiboxError::UploadError(error) => {
+                let (status, code) = upload_error_status(&error);
+                json_error(status, code, error.to_string())
             }
-            MiboxError::ValidationError(_) => {
-                (StatusCode::BAD_REQUEST, "Bad request".to_owned()).into_response()
+            MiboxError::ArchiveError(error) => {
+                let (status, code) = archive_error_status(&error);
+                json_error(status, code, error.to_string())
             }
-            MiboxError::UnexpectedError(_) => (
+            MiboxError::QueryRejection(QueryRejection::FailedToDeserializeQueryString(
+                rejection,
+            )) => json_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_query",
+                rejection.body_text(),
+            ),
+            MiboxError::UnexpectedError(_) | MiboxError::QueryRejection(_) => json_error(
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "Something went wrong".to_string(),
-            )
-                .into_response(),
-            MiboxError::QueryRejection(rejection) => match rejection {
-                QueryRejection::FailedToDeserializeQueryString(value) => {
-                    (StatusCode::BAD_REQUEST, value).into_response()
-                }
-                _ => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Something went wrong".to_owned(),
-                )
-                    .into_response(),
-            },
+                "internal_error",
+                "Something went wrong".to_owned(),
+            ),
         }
     }
 }
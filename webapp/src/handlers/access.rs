@@ -0,0 +1,117 @@
+//! Resolution of the drive a request operates on.
+use crate::{
+    acl::Permission, application::Application, authentication::Identity, error::MiboxError,
+};
+use anyhow::Context;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+    Extension,
+};
+use drive::{backend::Backend, Drive};
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Debug, Deserialize)]
+struct OwnerParameters {
+    owner: Option<String>,
+}
+
+/// The access of a request to a home drive.
+///
+/// Requests operate on the home of their user unless they name another
+/// `owner` in their query, in which case the user must have been granted
+/// access to the paths involved (see `Acl`).
+#[derive(Clone)]
+pub struct Access {
+    application: Application,
+    identity: Identity,
+    home: Drive<Backend>,
+    owner: Option<String>,
+}
+
+#[async_trait]
+impl FromRequestParts<Application> for Access {
+    type Rejection = MiboxError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        application: &Application,
+    ) -> Result<Self, Self::Rejection> {
+        let Extension(identity) = Extension::<Identity>::from_request_parts(parts, application)
+            .await
+            .context("the authentication middleware did not run")?;
+        let Extension(home) = Extension::<Drive<Backend>>::from_request_parts(parts, application)
+            .await
+            .context("the authentication middleware did not run")?;
+        let Query(OwnerParameters { owner }) = Query::try_from_uri(&parts.uri)?;
+        Ok(Self {
+            application: application.clone(),
+            identity,
+            home,
+            owner,
+        })
+    }
+}
+
+impl std::fmt::Debug for Access {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Access")
+            .field("username", &self.identity.username)
+            .field("owner", &self.owner)
+            .finish()
+    }
+}
+
+impl Access {
+    /// Returns the drive to operate on once `permission` on every path of
+    /// `paths` has been checked.
+    pub async fn drive<P: AsRef<Path>>(
+        &self,
+        paths: impl IntoIterator<Item = P>,
+        permission: Permission,
+    ) -> Result<Drive<Backend>, MiboxError> {
+        let username = &self.identity.username;
+        let owner = match &self.owner {
+            Some(owner) if owner != username => owner,
+            _ => return Ok(self.home.clone()),
+        };
+        let groups = self.application.authentication.groups(username);
+        let forbidden = |path: &Path| {
+            MiboxError::Forbidden(format!("{:?} of {} is not accessible", path, owner))
+        };
+        let paths = paths.into_iter().collect::<Vec<_>>();
+        if paths.is_empty() {
+            return Err(MiboxError::Forbidden(format!(
+                "the home of {} is not accessible",
+                owner
+            )));
+        }
+        for path in &paths {
+            let path = path.as_ref();
+            if !self
+                .application
+                .acl
+                .permits(owner, username, groups, path, permission)
+            {
+                return Err(forbidden(path));
+            }
+        }
+        // Links are followed as well so that one within a granted directory
+        // does not lead to entries outside of it.
+        let drive = self.application.home(owner).await?;
+        for path in &paths {
+            let path = path.as_ref();
+            let resolved = drive.resolve(path).await?;
+            if !self
+                .application
+                .acl
+                .permits(owner, username, groups, &resolved, permission)
+            {
+                return Err(forbidden(path));
+            }
+        }
+        Ok(drive.with_author(username))
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{
+    acl::{Grant, Grantee, Permission},
+    application::Application,
+    authentication::{Identity, Scope},
+    error::MiboxError,
+};
+use axum::{
+    debug_handler,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use drive::{backend::Backend, Drive};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateGrantParameters {
+    grantee: Grantee,
+    permission: Permission,
+    /// The entry of the home the grant gives access to, along with everything
+    /// below it.
+    path_prefix: String,
+}
+
+#[derive(PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct GrantView {
+    pub id: String,
+    pub owner: String,
+    pub grantee: Grantee,
+    pub permission: Permission,
+    pub path_prefix: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<&Grant> for GrantView {
+    fn from(grant: &Grant) -> Self {
+        Self {
+            id: grant.id.clone(),
+            owner: grant.owner.clone(),
+            grantee: grant.grantee.clone(),
+            permission: grant.permission,
+            path_prefix: grant.path_prefix.to_string_lossy().into_owned(),
+            created_at: grant.created_at.into(),
+        }
+    }
+}
+
+/// Grants another user or a group access to an entry of the home of the
+/// current user.
+#[tracing::instrument(name = "Create grant", skip(application, identity, drive))]
+#[debug_handler(state = Application)]
+pub async fn create_grant_service_handler(
+    State(application): State<Application>,
+    Extension(identity): Extension<Identity>,
+    Extension(drive): Extension<Drive<Backend>>,
+    WithRejection(Json(params), _): WithRejection<Json<CreateGrantParameters>, MiboxError>,
+) -> Result<Response, MiboxError> {
+    identity.require(Scope::Admin)?;
+    identity.authorize_path(&params.path_prefix)?;
+    if let Grantee::User(user) = &params.grantee {
+        if !application.authentication.has_user(user) {
+            return Err(MiboxError::ValidationError(format!(
+                "{} is not a user",
+                user
+            )));
+        }
+    }
+    let path_prefix = if params.path_prefix.is_empty() {
+        Default::default()
+    } else {
+        drive.stat(&params.path_prefix).await?.path().clone()
+    };
+    let grant = application
+        .acl
+        .grant(
+            identity.username,
+            params.grantee,
+            params.permission,
+            path_prefix,
+        )
+        .await?;
+    let view = GrantView::from(&grant);
+    Ok((StatusCode::CREATED, Json(json!({ "result": view }))).into_response())
+}
+
+/// Lists the grants given by the current user and the ones given to them.
+#[tracing::instrument(name = "List grants", skip(application, identity))]
+#[debug_handler(state = Application)]
+pub async fn list_grants_service_handler(
+    State(application): State<Application>,
+    Extension(identity): Extension<Identity>,
+) -> Json<serde_json::Value> {
+    let groups = application.authentication.groups(&identity.username);
+    let granted = application
+        .acl
+        .granted_by(&identity.username)
+        .iter()
+        .map(GrantView::from)
+        .collect::<Vec<_>>();
+    let received = application
+        .acl
+        .granted_to(&identity.username, groups)
+        .iter()
+        .map(GrantView::from)
+        .collect::<Vec<_>>();
+    Json(json!({ "result": { "granted": granted, "received": received } }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeGrantParameters {
+    id: String,
+}
+
+#[tracing::instrument(name = "Revoke grant", skip(application, identity))]
+#[debug_handler(state = Application)]
+pub async fn revoke_grant_service_handler(
+    State(application): State<Application>,
+    Extension(identity): Extension<Identity>,
+    WithRejection(Query(params), _): WithRejection<Query<RevokeGrantParameters>, MiboxError>,
+) -> Result<StatusCode, MiboxError> {
+    identity.require(Scope::Admin)?;
+    if !application
+        .acl
+        .revoke(&identity.username, &params.id)
+        .await?
+    {
+        return Err(MiboxError::NotFound(format!(
+            "grant {} not found",
+            params.id
+        )));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,148 @@
+//! Validators and conditional request handling shared by the handlers.
+use crate::error::MiboxError;
+use axum::http::{header::ETAG, HeaderMap};
+use axum_extra::headers::{ETag, HeaderMapExt, IfMatch, IfModifiedSince, IfNoneMatch};
+use chrono::{DateTime, Utc};
+use drive::{backend::Backend, entry::Entry, error::DriveError, Drive};
+use sha2::{Digest, Sha256};
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Builds a strong entity tag out of the digest of `parts`.
+fn entity_tag<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> ETag {
+    let digest = parts
+        .into_iter()
+        .fold(Sha256::new(), |hasher, part| hasher.chain_update(part))
+        .finalize();
+    let tag = digest
+        .iter()
+        .take(16)
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    format!("\"{}\"", tag)
+        .parse()
+        .expect("a quoted hex string is a valid entity tag")
+}
+
+/// The bytes identifying the current version of a file: its inode, size and
+/// modification time, so that any write produces a new version.
+fn file_version(entry: &Entry) -> Vec<u8> {
+    let modified = entry
+        .modified()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_nanos());
+    [
+        &entry.inode().unwrap_or_default().to_be_bytes()[..],
+        &entry.size().to_be_bytes(),
+        &modified.to_be_bytes(),
+    ]
+    .concat()
+}
+
+/// The entity tag of a file.
+pub fn file_etag(entry: &Entry) -> ETag {
+    entity_tag([file_version(entry).as_slice()])
+}
+
+/// A date as sent in headers, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// The entity tag as sent in a header, e.g. in a WebDAV `getetag` property.
+pub fn etag_value(etag: &ETag) -> String {
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(etag.clone());
+    headers
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// The entity tag of a directory listing, derived from the names, types and
+/// versions of its children so that adding, removing or changing a child
+/// produces a new tag.
+pub fn directory_etag(entries: &[Entry]) -> ETag {
+    let mut children = entries
+        .iter()
+        .map(|entry| {
+            let mut child = entry.path().as_os_str().as_encoded_bytes().to_vec();
+            child.push(0);
+            if !entry.is_directory() {
+                child.extend(file_version(entry));
+            }
+            child
+        })
+        .collect::<Vec<_>>();
+    children.sort();
+    entity_tag(children.iter().map(Vec::as_slice))
+}
+
+/// The most recent modification time among a directory and its children.
+pub fn directory_last_modified(directory: &Entry, entries: &[Entry]) -> Option<SystemTime> {
+    entries
+        .iter()
+        .chain(std::iter::once(directory))
+        .filter_map(Entry::modified)
+        .max()
+}
+
+/// Returns the current entity tag of an entry or `None` if it does not exist.
+pub async fn current_etag(
+    drive: &Drive<Backend>,
+    path: impl AsRef<Path>,
+) -> Result<Option<ETag>, MiboxError> {
+    let entry = match drive.stat(path.as_ref()).await {
+        Ok(entry) => entry,
+        Err(DriveError::EntryNotFound(_)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if !entry.is_directory() {
+        return Ok(Some(file_etag(&entry)));
+    }
+    let entries = drive.entries(path.as_ref()).await?;
+    Ok(Some(directory_etag(&entries)))
+}
+
+/// Evaluates the `If-Match` precondition of a request against the entry at
+/// `path`.
+///
+/// The precondition fails when the entry does not exist or its current tag
+/// is not matched, in which case the request must not be carried out.
+pub async fn if_match(
+    drive: &Drive<Backend>,
+    path: impl AsRef<Path>,
+    headers: &HeaderMap,
+) -> Result<(), MiboxError> {
+    let Some(if_match) = headers.typed_get::<IfMatch>() else {
+        return Ok(());
+    };
+    match current_etag(drive, path.as_ref()).await? {
+        Some(etag) if if_match.precondition_passes(&etag) => Ok(()),
+        _ => Err(MiboxError::PreconditionFailed(format!(
+            "{:?} does not match the If-Match precondition",
+            path.as_ref()
+        ))),
+    }
+}
+
+/// Whether the representation cached by the client is still fresh, in which
+/// case a 304 is returned instead of the representation.
+///
+/// `If-Modified-Since` is only evaluated in the absence of `If-None-Match`.
+pub fn not_modified(headers: &HeaderMap, etag: &ETag, last_modified: Option<SystemTime>) -> bool {
+    let if_none_match = headers.typed_get::<IfNoneMatch>();
+    let if_modified_since = headers.typed_get::<IfModifiedSince>();
+    match (if_none_match, if_modified_since, last_modified) {
+        (Some(if_none_match), _, _) => !if_none_match.precondition_passes(etag),
+        (None, Some(if_modified_since), Some(last_modified)) => {
+            !if_modified_since.is_modified(last_modified)
+        }
+        _ => false,
+    }
+}
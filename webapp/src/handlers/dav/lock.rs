@@ -0,0 +1,158 @@
+//! The basic WebDAV locks: exclusive write locks kept in memory.
+use quick_xml::escape::escape;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+/// How long a lock lasts when the client does not ask for a timeout, and
+/// the longest it can ask for.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+pub const MAX_LOCK_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// An exclusive write lock on a path of the home of `username`.
+#[derive(Debug, Clone)]
+pub struct Lock {
+    pub token: String,
+    pub username: String,
+    pub path: PathBuf,
+    /// Whether the lock covers the members of a collection as well.
+    pub recursive: bool,
+    pub owner: Option<String>,
+    pub timeout: Duration,
+    pub expires_at: SystemTime,
+}
+
+impl Lock {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= SystemTime::now()
+    }
+
+    /// Whether the lock applies to `path` of the home of `username`.
+    fn covers(&self, username: &str, path: &Path) -> bool {
+        self.username == username
+            && (self.path == path || (self.recursive && path.starts_with(&self.path)))
+    }
+
+    /// The `activelock` element describing the lock.
+    pub fn active_lock(&self) -> String {
+        let owner = self
+            .owner
+            .as_ref()
+            .map(|owner| format!("<D:owner>{}</D:owner>", escape(owner.as_str())))
+            .unwrap_or_default();
+        format!(
+            "<D:activelock><D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope><D:depth>{}</D:depth>{}<D:timeout>Second-{}</D:timeout><D:locktoken><D:href>{}</D:href></D:locktoken></D:activelock>",
+            if self.recursive { "infinity" } else { "0" },
+            owner,
+            self.timeout.as_secs(),
+            self.token
+        )
+    }
+}
+
+/// The active locks keyed by token.
+#[derive(Debug, Clone, Default)]
+pub struct Locks {
+    locks: Arc<RwLock<HashMap<String, Lock>>>,
+}
+
+impl Locks {
+    /// Locks `path`, returns `None` when a lock already covers it or, for
+    /// recursive locks, covers one of its members.
+    pub fn lock(
+        &self,
+        username: &str,
+        path: &Path,
+        recursive: bool,
+        owner: Option<String>,
+        timeout: Duration,
+    ) -> Option<Lock> {
+        let mut locks = self.locks.write().expect("locks lock poisoned");
+        locks.retain(|_, lock| !lock.is_expired());
+        if locks.values().any(|lock| {
+            lock.covers(username, path)
+                || (recursive && lock.username == username && lock.path.starts_with(path))
+        }) {
+            return None;
+        }
+        let lock = Lock {
+            token: format!("opaquelocktoken:{}", uuid::Uuid::new_v4()),
+            username: username.to_owned(),
+            path: path.to_path_buf(),
+            recursive,
+            owner,
+            timeout,
+            expires_at: SystemTime::now() + timeout,
+        };
+        locks.insert(lock.token.clone(), lock.clone());
+        Some(lock)
+    }
+
+    /// Extends the lock `token` held on `path` by `timeout`.
+    pub fn refresh(
+        &self,
+        username: &str,
+        path: &Path,
+        token: &str,
+        timeout: Duration,
+    ) -> Option<Lock> {
+        let mut locks = self.locks.write().expect("locks lock poisoned");
+        let lock = locks
+            .get_mut(token)
+            .filter(|lock| !lock.is_expired() && lock.covers(username, path))?;
+        lock.timeout = timeout;
+        lock.expires_at = SystemTime::now() + timeout;
+        Some(lock.clone())
+    }
+
+    /// Releases the lock `token` held on `path`, returning whether it
+    /// existed.
+    pub fn unlock(&self, username: &str, path: &Path, token: &str) -> bool {
+        let mut locks = self.locks.write().expect("locks lock poisoned");
+        match locks.get(token) {
+            Some(lock) if lock.covers(username, path) => locks.remove(token).is_some(),
+            _ => false,
+        }
+    }
+
+    /// The active locks applying to `path`.
+    pub fn covering(&self, username: &str, path: &Path) -> Vec<Lock> {
+        let locks = self.locks.read().expect("locks lock poisoned");
+        locks
+            .values()
+            .filter(|lock| !lock.is_expired() && lock.covers(username, path))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the first active lock preventing the modification of `path`
+    /// without one of the submitted `tokens`, the locks on the members of
+    /// `path` are taken into account when `members` is set.
+    pub fn conflict(
+        &self,
+        username: &str,
+        path: &Path,
+        members: bool,
+        tokens: &[String],
+    ) -> Option<Lock> {
+        let locks = self.locks.read().expect("locks lock poisoned");
+        locks
+            .values()
+            .find(|lock| {
+                !lock.is_expired()
+                    && !tokens.contains(&lock.token)
+                    && (lock.covers(username, path)
+                        || (members && lock.username == username && lock.path.starts_with(path)))
+            })
+            .cloned()
+    }
+
+    /// Drops the locks on `path` and its members, once they are removed.
+    pub fn release_all(&self, username: &str, path: &Path) {
+        let mut locks = self.locks.write().expect("locks lock poisoned");
+        locks.retain(|_, lock| !(lock.username == username && lock.path.starts_with(path)));
+    }
+}
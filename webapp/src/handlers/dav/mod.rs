@@ -0,0 +1,615 @@
+//! A WebDAV view of the home drive, so that it can be mounted by desktops
+//! and phones.
+//!
+//! Class 1 and 2 are implemented on top of `Drive`: `PROPFIND` with a depth
+//! of 0 or 1 (a missing depth is served as 1), `GET`, `PUT`, `MKCOL`,
+//! `DELETE`, `COPY`, `MOVE` and exclusive write locks through `LOCK` and
+//! `UNLOCK`.
+mod lock;
+pub use lock::*;
+mod xml;
+
+use super::{
+    conditional,
+    disposition::{download_headers, Disposition},
+};
+use crate::{application::Application, authentication::Identity, error::MiboxError};
+use axum::{
+    body::Body,
+    extract::{Path as UrlPath, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use axum_extra::{
+    headers::{ContentLength, LastModified},
+    TypedHeader,
+};
+use bytes::Bytes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use drive::{backend::Backend, entry::Entry, error::DriveError, mime, trash::TrashItem, Drive};
+use futures::TryStreamExt;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::{io, path::Path, time::Duration};
+use xml::{Multistatus, Propfind, Propstat, DAV_NAMESPACE};
+
+/// Where the WebDAV view is mounted.
+pub const DAV_PREFIX: &str = "/dav";
+
+const ALLOW: &str = "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, MKCOL, COPY, MOVE, LOCK, UNLOCK";
+
+/// The largest `PROPFIND`, `LOCK` or `MKCOL` body accepted.
+const MAX_XML_BODY: usize = 64 * 1024;
+
+/// The live properties of the resources, in the order they are reported.
+const PROPERTIES: [&str; 9] = [
+    "creationdate",
+    "displayname",
+    "getcontentlength",
+    "getcontenttype",
+    "getetag",
+    "getlastmodified",
+    "resourcetype",
+    "supportedlock",
+    "lockdiscovery",
+];
+
+/// The characters escaped in the segments of a href.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+const XML_CONTENT_TYPE: &str = "application/xml; charset=utf-8";
+
+/// The href of a drive path, collections end with a slash.
+fn href(path: &Path, is_directory: bool) -> String {
+    let segments = path
+        .iter()
+        .map(|segment| utf8_percent_encode(&segment.to_string_lossy(), PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/");
+    if is_directory && !segments.is_empty() {
+        format!("{}/{}/", DAV_PREFIX, segments)
+    } else {
+        format!("{}/{}", DAV_PREFIX, segments)
+    }
+}
+
+/// The drive path named by the `Destination` header, which holds either an
+/// absolute url or an absolute path below the WebDAV prefix.
+///
+/// Fails with `400 Bad Request` when the header is missing or invalid and
+/// with `502 Bad Gateway` when the url names another server than `Host`.
+fn destination(headers: &HeaderMap) -> Result<String, StatusCode> {
+    let value = headers
+        .get("destination")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let path = match value.split_once("://") {
+        Some((_, location)) => {
+            let (authority, path) = location.split_at(location.find('/').unwrap_or(location.len()));
+            let host = headers
+                .get(header::HOST)
+                .and_then(|host| host.to_str().ok());
+            if !host.is_some_and(|host| host.eq_ignore_ascii_case(authority)) {
+                return Err(StatusCode::BAD_GATEWAY);
+            }
+            path
+        }
+        None => value,
+    };
+    let path = path
+        .strip_prefix(DAV_PREFIX)
+        .filter(|path| path.is_empty() || path.starts_with('/'))
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let path = percent_decode_str(path)
+        .decode_utf8()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    Ok(path.trim_matches('/').to_owned())
+}
+
+/// The lock tokens submitted through the `If` header.
+fn submitted_tokens(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all("if")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split('<').skip(1))
+        .filter_map(|tagged| tagged.split_once('>').map(|(token, _)| token.to_owned()))
+        .filter(|token| token.starts_with("opaquelocktoken:"))
+        .collect()
+}
+
+fn depth(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("depth")
+        .and_then(|depth| depth.to_str().ok())
+        .map(str::trim)
+}
+
+/// The lock timeout asked for through the `Timeout` header.
+fn lock_timeout(headers: &HeaderMap) -> Duration {
+    let requested = headers
+        .get("timeout")
+        .and_then(|timeout| timeout.to_str().ok())
+        .and_then(|timeout| timeout.split(',').next())
+        .map(str::trim);
+    match requested {
+        Some("Infinite") => MAX_LOCK_TIMEOUT,
+        Some(timeout) => timeout
+            .strip_prefix("Second-")
+            .and_then(|seconds| seconds.parse().ok())
+            .map_or(DEFAULT_LOCK_TIMEOUT, |seconds| {
+                Duration::from_secs(seconds).min(MAX_LOCK_TIMEOUT)
+            }),
+        None => DEFAULT_LOCK_TIMEOUT,
+    }
+}
+
+/// The content type of a file as guessed from its extension, which spares
+/// reading the files of a listing.
+fn content_type(entry: &Entry) -> String {
+    entry
+        .mime_type()
+        .unwrap_or_else(|| mime::OCTET_STREAM.to_owned())
+}
+
+async fn read_body(body: Body) -> Result<String, MiboxError> {
+    let body = axum::body::to_bytes(body, MAX_XML_BODY)
+        .await
+        .map_err(|e| MiboxError::ValidationError(format!("invalid body: {}", e)))?;
+    String::from_utf8(body.to_vec())
+        .map_err(|e| MiboxError::ValidationError(format!("invalid body: {}", e)))
+}
+
+fn xml_response(status: StatusCode, body: String) -> Response {
+    (status, [(header::CONTENT_TYPE, XML_CONTENT_TYPE)], body).into_response()
+}
+
+fn options() -> Response {
+    (
+        [("dav", "1, 2"), ("allow", ALLOW), ("ms-author-via", "DAV")],
+        (),
+    )
+        .into_response()
+}
+
+/// Serves the WebDAV methods on the home drive of the user.
+#[tracing::instrument(name = "WebDAV", skip(application, identity, drive, headers, body))]
+pub async fn dav_service_handler(
+    State(application): State<Application>,
+    Extension(identity): Extension<Identity>,
+    Extension(drive): Extension<Drive<Backend>>,
+    path: Option<UrlPath<String>>,
+    method: Method,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, MiboxError> {
+    let path = path
+        .map(|UrlPath(path)| path.trim_matches('/').to_owned())
+        .unwrap_or_default();
+    identity.authorize_path(&path)?;
+    let dav = Dav {
+        locks: application.locks,
+        identity,
+        drive,
+        path,
+        headers,
+    };
+    match method.as_str() {
+        "OPTIONS" => Ok(options()),
+        "GET" | "HEAD" => dav.get().await,
+        "PUT" => dav.put(body).await,
+        "DELETE" => dav.delete().await,
+        "MKCOL" => dav.mkcol(body).await,
+        "PROPFIND" => dav.propfind(body).await,
+        "COPY" => dav.copy_or_move(false).await,
+        "MOVE" => dav.copy_or_move(true).await,
+        "LOCK" => dav.lock(body).await,
+        "UNLOCK" => Ok(dav.unlock()),
+        _ => Ok((StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, ALLOW)]).into_response()),
+    }
+}
+
+/// A WebDAV request on `path` of the home drive.
+struct Dav {
+    locks: Locks,
+    identity: Identity,
+    drive: Drive<Backend>,
+    path: String,
+    headers: HeaderMap,
+}
+
+impl Dav {
+    /// A `423 Locked` response if `path`, or one of its members when
+    /// `members` is set, is locked and the lock token was not submitted.
+    fn locked(&self, path: &str, members: bool) -> Option<Response> {
+        let tokens = submitted_tokens(&self.headers);
+        let lock =
+            self.locks
+                .conflict(&self.identity.username, Path::new(path), members, &tokens)?;
+        let body = format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:error xmlns:D=\"DAV:\"><D:lock-token-submitted><D:href>{}</D:href></D:lock-token-submitted></D:error>",
+            href(&lock.path, false)
+        );
+        Some(xml_response(StatusCode::LOCKED, body))
+    }
+
+    async fn stat(&self, path: &str) -> Result<Option<Entry>, MiboxError> {
+        match self.drive.stat(path).await {
+            Ok(entry) => Ok(Some(entry)),
+            Err(DriveError::EntryNotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Whether the parent of `path` is an existing collection, as required
+    /// to create `path`.
+    async fn parent_exists(&self, path: &str) -> Result<bool, MiboxError> {
+        match Path::new(path).parent() {
+            None => Ok(true),
+            Some(parent) => Ok(self
+                .stat(&parent.to_string_lossy())
+                .await?
+                .is_some_and(|parent| parent.is_directory())),
+        }
+    }
+
+    /// Moves `entry` to the trash, as the deletions of the api do.
+    async fn trash(&self, entry: &Entry) -> Result<TrashItem, MiboxError> {
+        if entry.is_directory() {
+            Ok(self.drive.trash_directory(entry.path(), true).await?)
+        } else {
+            Ok(self.drive.trash_file(entry.path()).await?)
+        }
+    }
+
+    /// Moves `entry` to the trash and releases the locks on it.
+    async fn remove(&self, entry: &Entry) -> Result<(), MiboxError> {
+        self.trash(entry).await?;
+        self.locks
+            .release_all(&self.identity.username, entry.path());
+        Ok(())
+    }
+
+    async fn get(&self) -> Result<Response, MiboxError> {
+        let entry = self.drive.stat(&self.path).await?;
+        if entry.is_directory() {
+            return Ok((StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, ALLOW)]).into_response());
+        }
+        let mime_type = self.drive.mime_type(&entry).await?.unwrap_or_default();
+        let content_headers = download_headers(
+            &mime_type,
+            Disposition::Attachment,
+            &entry.name().unwrap_or_default(),
+        )?;
+        let content = self.drive.read(&self.path).await?;
+        Ok((
+            TypedHeader(ContentLength(entry.size())),
+            TypedHeader(conditional::file_etag(&entry)),
+            entry
+                .modified()
+                .map(|modified| TypedHeader(LastModified::from(modified))),
+            content_headers,
+            Body::from_stream(content),
+        )
+            .into_response())
+    }
+
+    async fn put(&self, body: Body) -> Result<Response, MiboxError> {
+        if let Some(locked) = self.locked(&self.path, false) {
+            return Ok(locked);
+        }
+        let existing = self.stat(&self.path).await?;
+        if self.path.is_empty() || existing.as_ref().is_some_and(Entry::is_directory) {
+            return Ok((StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, ALLOW)]).into_response());
+        }
+        if !self.parent_exists(&self.path).await? {
+            return Ok(StatusCode::CONFLICT.into_response());
+        }
+        let stream = body.into_data_stream().map_err(io::Error::other);
+        self.drive.write(stream, &self.path).await?;
+        match existing {
+            Some(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+            None => Ok(StatusCode::CREATED.into_response()),
+        }
+    }
+
+    async fn delete(&self) -> Result<Response, MiboxError> {
+        if self.path.is_empty() {
+            return Ok(StatusCode::FORBIDDEN.into_response());
+        }
+        if let Some(locked) = self.locked(&self.path, true) {
+            return Ok(locked);
+        }
+        let entry = self.drive.stat(&self.path).await?;
+        self.remove(&entry).await?;
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+
+    async fn mkcol(&self, body: Body) -> Result<Response, MiboxError> {
+        if !read_body(body).await?.is_empty() {
+            return Ok(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response());
+        }
+        if let Some(locked) = self.locked(&self.path, false) {
+            return Ok(locked);
+        }
+        if self.path.is_empty() || self.stat(&self.path).await?.is_some() {
+            return Ok((StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, ALLOW)]).into_response());
+        }
+        if !self.parent_exists(&self.path).await? {
+            return Ok(StatusCode::CONFLICT.into_response());
+        }
+        self.drive.create_directory(&self.path).await?;
+        Ok(StatusCode::CREATED.into_response())
+    }
+
+    async fn propfind(&self, body: Body) -> Result<Response, MiboxError> {
+        let members = match depth(&self.headers) {
+            Some("0") => false,
+            Some("1") | None => true,
+            Some(_) => {
+                let body = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:error xmlns:D=\"DAV:\"><D:propfind-finite-depth/></D:error>";
+                return Ok(xml_response(StatusCode::FORBIDDEN, body.to_owned()));
+            }
+        };
+        let propfind = xml::parse_propfind(&read_body(body).await?)?;
+        let entry = self.drive.stat(&self.path).await?;
+        let mut entries = vec![];
+        if members && entry.is_directory() {
+            entries = self.drive.entries(&self.path).await?;
+        }
+        entries.insert(0, entry);
+
+        let mut multistatus = Multistatus::default();
+        for entry in &entries {
+            multistatus.push(
+                &href(entry.path(), entry.is_directory()),
+                &self.propstats(entry, &propfind),
+            );
+        }
+        Ok(xml_response(StatusCode::MULTI_STATUS, multistatus.finish()))
+    }
+
+    fn propstats(&self, entry: &Entry, propfind: &Propfind) -> Vec<Propstat> {
+        match propfind {
+            Propfind::AllProp => vec![Propstat {
+                status: "200 OK",
+                properties: PROPERTIES
+                    .iter()
+                    .filter_map(|name| self.property(entry, name))
+                    .collect(),
+            }],
+            Propfind::PropName => vec![Propstat {
+                status: "200 OK",
+                properties: PROPERTIES
+                    .iter()
+                    .filter(|name| self.property(entry, name).is_some())
+                    .map(|name| format!("<D:{}/>", name))
+                    .collect(),
+            }],
+            Propfind::Prop(names) => {
+                let mut found = vec![];
+                let mut missing = vec![];
+                for name in names {
+                    match (name.namespace == DAV_NAMESPACE)
+                        .then(|| self.property(entry, &name.name))
+                        .flatten()
+                    {
+                        Some(property) => found.push(property),
+                        None => missing.push(name.element()),
+                    }
+                }
+                vec![
+                    Propstat {
+                        status: "200 OK",
+                        properties: found,
+                    },
+                    Propstat {
+                        status: "404 Not Found",
+                        properties: missing,
+                    },
+                ]
+            }
+        }
+    }
+
+    /// The value of the live property `name` of an entry, if it has one.
+    fn property(&self, entry: &Entry, name: &str) -> Option<String> {
+        let is_file = !entry.is_directory();
+        match name {
+            "creationdate" => entry.created().map(|created| {
+                let created = DateTime::<Utc>::from(created);
+                xml::text_property(name, &created.to_rfc3339_opts(SecondsFormat::Secs, true))
+            }),
+            "displayname" => Some(xml::text_property(
+                name,
+                &entry.name().unwrap_or_default(),
+            )),
+            "getcontentlength" => {
+                is_file.then(|| xml::text_property(name, &entry.size().to_string()))
+            }
+            "getcontenttype" => is_file.then(|| xml::text_property(name, &content_type(entry))),
+            "getetag" => is_file.then(|| {
+                xml::text_property(name, &conditional::etag_value(&conditional::file_etag(entry)))
+            }),
+            "getlastmodified" => entry
+                .modified()
+                .map(|modified| xml::text_property(name, &conditional::http_date(modified))),
+            "resourcetype" if is_file => Some("<D:resourcetype/>".to_owned()),
+            "resourcetype" => Some("<D:resourcetype><D:collection/></D:resourcetype>".to_owned()),
+            "supportedlock" => Some(
+                "<D:supportedlock><D:lockentry><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry></D:supportedlock>"
+                    .to_owned(),
+            ),
+            "lockdiscovery" => {
+                let locks = self
+                    .locks
+                    .covering(&self.identity.username, entry.path())
+                    .iter()
+                    .map(Lock::active_lock)
+                    .collect::<String>();
+                Some(format!("<D:lockdiscovery>{}</D:lockdiscovery>", locks))
+            }
+            _ => None,
+        }
+    }
+
+    async fn copy_or_move(&self, is_move: bool) -> Result<Response, MiboxError> {
+        let destination = match destination(&self.headers) {
+            Ok(destination) => destination,
+            Err(status) => return Ok(status.into_response()),
+        };
+        self.identity.authorize_path(&destination)?;
+        // Copying or moving onto an ancestor would remove the source along
+        // with the destination it overwrites.
+        if self.path.is_empty()
+            || destination.is_empty()
+            || Path::new(&destination).starts_with(&self.path)
+            || Path::new(&self.path).starts_with(&destination)
+        {
+            return Ok(StatusCode::FORBIDDEN.into_response());
+        }
+        let overwrite = self
+            .headers
+            .get("overwrite")
+            .is_none_or(|overwrite| !overwrite.as_bytes().eq_ignore_ascii_case(b"F"));
+        let entry = self.drive.stat(&self.path).await?;
+        let locked = match is_move {
+            true => self.locked(&self.path, true),
+            false => None,
+        };
+        if let Some(locked) = locked.or_else(|| self.locked(&destination, true)) {
+            return Ok(locked);
+        }
+        if !self.parent_exists(&destination).await? {
+            return Ok(StatusCode::CONFLICT.into_response());
+        }
+        let existing = self.stat(&destination).await?;
+        // The overwritten destination is restored if the copy or move fails.
+        let trashed = match &existing {
+            Some(_) if !overwrite => {
+                return Ok(StatusCode::PRECONDITION_FAILED.into_response());
+            }
+            Some(existing) => Some(self.trash(existing).await?),
+            None => None,
+        };
+        let transferred = match (is_move, entry.is_directory()) {
+            (true, true) => self.drive.rename_directory(&self.path, &destination).await,
+            (true, false) => self.drive.rename_file(&self.path, &destination).await,
+            (false, true) if depth(&self.headers) == Some("0") => {
+                self.drive.create_directory(&destination).await
+            }
+            (false, true) => self.drive.copy_directory(&self.path, &destination).await,
+            (false, false) => self.drive.copy_file(&self.path, &destination).await,
+        };
+        if let Err(e) = transferred {
+            if let Some(trashed) = trashed {
+                self.restore(&destination, &trashed).await?;
+            }
+            return Err(e.into());
+        }
+        if trashed.is_some() {
+            self.locks
+                .release_all(&self.identity.username, Path::new(&destination));
+        }
+        if is_move {
+            self.locks
+                .release_all(&self.identity.username, Path::new(&self.path));
+        }
+        match existing {
+            Some(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+            None => Ok(StatusCode::CREATED.into_response()),
+        }
+    }
+
+    /// Puts `trashed` back at `destination` after a failed copy or move,
+    /// removing what it left there.
+    async fn restore(&self, destination: &str, trashed: &TrashItem) -> Result<(), MiboxError> {
+        match self.stat(destination).await? {
+            Some(partial) if partial.is_directory() => {
+                self.drive.remove_directory(destination, true).await?
+            }
+            Some(_) => self.drive.remove_file(destination).await?,
+            None => {}
+        }
+        self.drive
+            .restore(trashed.id(), Some(Path::new(destination)))
+            .await?;
+        Ok(())
+    }
+
+    /// Locks the resource, or refreshes a lock when the request has no body.
+    ///
+    /// Locking a missing resource creates it empty.
+    async fn lock(&self, body: Body) -> Result<Response, MiboxError> {
+        let body = read_body(body).await?;
+        let username = &self.identity.username;
+        let path = Path::new(&self.path);
+        let timeout = lock_timeout(&self.headers);
+        if body.trim().is_empty() {
+            let refreshed = submitted_tokens(&self.headers)
+                .iter()
+                .find_map(|token| self.locks.refresh(username, path, token, timeout));
+            return match refreshed {
+                Some(lock) => Ok(lock_response(StatusCode::OK, &lock)),
+                None => Ok(StatusCode::PRECONDITION_FAILED.into_response()),
+            };
+        }
+
+        let owner = xml::parse_lock_owner(&body)?;
+        let existing = self.stat(&self.path).await?;
+        if existing.is_none() && !self.parent_exists(&self.path).await? {
+            return Ok(StatusCode::CONFLICT.into_response());
+        }
+        let recursive = depth(&self.headers) != Some("0");
+        let Some(lock) = self.locks.lock(username, path, recursive, owner, timeout) else {
+            return Ok(StatusCode::LOCKED.into_response());
+        };
+        let status = match existing {
+            Some(_) => StatusCode::OK,
+            None => {
+                let empty = futures::stream::empty::<io::Result<Bytes>>();
+                if let Err(e) = self.drive.write(empty, &self.path).await {
+                    self.locks.unlock(username, path, &lock.token);
+                    return Err(e.into());
+                }
+                StatusCode::CREATED
+            }
+        };
+        let mut response = lock_response(status, &lock);
+        let lock_token = HeaderValue::from_str(&format!("<{}>", lock.token))
+            .expect("lock tokens are valid header values");
+        response.headers_mut().insert("lock-token", lock_token);
+        Ok(response)
+    }
+
+    fn unlock(&self) -> Response {
+        let token = self
+            .headers
+            .get("lock-token")
+            .and_then(|token| token.to_str().ok())
+            .map(|token| token.trim().trim_start_matches('<').trim_end_matches('>'));
+        let Some(token) = token else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        if self
+            .locks
+            .unlock(&self.identity.username, Path::new(&self.path), token)
+        {
+            StatusCode::NO_CONTENT.into_response()
+        } else {
+            StatusCode::CONFLICT.into_response()
+        }
+    }
+}
+
+fn lock_response(status: StatusCode, lock: &Lock) -> Response {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery>{}</D:lockdiscovery></D:prop>",
+        lock.active_lock()
+    );
+    xml_response(status, body)
+}
@@ -0,0 +1,186 @@
+//! Parsing of the WebDAV request bodies and writing of the multistatus
+//! responses.
+use crate::error::MiboxError;
+use quick_xml::{
+    escape::escape,
+    events::Event,
+    name::{Namespace, ResolveResult},
+    NsReader,
+};
+
+pub const DAV_NAMESPACE: &str = "DAV:";
+
+/// A property name, made of its namespace and local name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyName {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl PropertyName {
+    /// The empty element naming the property, as reported in `propname`
+    /// responses and for missing properties.
+    pub fn element(&self) -> String {
+        if self.namespace == DAV_NAMESPACE {
+            format!("<D:{}/>", self.name)
+        } else {
+            format!(
+                "<{} xmlns=\"{}\"/>",
+                self.name,
+                escape(self.namespace.as_str())
+            )
+        }
+    }
+}
+
+/// What a `PROPFIND` asks for.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Propfind {
+    AllProp,
+    PropName,
+    Prop(Vec<PropertyName>),
+}
+
+fn invalid(e: impl std::fmt::Display) -> MiboxError {
+    MiboxError::ValidationError(format!("invalid xml body: {}", e))
+}
+
+fn namespace(resolved: &ResolveResult) -> String {
+    match resolved {
+        ResolveResult::Bound(Namespace(namespace)) => {
+            String::from_utf8_lossy(namespace).into_owned()
+        }
+        _ => String::new(),
+    }
+}
+
+/// Parses the body of a `PROPFIND`, an empty body asks for every property.
+pub fn parse_propfind(body: &str) -> Result<Propfind, MiboxError> {
+    if body.trim().is_empty() {
+        return Ok(Propfind::AllProp);
+    }
+    let mut reader = NsReader::from_str(body);
+    reader.trim_text(true);
+    // The elements opened so far, the requested properties are the children
+    // of `propfind/prop`.
+    let mut path: Vec<String> = vec![];
+    let mut propfind = None;
+    let mut properties = vec![];
+    loop {
+        let (resolved, event) = reader.read_resolved_event().map_err(invalid)?;
+        match event {
+            Event::Start(ref element) | Event::Empty(ref element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+                let is_dav = namespace(&resolved) == DAV_NAMESPACE;
+                match (path.as_slice(), name.as_str()) {
+                    ([root], "allprop") if root == "propfind" && is_dav => {
+                        propfind = Some(Propfind::AllProp)
+                    }
+                    ([root], "propname") if root == "propfind" && is_dav => {
+                        propfind = Some(Propfind::PropName)
+                    }
+                    ([root, prop], _) if root == "propfind" && prop == "prop" => {
+                        properties.push(PropertyName {
+                            namespace: namespace(&resolved),
+                            name: name.clone(),
+                        })
+                    }
+                    _ => {}
+                }
+                if matches!(event, Event::Start(_)) {
+                    path.push(name);
+                }
+            }
+            Event::End(_) => {
+                path.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    match propfind {
+        Some(propfind) => Ok(propfind),
+        None if !properties.is_empty() => Ok(Propfind::Prop(properties)),
+        None => Err(invalid("propfind without allprop, propname or prop")),
+    }
+}
+
+/// Parses the body of a `LOCK` and returns the text of its owner, if any.
+pub fn parse_lock_owner(body: &str) -> Result<Option<String>, MiboxError> {
+    let mut reader = NsReader::from_str(body);
+    reader.trim_text(true);
+    let mut in_owner = 0;
+    let mut owner = String::new();
+    let mut is_lockinfo = false;
+    loop {
+        let (resolved, event) = reader.read_resolved_event().map_err(invalid)?;
+        match event {
+            Event::Start(ref element) => {
+                let name = element.local_name();
+                if namespace(&resolved) == DAV_NAMESPACE {
+                    is_lockinfo |= name.as_ref() == b"lockinfo";
+                    if name.as_ref() == b"owner" || in_owner > 0 {
+                        in_owner += 1;
+                    }
+                }
+            }
+            Event::End(_) if in_owner > 0 => in_owner -= 1,
+            Event::Text(text) if in_owner > 0 => owner.push_str(&text.unescape().map_err(invalid)?),
+            Event::Empty(ref element) => {
+                is_lockinfo |= namespace(&resolved) == DAV_NAMESPACE
+                    && element.local_name().as_ref() == b"lockinfo";
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !is_lockinfo {
+        return Err(invalid("lock without lockinfo"));
+    }
+    Ok((!owner.is_empty()).then_some(owner))
+}
+
+/// The properties of a resource grouped by status.
+pub struct Propstat {
+    pub status: &'static str,
+    pub properties: Vec<String>,
+}
+
+/// Builds a `207 Multi-Status` body.
+#[derive(Default)]
+pub struct Multistatus {
+    responses: Vec<String>,
+}
+
+impl Multistatus {
+    pub fn push(&mut self, href: &str, propstats: &[Propstat]) {
+        let propstats = propstats
+            .iter()
+            .filter(|propstat| !propstat.properties.is_empty())
+            .map(|propstat| {
+                format!(
+                    "<D:propstat><D:prop>{}</D:prop><D:status>HTTP/1.1 {}</D:status></D:propstat>",
+                    propstat.properties.concat(),
+                    propstat.status
+                )
+            })
+            .collect::<String>();
+        self.responses.push(format!(
+            "<D:response><D:href>{}</D:href>{}</D:response>",
+            escape(href),
+            propstats
+        ));
+    }
+
+    pub fn finish(self) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">{}</D:multistatus>",
+            self.responses.concat()
+        )
+    }
+}
+
+/// A DAV property holding text.
+pub fn text_property(name: &str, value: &str) -> String {
+    format!("<D:{name}>{}</D:{name}>", escape(value))
+}
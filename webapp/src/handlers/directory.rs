@@ -1,31 +1,43 @@
-use crate::{application::Application, error::MiboxError};
+use super::{
+    access::Access,
+    conditional,
+    disposition::{content_disposition, Disposition},
+};
+use crate::{
+    acl::Permission,
+    application::Application,
+    archive::{stream_archive, ArchiveFormat},
+    error::MiboxError,
+};
 use anyhow::{anyhow, Context};
 use axum::{
+    body::Body,
     debug_handler,
-    extract::{Query, State},
-    http::{header::ACCEPT, HeaderMap, StatusCode},
-    response::IntoResponse,
+    extract::Query,
+    http::{header, header::ACCEPT, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
 };
-use axum_extra::extract::WithRejection;
-use drive::Drive;
+use axum_extra::{extract::WithRejection, headers::LastModified, TypedHeader};
+use chrono::{DateTime, Utc};
+use drive::{backend::Backend, entry::Entry, Drive};
+use futures::future::try_join_all;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::path::PathBuf;
 
 #[derive(Debug, Deserialize)]
 pub struct CreateDirParameters {
     path: String,
 }
 
-#[tracing::instrument(name = "Create directory", skip(application))]
-#[debug_handler]
+#[tracing::instrument(name = "Create directory", skip(access))]
+#[debug_handler(state = Application)]
 pub async fn create_dir_service_handler(
-    State(application): State<Application>,
+    access: Access,
     WithRejection(Query(params), _): WithRejection<Query<CreateDirParameters>, MiboxError>,
 ) -> Result<StatusCode, MiboxError> {
-    Drive::new(application.drive)
-        .create_directory(params.path)
-        .await
-        .context("create")?;
+    let drive = access.drive([&params.path], Permission::Write).await?;
+    drive.create_directory(params.path).await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -39,36 +51,79 @@ pub struct ListParameters {
 pub struct DirectoryView {
     pub path: String,
     pub is_directory: bool,
+    pub is_symlink: bool,
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+    pub created: Option<DateTime<Utc>>,
+    /// Unix permission bits in octal notation, e.g. `0644`.
+    pub permissions: Option<String>,
+    pub mime_type: Option<String>,
 }
 
-#[tracing::instrument(name = "Drive listing", skip(application, headers))]
-#[debug_handler]
+impl DirectoryView {
+    /// The view of an entry, entries without a name have none.
+    pub fn from_entry(entry: &Entry) -> Option<Self> {
+        Some(DirectoryView {
+            path: entry.name()?,
+            is_directory: entry.is_directory(),
+            is_symlink: entry.is_symlink(),
+            size: entry.size(),
+            modified: entry.modified().map(DateTime::from),
+            created: entry.created().map(DateTime::from),
+            permissions: entry.permissions().map(|mode| format!("{:04o}", mode)),
+            mime_type: entry.mime_type(),
+        })
+    }
+}
+
+/// How many files of a listing are sniffed for their MIME type at once.
+const SNIFF_CONCURRENCY: usize = 16;
+
+/// The views of `entries`, with MIME types sniffed from the content of files
+/// that have no known extension (see `Drive::mime_type`).
+pub async fn directory_views(
+    drive: &Drive<Backend>,
+    entries: &[Entry],
+) -> Result<Vec<DirectoryView>, MiboxError> {
+    let mut mime_types = Vec::with_capacity(entries.len());
+    for chunk in entries.chunks(SNIFF_CONCURRENCY) {
+        mime_types.extend(try_join_all(chunk.iter().map(|entry| drive.mime_type(entry))).await?);
+    }
+    Ok(entries
+        .iter()
+        .zip(mime_types)
+        .filter_map(|(entry, mime_type)| {
+            let view = DirectoryView::from_entry(entry)?;
+            Some(DirectoryView { mime_type, ..view })
+        })
+        .collect())
+}
+
+#[tracing::instrument(name = "Drive listing", skip(access, headers))]
+#[debug_handler(state = Application)]
 pub async fn list_service_handler(
-    State(application): State<Application>,
+    access: Access,
     WithRejection(Query(params), _): WithRejection<Query<ListParameters>, MiboxError>,
     headers: HeaderMap,
-) -> Result<impl IntoResponse, MiboxError> {
-    let entries = Drive::new(application.drive)
-        .entries(params.path)
-        .await
-        .context("list")?;
+) -> Result<Response, MiboxError> {
+    let drive = access.drive([&params.path], Permission::Read).await?;
+    let directory = drive.stat(&params.path).await?;
+    let entries = drive.entries(params.path).await?;
 
-    let view = entries
-        .iter()
-        .map(|elem| {
-            let path = match elem.name() {
-                Some(path) => path,
-                None => return None,
-            };
-
-            Some(DirectoryView {
-                is_directory: elem.is_directory(),
-                path,
-            })
-        })
-        .filter(Option::is_some)
-        .flatten()
-        .collect::<Vec<DirectoryView>>();
+    let etag = conditional::directory_etag(&entries);
+    let modified = conditional::directory_last_modified(&directory, &entries);
+    let last_modified = modified.map(|modified| TypedHeader(LastModified::from(modified)));
+    if conditional::not_modified(&headers, &etag, modified) {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            TypedHeader(etag),
+            last_modified,
+            (),
+        )
+            .into_response());
+    }
+
+    let view = directory_views(&drive, &entries).await?;
 
     let accept_header = headers.get(ACCEPT).context("no accept header")?;
     let accept_header = accept_header.to_str().context("invalid accept header")?;
@@ -77,7 +132,7 @@ pub async fn list_service_handler(
             "result" : view
         });
         let j = serde_json::to_value(j).context("error serializing response")?;
-        return Ok(axum::Json(j));
+        return Ok((TypedHeader(etag), last_modified, axum::Json(j)).into_response());
     }
     return Err(MiboxError::UnexpectedError(anyhow!(
         "invalid accept header value"
@@ -87,18 +142,28 @@ pub async fn list_service_handler(
 #[derive(Debug, Deserialize)]
 pub struct RemoveDirParameters {
     path: String,
+    /// Whether the contents of the directory are removed as well, otherwise
+    /// only an empty directory can be removed.
+    #[serde(default = "recursive_default")]
+    recursive: bool,
 }
 
-#[tracing::instrument(name = "Remove directory", skip(application))]
-#[debug_handler]
+fn recursive_default() -> bool {
+    true
+}
+
+#[tracing::instrument(name = "Remove directory", skip(access, headers))]
+#[debug_handler(state = Application)]
 pub async fn remove_dir_service_handler(
-    State(application): State<Application>,
+    access: Access,
     WithRejection(Query(params), _): WithRejection<Query<RemoveDirParameters>, MiboxError>,
+    headers: HeaderMap,
 ) -> Result<StatusCode, MiboxError> {
-    let path = application.drive.join(params.path.clone());
-    tokio::fs::remove_dir_all(path.clone())
-        .await
-        .context(format!("error removing directory {:?}", path))?;
+    let drive = access.drive([&params.path], Permission::Write).await?;
+    conditional::if_match(&drive, &params.path, &headers).await?;
+    drive
+        .trash_directory(&params.path, params.recursive)
+        .await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -109,16 +174,55 @@ pub struct UpdateDirParameters {
     to: String,
 }
 
-#[tracing::instrument(name = "Update directory", skip(application))]
-#[debug_handler]
+#[tracing::instrument(name = "Update directory", skip(access, headers))]
+#[debug_handler(state = Application)]
 pub async fn update_dir_service_handler(
-    State(application): State<Application>,
+    access: Access,
     WithRejection(Query(params), _): WithRejection<Query<UpdateDirParameters>, MiboxError>,
+    headers: HeaderMap,
 ) -> Result<StatusCode, MiboxError> {
-    Drive::new(application.drive)
-        .rename_directory(params.from, params.to)
-        .await
-        .context("rename")?;
+    let drive = access
+        .drive([&params.from, &params.to], Permission::Write)
+        .await?;
+    conditional::if_match(&drive, &params.from, &headers).await?;
+    drive.rename_directory(params.from, params.to).await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
+
+#[derive(Debug, Deserialize)]
+pub struct ArchiveParameters {
+    path: String,
+    #[serde(default)]
+    format: ArchiveFormat,
+}
+
+/// Downloads a directory and everything below it as a single archive, which
+/// is streamed while the tree is walked.
+#[tracing::instrument(name = "Directory archive", skip(access))]
+#[debug_handler(state = Application)]
+pub async fn archive_service_handler(
+    access: Access,
+    WithRejection(Query(params), _): WithRejection<Query<ArchiveParameters>, MiboxError>,
+) -> Result<Response, MiboxError> {
+    let drive = access.drive([&params.path], Permission::Read).await?;
+    let entries = drive.entries(&params.path).await?;
+    let root = PathBuf::from(&params.path);
+    let name = root
+        .file_name()
+        .map_or("home".into(), |name| name.to_string_lossy());
+    let file_name = format!("{}.{}", name, params.format.extension());
+    let content_disposition = content_disposition(Disposition::Attachment, &file_name);
+    let archive = stream_archive(drive, root.clone(), entries, params.format);
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(params.format.content_type()),
+            ),
+            (header::CONTENT_DISPOSITION, content_disposition),
+        ],
+        Body::from_stream(archive),
+    )
+        .into_response())
+}
@@ -0,0 +1,91 @@
+//! The `Content-Disposition` of downloads (RFC 6266), with file names
+//! outside of ASCII encoded following RFC 5987, and the other headers
+//! downloads of user files are served with.
+use anyhow::Context;
+use axum::http::{header, HeaderName, HeaderValue};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::Deserialize;
+
+/// The characters of `attr-char` in RFC 5987 left as is in an extended
+/// parameter value.
+const ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+/// Whether a downloaded file is displayed by the browser or saved.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Disposition {
+    Inline,
+    #[default]
+    Attachment,
+}
+
+/// The `Content-Disposition` of a download of `file_name`.
+///
+/// Names that are not plain ASCII are given in a `filename*` parameter, with
+/// an ASCII `filename` fallback for clients that do not understand it.
+pub fn content_disposition(disposition: Disposition, file_name: &str) -> HeaderValue {
+    let disposition = match disposition {
+        Disposition::Inline => "inline",
+        Disposition::Attachment => "attachment",
+    };
+    let fallback = file_name
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    let value = if fallback == file_name {
+        format!("{}; filename=\"{}\"", disposition, file_name)
+    } else {
+        format!(
+            "{}; filename=\"{}\"; filename*=UTF-8''{}",
+            disposition,
+            fallback,
+            utf8_percent_encode(file_name, ATTR_CHAR)
+        )
+    };
+    HeaderValue::try_from(value).expect("content disposition is ASCII")
+}
+
+/// The headers of a download of `file_name` holding `mime_type` contents.
+///
+/// Browsers must neither second-guess the type of the files they show nor
+/// run the scripts of html or svg files, which would act on behalf of
+/// whoever views them.
+pub fn download_headers(
+    mime_type: &str,
+    disposition: Disposition,
+    file_name: &str,
+) -> anyhow::Result<[(HeaderName, HeaderValue); 4]> {
+    Ok([
+        (
+            header::CONTENT_TYPE,
+            HeaderValue::try_from(mime_type).context("file content type")?,
+        ),
+        (
+            header::CONTENT_DISPOSITION,
+            content_disposition(disposition, file_name),
+        ),
+        (
+            header::X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        ),
+        (
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("sandbox"),
+        ),
+    ])
+}
@@ -1,33 +1,46 @@
-use crate::{application::Application, error::MiboxError};
+use super::{
+    access::Access,
+    conditional,
+    disposition::{download_headers, Disposition},
+};
+use crate::{
+    acl::Permission,
+    application::Application,
+    archive::{extract_archive, ArchiveError, ArchiveFormat},
+    error::MiboxError,
+};
 use anyhow::Context;
 use axum::{
     body::Body,
     debug_handler,
     extract::{Multipart, Query, State},
-    http::{header, StatusCode},
-    response::IntoResponse,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use axum_extra::{
+    extract::WithRejection,
+    headers::{AcceptRanges, ContentLength, ContentRange, IfRange, LastModified, Range},
+    TypedHeader,
 };
-use axum_extra::extract::WithRejection;
-use drive::Drive;
 use futures::TryStreamExt;
 use serde::Deserialize;
-use std::io;
+use std::{io, ops::Bound, path::Path};
 
 #[derive(Debug, Deserialize)]
 pub struct DeleteParameters {
     path: String,
 }
 
-#[tracing::instrument(name = "File delete", skip(application))]
-#[debug_handler]
+#[tracing::instrument(name = "File delete", skip(access, headers))]
+#[debug_handler(state = Application)]
 pub async fn delete_service_handler(
-    State(application): State<Application>,
+    access: Access,
     WithRejection(Query(params), _): WithRejection<Query<DeleteParameters>, MiboxError>,
+    headers: HeaderMap,
 ) -> Result<StatusCode, MiboxError> {
-    let path = application.drive.join(params.path.clone());
-    tokio::fs::remove_file(path.clone())
-        .await
-        .context(format!("error removing file {:?}", path))?;
+    let drive = access.drive([&params.path], Permission::Write).await?;
+    conditional::if_match(&drive, &params.path, &headers).await?;
+    drive.trash_file(&params.path).await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -35,43 +48,120 @@ pub async fn delete_service_handler(
 #[derive(Debug, Deserialize)]
 pub struct DownloadParameters {
     path: String,
+    /// Whether the file is displayed by the browser rather than saved.
+    #[serde(default)]
+    disposition: Disposition,
 }
 
-#[tracing::instrument(name = "File download", skip(application))]
-#[debug_handler]
+#[tracing::instrument(name = "File download", skip(access, headers))]
+#[debug_handler(state = Application)]
 pub async fn download_service_handler(
-    State(application): State<Application>,
+    access: Access,
     WithRejection(Query(params), _): WithRejection<Query<DownloadParameters>, MiboxError>,
-) -> Result<impl IntoResponse, MiboxError> {
-    let d = Drive::new(application.drive)
-        .read(params.path.clone())
-        .await
-        .context("file stream")?;
-    let body = Body::from_stream(d);
+    range: Option<TypedHeader<Range>>,
+    if_range: Option<TypedHeader<IfRange>>,
+    headers: HeaderMap,
+) -> Result<Response, MiboxError> {
+    let drive = access.drive([&params.path], Permission::Read).await?;
+    let entry = drive.stat(&params.path).await?;
+    let size = entry.size();
+    let etag = conditional::file_etag(&entry);
+    let last_modified = entry.modified().map(LastModified::from);
+    if conditional::not_modified(&headers, &etag, entry.modified()) {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            TypedHeader(etag),
+            last_modified.map(TypedHeader),
+            (),
+        )
+            .into_response());
+    }
+
+    let mime_type = drive.mime_type(&entry).await?.unwrap_or_default();
+    let content_headers = download_headers(
+        &mime_type,
+        params.disposition,
+        &entry.name().unwrap_or_default(),
+    )?;
 
-    let headers = [
-        (header::CONTENT_TYPE, "text/toml; charset=utf-8".to_owned()),
-        (
-            header::CONTENT_DISPOSITION,
-            format!(
-                "attachment; filename=\"{}\"",
-                params.path.split('/').last().unwrap_or("")
-            ),
-        ),
-    ];
+    // A stale If-Range means the client holds an outdated copy, in which case
+    // the whole file is sent instead of the requested range.
+    let range = range.filter(|_| {
+        !if_range.is_some_and(|TypedHeader(if_range)| {
+            if_range.is_modified(Some(&etag), last_modified.as_ref())
+        })
+    });
+    let Some(TypedHeader(range)) = range else {
+        let d = drive.read(&params.path).await?;
+        return Ok((
+            TypedHeader(AcceptRanges::bytes()),
+            TypedHeader(ContentLength(size)),
+            TypedHeader(etag),
+            last_modified.map(TypedHeader),
+            content_headers,
+            Body::from_stream(d),
+        )
+            .into_response());
+    };
 
-    return Ok((headers, body));
+    let Some(range) = satisfiable_range(&range, size) else {
+        return Ok((
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            TypedHeader(ContentRange::unsatisfied_bytes(size)),
+        )
+            .into_response());
+    };
+    let content_range = ContentRange::bytes(range.clone(), size).context("file content range")?;
+    let content_length = range.end - range.start;
+    let d = drive.read_range(&params.path, range).await?;
+    Ok((
+        StatusCode::PARTIAL_CONTENT,
+        TypedHeader(AcceptRanges::bytes()),
+        TypedHeader(ContentLength(content_length)),
+        TypedHeader(content_range),
+        TypedHeader(etag),
+        last_modified.map(TypedHeader),
+        content_headers,
+        Body::from_stream(d),
+    )
+        .into_response())
+}
+
+/// Resolves the byte range requested by a `Range` header against a file of
+/// `size` bytes.
+///
+/// Only single ranges are served, a request for several ranges resolves to
+/// the first one.
+fn satisfiable_range(range: &Range, size: u64) -> Option<std::ops::Range<u64>> {
+    let (start, end) = range.satisfiable_ranges(size).next()?;
+    let start = match start {
+        Bound::Included(start) => start,
+        Bound::Excluded(start) => start.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match end {
+        Bound::Included(end) => end.saturating_add(1).min(size),
+        Bound::Excluded(end) => end.min(size),
+        Bound::Unbounded => size,
+    };
+    (start < end).then_some(start..end)
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UploadParameters {
     path: String,
+    /// Whether the uploaded files are zip, tar or tar.gz archives to be
+    /// extracted into `path`.
+    #[serde(default)]
+    extract: bool,
 }
 
-#[tracing::instrument(name = "File upload", skip(application))]
+#[tracing::instrument(name = "File upload", skip(application, access, headers))]
 pub async fn upload_service_handler(
     State(application): State<Application>,
+    access: Access,
     WithRejection(Query(params), _): WithRejection<Query<UploadParameters>, MiboxError>,
+    headers: HeaderMap,
     mut multipart: Multipart,
 ) -> Result<StatusCode, MiboxError> {
     while let Ok(Some(field)) = multipart.next_field().await {
@@ -80,12 +170,25 @@ pub async fn upload_service_handler(
         } else {
             continue;
         };
-        let stream = field.map_err(|e| io::Error::new(io::ErrorKind::Other, e));
-        let path = application.drive.join(params.path.clone());
-        Drive::new(path.clone())
-            .write(stream, file_name)
-            .await
-            .context("error uploading file")?;
+        if params.extract {
+            let format = ArchiveFormat::from_file_name(&file_name).ok_or_else(|| {
+                ArchiveError::UnsupportedArchive(format!("{:?} is not an archive", file_name))
+            })?;
+            let drive = access.drive([&params.path], Permission::Write).await?;
+            let archive = application
+                .uploads
+                .stage(field.map_err(io::Error::other))
+                .await?;
+            let target = Path::new(&params.path);
+            let limits = &application.extract_limits;
+            extract_archive(&drive, target, archive.path(), format, limits).await?;
+            continue;
+        }
+        let path = Path::new(&params.path).join(file_name);
+        let drive = access.drive([&path], Permission::Write).await?;
+        conditional::if_match(&drive, &path, &headers).await?;
+        let stream = field.map_err(io::Error::other);
+        drive.write(stream, path).await?;
     }
     Ok(StatusCode::OK)
 }
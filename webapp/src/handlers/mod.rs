@@ -1,6 +1,18 @@
+mod access;
+pub mod acl;
+mod conditional;
+pub mod dav;
 pub mod directory;
+pub mod disposition;
 mod fallback;
 pub mod file;
 pub use fallback::*;
 mod health;
 pub use health::*;
+pub mod search;
+pub mod session;
+pub mod share;
+pub mod token;
+pub mod trash;
+pub mod upload;
+pub mod versions;
@@ -0,0 +1,132 @@
+//! Search of a home drive by name and metadata (see `Drive::search`).
+use super::access::Access;
+use crate::{
+    acl::Permission, application::Application, authentication::Identity, error::MiboxError,
+};
+use axum::{debug_handler, extract::Query, Extension, Json};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use drive::search::{EntryKind, SearchHit, SearchQuery};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// How many results are returned at most per request.
+const MAX_LIMIT: usize = 500;
+
+fn default_limit() -> usize {
+    50
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchKind {
+    File,
+    Directory,
+}
+
+impl From<SearchKind> for EntryKind {
+    fn from(kind: SearchKind) -> Self {
+        match kind {
+            SearchKind::File => EntryKind::File,
+            SearchKind::Directory => EntryKind::Directory,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchParameters {
+    /// Text names contain, or a glob they match when holding `*`, `?` or `[`.
+    #[serde(default)]
+    q: String,
+    #[serde(rename = "type")]
+    kind: Option<SearchKind>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    modified_after: Option<DateTime<Utc>>,
+    modified_before: Option<DateTime<Utc>>,
+    /// The directory searched, the root of the drive by default.
+    #[serde(default)]
+    path: String,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+#[derive(PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct SearchView {
+    /// The path of the entry from the root of the drive.
+    pub path: String,
+    pub is_directory: bool,
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+    pub mime_type: Option<String>,
+    pub score: u32,
+}
+
+impl From<&SearchHit> for SearchView {
+    fn from(hit: &SearchHit) -> Self {
+        let entry = hit.entry();
+        Self {
+            path: entry.path().to_string_lossy().into_owned(),
+            is_directory: entry.is_directory(),
+            size: entry.size(),
+            modified: entry.modified().map(DateTime::from),
+            mime_type: entry.mime_type(),
+            score: hit.score(),
+        }
+    }
+}
+
+/// Searches the entries within `path`, best matches first, returning a page
+/// of `limit` results from `offset` along with the total number of results.
+#[tracing::instrument(name = "Search", skip(access, identity))]
+#[debug_handler(state = Application)]
+pub async fn search_service_handler(
+    access: Access,
+    Extension(identity): Extension<Identity>,
+    WithRejection(Query(params), _): WithRejection<Query<SearchParameters>, MiboxError>,
+) -> Result<Json<serde_json::Value>, MiboxError> {
+    if let (Some(min), Some(max)) = (params.min_size, params.max_size) {
+        if min > max {
+            return Err(MiboxError::ValidationError(format!(
+                "min_size {} is greater than max_size {}",
+                min, max
+            )));
+        }
+    }
+    if params.limit == 0 || params.limit > MAX_LIMIT {
+        return Err(MiboxError::ValidationError(format!(
+            "limit must be between 1 and {}",
+            MAX_LIMIT
+        )));
+    }
+    let drive = access.drive([&params.path], Permission::Read).await?;
+    let query = SearchQuery {
+        pattern: params.q,
+        kind: params.kind.map(EntryKind::from),
+        min_size: params.min_size,
+        max_size: params.max_size,
+        modified_after: params.modified_after.map(Into::into),
+        modified_before: params.modified_before.map(Into::into),
+        within: params.path.into(),
+    };
+    let hits = drive
+        .search(&query)
+        .await?
+        .into_iter()
+        .filter(|hit| identity.authorize_path(hit.entry().path()).is_ok())
+        .collect::<Vec<_>>();
+    let views = hits
+        .iter()
+        .skip(params.offset)
+        .take(params.limit)
+        .map(SearchView::from)
+        .collect::<Vec<_>>();
+    Ok(Json(json!({
+        "result": views,
+        "total": hits.len(),
+        "offset": params.offset,
+        "limit": params.limit,
+    })))
+}
@@ -0,0 +1,47 @@
+use crate::{application::Application, authentication::SESSION_COOKIE, error::MiboxError};
+use axum::{debug_handler, extract::State, http::StatusCode, Json};
+use axum_extra::extract::{cookie::Cookie, SignedCookieJar, WithRejection};
+use secrecy::Secret;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct Credentials {
+    username: String,
+    password: Secret<String>,
+}
+
+#[tracing::instrument(
+    name = "Login",
+    skip(application, jar, credentials),
+    fields(username = %credentials.username)
+)]
+#[debug_handler(state = Application)]
+pub async fn login_service_handler(
+    State(application): State<Application>,
+    jar: SignedCookieJar,
+    WithRejection(Json(credentials), _): WithRejection<Json<Credentials>, MiboxError>,
+) -> Result<(SignedCookieJar, StatusCode), MiboxError> {
+    let id = application
+        .authentication
+        .login(&credentials.username, credentials.password)
+        .await?;
+    let cookie = application.authentication.session_cookie(id);
+
+    Ok((jar.add(cookie), StatusCode::NO_CONTENT))
+}
+
+#[tracing::instrument(name = "Logout", skip(application, jar))]
+#[debug_handler(state = Application)]
+pub async fn logout_service_handler(
+    State(application): State<Application>,
+    jar: SignedCookieJar,
+) -> (SignedCookieJar, StatusCode) {
+    if let Some(cookie) = jar.get(SESSION_COOKIE) {
+        application.authentication.sessions().remove(cookie.value());
+    }
+
+    (
+        jar.remove(Cookie::build(SESSION_COOKIE).path("/")),
+        StatusCode::NO_CONTENT,
+    )
+}
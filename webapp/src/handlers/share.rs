@@ -0,0 +1,178 @@
+use super::{
+    directory::directory_views,
+    disposition::{download_headers, Disposition},
+};
+use crate::{
+    application::Application,
+    authentication::Identity,
+    error::MiboxError,
+    sharing::{Share, ShareOptions},
+};
+use anyhow::Context;
+use axum::{
+    body::Body,
+    debug_handler,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::{
+    extract::WithRejection,
+    headers::{authorization::Basic, Authorization, ContentLength},
+    TypedHeader,
+};
+use chrono::{DateTime, Utc};
+use drive::{backend::Backend, Drive};
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateShareParameters {
+    path: String,
+    /// Seconds the share stays valid, a week when not set.
+    expires_in: Option<u64>,
+    password: Option<Secret<String>>,
+    max_downloads: Option<u64>,
+}
+
+#[derive(PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct ShareView {
+    pub id: String,
+    pub token: String,
+    pub url: String,
+    pub path: String,
+    pub expires_at: DateTime<Utc>,
+    pub max_downloads: Option<u64>,
+    pub downloads: u64,
+    pub password_protected: bool,
+}
+
+impl ShareView {
+    fn new(share: &Share, base_url: &str) -> Self {
+        Self {
+            id: share.id.clone(),
+            token: share.token.clone(),
+            url: format!("{}/s/{}", base_url.trim_end_matches('/'), share.token),
+            path: share.path.to_string_lossy().into_owned(),
+            expires_at: share.expires_at.into(),
+            max_downloads: share.max_downloads,
+            downloads: share.downloads,
+            password_protected: share.is_password_protected(),
+        }
+    }
+}
+
+#[tracing::instrument(name = "Create share", skip(application, drive, identity, params), fields(path = %params.path))]
+#[debug_handler(state = Application)]
+pub async fn create_share_service_handler(
+    State(application): State<Application>,
+    Extension(drive): Extension<Drive<Backend>>,
+    Extension(identity): Extension<Identity>,
+    WithRejection(Json(params), _): WithRejection<Json<CreateShareParameters>, MiboxError>,
+) -> Result<Response, MiboxError> {
+    identity.authorize_path(&params.path)?;
+    let entry = drive.stat(&params.path).await?;
+    let options = ShareOptions {
+        ttl: params.expires_in.map(Duration::from_secs),
+        password: params.password,
+        max_downloads: params.max_downloads,
+    };
+    let share = application
+        .shares
+        .create(identity.username, entry.path().clone(), options)
+        .await?;
+    let view = ShareView::new(&share, &application.base_url);
+    Ok((StatusCode::CREATED, Json(json!({ "result": view }))).into_response())
+}
+
+#[tracing::instrument(name = "List shares", skip(application, identity))]
+#[debug_handler(state = Application)]
+pub async fn list_shares_service_handler(
+    State(application): State<Application>,
+    Extension(identity): Extension<Identity>,
+) -> Json<serde_json::Value> {
+    let views = application
+        .shares
+        .list(&identity.username)
+        .iter()
+        .map(|share| ShareView::new(share, &application.base_url))
+        .collect::<Vec<_>>();
+    Json(json!({ "result": views }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeShareParameters {
+    id: String,
+}
+
+#[tracing::instrument(name = "Revoke share", skip(application, identity))]
+#[debug_handler(state = Application)]
+pub async fn revoke_share_service_handler(
+    State(application): State<Application>,
+    Extension(identity): Extension<Identity>,
+    WithRejection(Query(params), _): WithRejection<Query<RevokeShareParameters>, MiboxError>,
+) -> Result<StatusCode, MiboxError> {
+    application
+        .shares
+        .revoke(&identity.username, &params.id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SharedParameters {
+    /// Path of an entry within a shared directory.
+    #[serde(default)]
+    path: String,
+}
+
+/// Serves a share to anyone holding its token.
+///
+/// A shared file is downloaded, a shared directory is listed and its entries
+/// are reached through `path`. The password of a protected share is sent as
+/// the password of a basic authorization, whatever the user name.
+#[tracing::instrument(name = "Shared download", skip(application, token, authorization))]
+#[debug_handler(state = Application)]
+pub async fn shared_service_handler(
+    State(application): State<Application>,
+    Path(token): Path<String>,
+    WithRejection(Query(params), _): WithRejection<Query<SharedParameters>, MiboxError>,
+    authorization: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Response, MiboxError> {
+    let password = authorization
+        .map(|TypedHeader(authorization)| Secret::new(authorization.password().to_owned()));
+    let share = application.shares.open(&token, password).await?;
+    let drive = application.home(&share.owner).await?;
+    let path = if params.path.is_empty() {
+        share.path.clone()
+    } else {
+        share.path.join(&params.path)
+    };
+
+    let entry = drive.stat(&path).await?;
+    if entry.is_directory() {
+        let entries = drive.entries(&path).await?;
+        let view = directory_views(&drive, &entries).await?;
+        let body = serde_json::to_value(json!({ "result": view }))
+            .context("error serializing response")?;
+        return Ok(Json(body).into_response());
+    }
+
+    application.shares.record_download(&share.id).await?;
+    let mime_type = drive.mime_type(&entry).await?.unwrap_or_default();
+    let content = drive.read(&path).await?;
+    let content_headers = download_headers(
+        &mime_type,
+        Disposition::Attachment,
+        &entry.name().unwrap_or_default(),
+    )?;
+    Ok((
+        TypedHeader(ContentLength(entry.size())),
+        content_headers,
+        Body::from_stream(content),
+    )
+        .into_response())
+}
@@ -0,0 +1,141 @@
+use crate::{
+    application::Application,
+    authentication::{ApiToken, Identity, Scope},
+    error::MiboxError,
+};
+use axum::{
+    debug_handler,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::PathBuf;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTokenParameters {
+    name: String,
+    scope: Scope,
+    /// The only part of the home the token gives access to.
+    path_prefix: Option<PathBuf>,
+}
+
+#[derive(PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct TokenView {
+    pub id: String,
+    pub name: String,
+    pub scope: Scope,
+    pub path_prefix: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// The token itself, only returned when it is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl From<&ApiToken> for TokenView {
+    fn from(token: &ApiToken) -> Self {
+        Self {
+            id: token.id.clone(),
+            name: token.name.clone(),
+            scope: token.scope,
+            path_prefix: token
+                .path_prefix
+                .as_ref()
+                .map(|prefix| prefix.to_string_lossy().into_owned()),
+            created_at: token.created_at.into(),
+            token: None,
+        }
+    }
+}
+
+/// Creates an api token for the current user.
+///
+/// A token cannot be granted more than the identity creating it, so a token
+/// restricted to a path prefix can only create tokens within that prefix.
+#[tracing::instrument(name = "Create token", skip(application, identity))]
+#[debug_handler(state = Application)]
+pub async fn create_token_service_handler(
+    State(application): State<Application>,
+    Extension(identity): Extension<Identity>,
+    WithRejection(Json(params), _): WithRejection<Json<CreateTokenParameters>, MiboxError>,
+) -> Result<Response, MiboxError> {
+    identity.require(Scope::Admin)?;
+    identity.require(params.scope)?;
+    match &params.path_prefix {
+        Some(prefix) => identity.authorize_path(prefix)?,
+        None => identity.authorize_path("")?,
+    }
+    if params
+        .path_prefix
+        .as_ref()
+        .is_some_and(|prefix| prefix.is_absolute())
+    {
+        return Err(MiboxError::ValidationError(format!(
+            "{:?} must be relative to the home",
+            params.path_prefix
+        )));
+    }
+    let (api_token, token) = application
+        .authentication
+        .tokens()
+        .create(
+            identity.username,
+            params.name,
+            params.scope,
+            params.path_prefix,
+        )
+        .await?;
+    let view = TokenView {
+        token: Some(token),
+        ..TokenView::from(&api_token)
+    };
+    Ok((StatusCode::CREATED, Json(json!({ "result": view }))).into_response())
+}
+
+#[tracing::instrument(name = "List tokens", skip(application, identity))]
+#[debug_handler(state = Application)]
+pub async fn list_tokens_service_handler(
+    State(application): State<Application>,
+    Extension(identity): Extension<Identity>,
+) -> Result<Json<serde_json::Value>, MiboxError> {
+    identity.require(Scope::Admin)?;
+    let views = application
+        .authentication
+        .tokens()
+        .list(&identity.username)
+        .iter()
+        .map(TokenView::from)
+        .collect::<Vec<_>>();
+    Ok(Json(json!({ "result": views })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeTokenParameters {
+    id: String,
+}
+
+#[tracing::instrument(name = "Revoke token", skip(application, identity))]
+#[debug_handler(state = Application)]
+pub async fn revoke_token_service_handler(
+    State(application): State<Application>,
+    Extension(identity): Extension<Identity>,
+    WithRejection(Query(params), _): WithRejection<Query<RevokeTokenParameters>, MiboxError>,
+) -> Result<StatusCode, MiboxError> {
+    identity.require(Scope::Admin)?;
+    if !application
+        .authentication
+        .tokens()
+        .revoke(&identity.username, &params.id)
+        .await?
+    {
+        return Err(MiboxError::NotFound(format!(
+            "token {} not found",
+            params.id
+        )));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,110 @@
+//! The trash of the home drive, where removed files and directories are kept
+//! until restored, purged or expired (see `Application::expire_trash`).
+use crate::{application::Application, authentication::Identity, error::MiboxError};
+use axum::{
+    debug_handler,
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use drive::{backend::Backend, trash::TrashItem, Drive};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct TrashView {
+    pub id: String,
+    /// The path the entry was removed from.
+    pub path: String,
+    pub deleted: DateTime<Utc>,
+    pub is_directory: bool,
+    pub size: u64,
+}
+
+impl From<&TrashItem> for TrashView {
+    fn from(item: &TrashItem) -> Self {
+        Self {
+            id: item.id().to_owned(),
+            path: item.path().to_string_lossy().into_owned(),
+            deleted: item.deleted().into(),
+            is_directory: item.is_directory(),
+            size: item.size(),
+        }
+    }
+}
+
+#[tracing::instrument(name = "List trash", skip(drive, identity))]
+#[debug_handler(state = Application)]
+pub async fn list_trash_service_handler(
+    Extension(drive): Extension<Drive<Backend>>,
+    Extension(identity): Extension<Identity>,
+) -> Result<Json<serde_json::Value>, MiboxError> {
+    let views = drive
+        .trash_items()
+        .await?
+        .iter()
+        .filter(|item| identity.authorize_path(item.path()).is_ok())
+        .map(TrashView::from)
+        .collect::<Vec<_>>();
+    Ok(Json(json!({ "result": views })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreParameters {
+    id: String,
+    /// Where the entry is restored to, by default where it was removed from.
+    path: Option<String>,
+}
+
+/// Restores an entry of the trash, returning the path it was restored to
+/// which differs from the original one when that was taken.
+#[tracing::instrument(name = "Restore trash item", skip(drive, identity))]
+#[debug_handler(state = Application)]
+pub async fn restore_trash_service_handler(
+    Extension(drive): Extension<Drive<Backend>>,
+    Extension(identity): Extension<Identity>,
+    WithRejection(Query(params), _): WithRejection<Query<RestoreParameters>, MiboxError>,
+) -> Result<Response, MiboxError> {
+    let item = drive.trash_item(&params.id).await?;
+    identity.authorize_path(item.path())?;
+    if let Some(path) = &params.path {
+        identity.authorize_path(path)?;
+    }
+    let path = drive
+        .restore(&params.id, params.path.as_ref().map(AsRef::as_ref))
+        .await?;
+    Ok(Json(json!({ "result": { "path": path } })).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PurgeParameters {
+    id: String,
+}
+
+#[tracing::instrument(name = "Purge trash item", skip(drive, identity))]
+#[debug_handler(state = Application)]
+pub async fn purge_trash_service_handler(
+    Extension(drive): Extension<Drive<Backend>>,
+    Extension(identity): Extension<Identity>,
+    WithRejection(Query(params), _): WithRejection<Query<PurgeParameters>, MiboxError>,
+) -> Result<StatusCode, MiboxError> {
+    let item = drive.trash_item(&params.id).await?;
+    identity.authorize_path(item.path())?;
+    drive.purge(&params.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[tracing::instrument(name = "Empty trash", skip(drive, identity))]
+#[debug_handler(state = Application)]
+pub async fn empty_trash_service_handler(
+    Extension(drive): Extension<Drive<Backend>>,
+    Extension(identity): Extension<Identity>,
+) -> Result<StatusCode, MiboxError> {
+    // Only identities reaching the whole drive may empty its trash.
+    identity.authorize_path("")?;
+    drive.empty_trash().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
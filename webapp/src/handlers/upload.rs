@@ -0,0 +1,258 @@
+//! Resumable uploads following version 1.0.0 of the tus protocol
+//! (<https://tus.io/protocols/resumable-upload>), with its creation,
+//! creation-with-upload, termination and expiration extensions.
+use super::{access::Access, conditional};
+use crate::{
+    acl::Permission,
+    application::Application,
+    authentication::Identity,
+    error::MiboxError,
+    uploads::{Upload, MAX_UPLOAD_LENGTH},
+};
+use axum::{
+    body::Body,
+    debug_handler,
+    extract::{Path as UrlPath, Query, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use axum_extra::extract::WithRejection;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::TryStreamExt;
+use serde::Deserialize;
+use std::{
+    io,
+    path::{Component, Path},
+};
+
+pub const TUS_VERSION: &str = "1.0.0";
+
+const TUS_EXTENSIONS: &str = "creation,creation-with-upload,termination,expiration";
+
+/// The content type of the chunks of an upload.
+const CHUNK_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+const TUS_RESUMABLE: HeaderName = HeaderName::from_static("tus-resumable");
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+const UPLOAD_METADATA: HeaderName = HeaderName::from_static("upload-metadata");
+const UPLOAD_EXPIRES: HeaderName = HeaderName::from_static("upload-expires");
+
+/// Middleware adding the `Tus-Resumable` header every tus response carries.
+pub async fn tus_resumable_layer(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .insert(TUS_RESUMABLE, HeaderValue::from_static(TUS_VERSION));
+    response
+}
+
+/// A `412 Precondition Failed` response unless the request speaks the
+/// supported version of the protocol.
+fn unsupported_version(headers: &HeaderMap) -> Option<Response> {
+    if headers
+        .get(TUS_RESUMABLE)
+        .is_some_and(|version| version == TUS_VERSION)
+    {
+        return None;
+    }
+    Some(
+        (
+            StatusCode::PRECONDITION_FAILED,
+            [("tus-version", TUS_VERSION)],
+        )
+            .into_response(),
+    )
+}
+
+fn header_u64(headers: &HeaderMap, name: &HeaderName) -> Result<u64, MiboxError> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| MiboxError::ValidationError(format!("missing or invalid {}", name)))
+}
+
+fn is_chunk(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|content_type| content_type == CHUNK_CONTENT_TYPE)
+}
+
+/// The name of the uploaded file, read from the `filename` or `name` key of
+/// the `Upload-Metadata` header whose values are base64 encoded.
+fn file_name(metadata: &str) -> Result<String, MiboxError> {
+    let invalid = || MiboxError::ValidationError(format!("invalid upload metadata {:?}", metadata));
+    let mut name = None;
+    for pair in metadata.split(',') {
+        let (key, value) = pair.trim().split_once(' ').unwrap_or((pair.trim(), ""));
+        if key == "filename" || (key == "name" && name.is_none()) {
+            let value = STANDARD.decode(value.trim()).map_err(|_| invalid())?;
+            name = Some(String::from_utf8(value).map_err(|_| invalid())?);
+        }
+    }
+    let name = name.ok_or_else(invalid)?;
+    let mut components = Path::new(&name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(name),
+        _ => Err(invalid()),
+    }
+}
+
+/// The headers describing the progress of an upload.
+fn progress(upload: &Upload) -> [(HeaderName, String); 2] {
+    [
+        (UPLOAD_OFFSET, upload.offset.to_string()),
+        (UPLOAD_EXPIRES, conditional::http_date(upload.expires_at)),
+    ]
+}
+
+#[tracing::instrument(name = "Upload options")]
+#[debug_handler(state = Application)]
+pub async fn upload_options_service_handler() -> Response {
+    (
+        StatusCode::NO_CONTENT,
+        [
+            ("tus-version", TUS_VERSION.to_owned()),
+            ("tus-extension", TUS_EXTENSIONS.to_owned()),
+            ("tus-max-size", MAX_UPLOAD_LENGTH.to_string()),
+        ],
+    )
+        .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateUploadParameters {
+    /// The directory the file is uploaded to.
+    path: String,
+}
+
+/// Creates an upload, whose url is returned in the `Location` header, and
+/// receives its first chunk if the request has one.
+#[tracing::instrument(
+    name = "Upload create",
+    skip(application, identity, access, headers, body)
+)]
+#[debug_handler(state = Application)]
+pub async fn create_upload_service_handler(
+    State(application): State<Application>,
+    Extension(identity): Extension<Identity>,
+    access: Access,
+    WithRejection(Query(params), _): WithRejection<Query<CreateUploadParameters>, MiboxError>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, MiboxError> {
+    if let Some(unsupported) = unsupported_version(&headers) {
+        return Ok(unsupported);
+    }
+    if headers.contains_key("upload-defer-length") {
+        return Err(MiboxError::ValidationError(
+            "deferred upload lengths are not supported".to_owned(),
+        ));
+    }
+    let length = header_u64(&headers, &UPLOAD_LENGTH)?;
+    let metadata = headers
+        .get(UPLOAD_METADATA)
+        .and_then(|metadata| metadata.to_str().ok())
+        .ok_or_else(|| MiboxError::ValidationError("missing upload metadata".to_owned()))?;
+    let path = Path::new(&params.path).join(file_name(metadata)?);
+    let drive = access.drive([&path], Permission::Write).await?;
+    if !drive.stat(&params.path).await?.is_directory() {
+        return Err(MiboxError::ValidationError(format!(
+            "{:?} is not a directory",
+            params.path
+        )));
+    }
+
+    let uploads = &application.uploads;
+    let username = &identity.username;
+    let mut upload = uploads
+        .create(username, drive, path, length, Some(metadata.to_owned()))
+        .await?;
+    // Empty files are complete as soon as they are created.
+    if is_chunk(&headers) || length == 0 {
+        let chunk = body.into_data_stream().map_err(io::Error::other);
+        upload = uploads.append(username, &upload.id, 0, chunk).await?;
+    }
+    Ok((
+        StatusCode::CREATED,
+        [(header::LOCATION, format!("/v1/upload/{}", upload.id))],
+        progress(&upload),
+    )
+        .into_response())
+}
+
+/// Reports how many bytes of an upload were received.
+#[tracing::instrument(name = "Upload progress", skip(application, identity, headers))]
+#[debug_handler(state = Application)]
+pub async fn upload_progress_service_handler(
+    State(application): State<Application>,
+    Extension(identity): Extension<Identity>,
+    UrlPath(id): UrlPath<String>,
+    headers: HeaderMap,
+) -> Result<Response, MiboxError> {
+    if let Some(unsupported) = unsupported_version(&headers) {
+        return Ok(unsupported);
+    }
+    let upload = application.uploads.get(&identity.username, &id)?;
+    identity.authorize_path(&upload.path)?;
+    let mut response = (
+        [
+            (UPLOAD_LENGTH, upload.length.to_string()),
+            (header::CACHE_CONTROL, "no-store".to_owned()),
+        ],
+        progress(&upload),
+    )
+        .into_response();
+    if let Some(metadata) = upload
+        .metadata
+        .and_then(|metadata| HeaderValue::from_str(&metadata).ok())
+    {
+        response.headers_mut().insert(UPLOAD_METADATA, metadata);
+    }
+    Ok(response)
+}
+
+/// Receives a chunk of an upload starting at its `Upload-Offset`.
+#[tracing::instrument(name = "Upload chunk", skip(application, identity, headers, body))]
+#[debug_handler(state = Application)]
+pub async fn upload_chunk_service_handler(
+    State(application): State<Application>,
+    Extension(identity): Extension<Identity>,
+    UrlPath(id): UrlPath<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, MiboxError> {
+    if let Some(unsupported) = unsupported_version(&headers) {
+        return Ok(unsupported);
+    }
+    if !is_chunk(&headers) {
+        return Ok(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response());
+    }
+    let offset = header_u64(&headers, &UPLOAD_OFFSET)?;
+    let uploads = &application.uploads;
+    identity.authorize_path(&uploads.get(&identity.username, &id)?.path)?;
+    let chunk = body.into_data_stream().map_err(io::Error::other);
+    let upload = uploads
+        .append(&identity.username, &id, offset, chunk)
+        .await?;
+    Ok((StatusCode::NO_CONTENT, progress(&upload)).into_response())
+}
+
+/// Cancels an upload, dropping the bytes received so far.
+#[tracing::instrument(name = "Upload terminate", skip(application, identity, headers))]
+#[debug_handler(state = Application)]
+pub async fn terminate_upload_service_handler(
+    State(application): State<Application>,
+    Extension(identity): Extension<Identity>,
+    UrlPath(id): UrlPath<String>,
+    headers: HeaderMap,
+) -> Result<Response, MiboxError> {
+    if let Some(unsupported) = unsupported_version(&headers) {
+        return Ok(unsupported);
+    }
+    let uploads = &application.uploads;
+    identity.authorize_path(&uploads.get(&identity.username, &id)?.path)?;
+    uploads.terminate(&identity.username, &id).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
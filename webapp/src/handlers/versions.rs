@@ -0,0 +1,103 @@
+//! The prior versions of files, kept when files are overwritten.
+use super::{
+    access::Access,
+    disposition::{download_headers, Disposition},
+};
+use crate::{acl::Permission, application::Application, error::MiboxError};
+use axum::{
+    body::Body,
+    debug_handler,
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::{extract::WithRejection, headers::ContentLength, TypedHeader};
+use chrono::{DateTime, Utc};
+use drive::{mime, versions::Version};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+
+#[derive(PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct VersionView {
+    pub id: String,
+    pub size: u64,
+    /// When the contents of the version were written.
+    pub modified: Option<DateTime<Utc>>,
+    /// When the version was superseded.
+    pub archived: DateTime<Utc>,
+    pub author: Option<String>,
+}
+
+impl From<&Version> for VersionView {
+    fn from(version: &Version) -> Self {
+        Self {
+            id: version.id().to_owned(),
+            size: version.size(),
+            modified: version.modified().map(DateTime::from),
+            archived: version.archived().into(),
+            author: version.author().map(ToOwned::to_owned),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListVersionsParameters {
+    path: String,
+}
+
+#[tracing::instrument(name = "List versions", skip(access))]
+#[debug_handler(state = Application)]
+pub async fn list_versions_service_handler(
+    access: Access,
+    WithRejection(Query(params), _): WithRejection<Query<ListVersionsParameters>, MiboxError>,
+) -> Result<Json<serde_json::Value>, MiboxError> {
+    let drive = access.drive([&params.path], Permission::Read).await?;
+    let views = drive
+        .versions(&params.path)
+        .await?
+        .iter()
+        .map(VersionView::from)
+        .collect::<Vec<_>>();
+    Ok(Json(json!({ "result": views })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VersionParameters {
+    path: String,
+    id: String,
+}
+
+#[tracing::instrument(name = "Download version", skip(access))]
+#[debug_handler(state = Application)]
+pub async fn download_version_service_handler(
+    access: Access,
+    WithRejection(Query(params), _): WithRejection<Query<VersionParameters>, MiboxError>,
+) -> Result<Response, MiboxError> {
+    let drive = access.drive([&params.path], Permission::Read).await?;
+    let version = drive.version(&params.path, &params.id).await?;
+    let content = drive.read_version(&params.path, &params.id).await?;
+    let path = Path::new(&params.path);
+    let mime_type = mime::from_extension(path).unwrap_or_else(|| mime::OCTET_STREAM.to_owned());
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    Ok((
+        TypedHeader(ContentLength(version.size())),
+        download_headers(&mime_type, Disposition::Attachment, &file_name)?,
+        Body::from_stream(content),
+    )
+        .into_response())
+}
+
+/// Makes a version the current contents of its file, the contents it
+/// replaces are kept as a version in turn.
+#[tracing::instrument(name = "Restore version", skip(access))]
+#[debug_handler(state = Application)]
+pub async fn restore_version_service_handler(
+    access: Access,
+    WithRejection(Query(params), _): WithRejection<Query<VersionParameters>, MiboxError>,
+) -> Result<StatusCode, MiboxError> {
+    let drive = access.drive([&params.path], Permission::Write).await?;
+    drive.restore_version(&params.path, &params.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
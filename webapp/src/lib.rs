@@ -1,6 +1,11 @@
+pub mod acl;
 pub mod application;
+pub mod archive;
+pub mod authentication;
 pub mod configuration;
 pub mod error;
 pub mod handlers;
 pub mod server;
+pub mod sharing;
 pub mod telemetry;
+pub mod uploads;
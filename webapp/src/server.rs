@@ -1,15 +1,45 @@
 use crate::{
-    application::Application,
-    configuration::Settings,
+    acl::Acl,
+    application::{validate_username, Application},
+    authentication::{require_authentication, ApiTokens, Authentication},
+    configuration::{S3Settings, Settings},
     handlers::{
+        acl::{
+            create_grant_service_handler, list_grants_service_handler, revoke_grant_service_handler,
+        },
+        dav::{dav_service_handler, DAV_PREFIX},
         directory::{
-            create_dir_service_handler, list_service_handler, remove_dir_service_handler,
-            update_dir_service_handler,
+            archive_service_handler, create_dir_service_handler, list_service_handler,
+            remove_dir_service_handler, update_dir_service_handler,
         },
         fallback_service_handler,
         file::{delete_service_handler, download_service_handler, upload_service_handler},
         health_check_service_handler,
+        search::search_service_handler,
+        session::{login_service_handler, logout_service_handler},
+        share::{
+            create_share_service_handler, list_shares_service_handler,
+            revoke_share_service_handler, shared_service_handler,
+        },
+        token::{
+            create_token_service_handler, list_tokens_service_handler, revoke_token_service_handler,
+        },
+        trash::{
+            empty_trash_service_handler, list_trash_service_handler, purge_trash_service_handler,
+            restore_trash_service_handler,
+        },
+        upload::{
+            create_upload_service_handler, terminate_upload_service_handler, tus_resumable_layer,
+            upload_chunk_service_handler, upload_options_service_handler,
+            upload_progress_service_handler,
+        },
+        versions::{
+            download_version_service_handler, list_versions_service_handler,
+            restore_version_service_handler,
+        },
     },
+    sharing::Shares,
+    uploads::Uploads,
 };
 use axum::{
     body::Body,
@@ -17,13 +47,34 @@ use axum::{
     http::HeaderValue,
     middleware::{self, Next},
     response::Response,
-    routing::{delete, get, post, put},
+    routing::{any, delete, get, head, options, patch, post, put},
     Router,
 };
-use std::net::SocketAddr;
+use drive::{
+    backend::{Backend, SymlinkPolicy},
+    index::Index,
+    Drive, RESERVED_DIR,
+};
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
 use tokio::signal;
 use tower_http::trace::TraceLayer;
 
+/// The directory of the reserved directory of local drives resumable uploads
+/// are staged in, unless configured otherwise.
+const UPLOADS_DIR: &str = "uploads";
+
+/// How often abandoned resumable uploads are looked for.
+const UPLOAD_EXPIRY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// How often the trash of every drive is looked for expired entries.
+const TRASH_EXPIRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often the versions of files are looked for expired ones.
+const VERSION_EXPIRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often the blob store of a deduplicated drive is garbage collected.
+const BLOB_COLLECTION_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
 pub struct Server {
     address: SocketAddr,
     application: Application,
@@ -37,17 +88,105 @@ impl Server {
         }
     }
 
+    /// Builds the server from `settings`, indexing the home drives when
+    /// their index is empty, e.g. because it was just created.
     pub async fn with_settings(settings: Settings) -> anyhow::Result<Self> {
+        let server = Self::without_indexing(settings).await?;
+        if let Some(index) = &server.application.index {
+            if index.is_empty().await? {
+                let indexed = server.application.rebuild_index().await?;
+                tracing::info!("indexed {} entries", indexed);
+            }
+        }
+        Ok(server)
+    }
+
+    /// Builds the server from `settings`, leaving the index as it is.
+    pub async fn without_indexing(settings: Settings) -> anyhow::Result<Self> {
         let address = settings
             .application
             .address()
             .parse()
             .expect("failed to parse address");
 
+        let s3 = settings
+            .application
+            .s3
+            .as_ref()
+            .map(S3Settings::options)
+            .unwrap_or_default();
+        let symlinks = settings
+            .application
+            .symlinks
+            .as_deref()
+            .map(str::parse::<SymlinkPolicy>)
+            .transpose()
+            .map_err(|err| anyhow::anyhow!("{}", err))?
+            .unwrap_or_default();
+        let backend = Backend::from_url(&settings.application.drive, &s3)
+            .map_err(|err| anyhow::anyhow!("{}", err))?
+            .with_symlink_policy(symlinks);
+        let backend = if settings.application.dedup {
+            backend.deduplicated()
+        } else {
+            backend
+        };
+        for user in &settings.application.users {
+            validate_username(&user.username).map_err(|err| anyhow::anyhow!("{}", err))?;
+        }
+        let users = settings
+            .application
+            .users
+            .iter()
+            .map(|user| (user.username.clone(), user.password_hash.clone()));
+        let authentication = Authentication::new(
+            &settings.application.hmac_secret,
+            users,
+            Duration::from_secs(settings.application.session_ttl),
+        )
+        .with_groups(
+            settings
+                .application
+                .users
+                .iter()
+                .map(|user| (user.username.clone(), user.groups.clone())),
+        )
+        .with_secure_cookies(settings.application.base_url.starts_with("https://"));
+        // Uploads are staged next to the files of local drives, so that
+        // instances on distinct drives never share their staging directory.
+        let staging = match &settings.application.upload_staging {
+            Some(staging) => Some(PathBuf::from(staging)),
+            None => backend
+                .local_base()
+                .map(|base| base.join(RESERVED_DIR).join(UPLOADS_DIR)),
+        };
+        // What the application keeps across restarts is recorded in the
+        // drive (see `Drive::put_record`).
+        let drive = Drive::with_backend(backend);
+        let authentication =
+            authentication.with_tokens(ApiTokens::default().with_records(drive.clone()).await?);
+        let shares = Shares::new(&settings.application.hmac_secret)
+            .with_records(drive.clone())
+            .await?;
+        let acl = Acl::default().with_records(drive.clone()).await?;
         let application = Application::new(
             settings.application.base_url.clone(),
-            settings.application.drive.into(),
-        );
+            drive,
+            authentication,
+            shares,
+        )
+        .with_acl(acl);
+        let application = match staging {
+            Some(staging) => application.with_uploads(Uploads::new(staging)),
+            None => application,
+        }
+        .with_extract_limits(settings.application.extract.limits())
+        .with_trash_retention(Duration::from_secs(settings.application.trash_retention))
+        .with_versioning(settings.application.versions.policy());
+        let application = match &settings.application.index {
+            Some(path) => application.with_index(Index::open(path)?),
+            None => application,
+        };
 
         Ok(Self {
             address,
@@ -56,6 +195,10 @@ impl Server {
     }
 
     pub async fn serve(&self) -> anyhow::Result<()> {
+        let swept = self.application.uploads.sweep().await;
+        if swept > 0 {
+            tracing::info!("removed {} staged files of previous uploads", swept);
+        }
         tracing::info!("listening on {}", self.address);
         let listener = tokio::net::TcpListener::bind(self.address)
             .await
@@ -65,6 +208,10 @@ impl Server {
             })?;
 
         let app = self.create_router().await?;
+        tokio::spawn(Self::expire_uploads(self.application.uploads.clone()));
+        tokio::spawn(Self::expire_trash(self.application.clone()));
+        tokio::spawn(Self::expire_versions(self.application.clone()));
+        tokio::spawn(Self::collect_blobs(self.application.clone()));
         let _ = axum::serve(listener, app)
             .with_graceful_shutdown(Self::shutdown())
             .await
@@ -73,21 +220,125 @@ impl Server {
     }
 
     pub async fn create_router(&self) -> anyhow::Result<Router> {
+        let uploads = Router::new()
+            .route("/upload", options(upload_options_service_handler))
+            .route("/upload", post(create_upload_service_handler))
+            .route("/upload/:id", head(upload_progress_service_handler))
+            .route("/upload/:id", patch(upload_chunk_service_handler))
+            .route("/upload/:id", delete(terminate_upload_service_handler))
+            .layer(middleware::map_response(tus_resumable_layer));
+        let v1 = Router::new()
+            .route("/file", post(upload_service_handler))
+            .route("/file", get(download_service_handler))
+            .route("/file", delete(delete_service_handler))
+            .route("/file/versions", get(list_versions_service_handler))
+            .route(
+                "/file/versions/content",
+                get(download_version_service_handler),
+            )
+            .route(
+                "/file/versions/restore",
+                post(restore_version_service_handler),
+            )
+            .route("/directory", get(list_service_handler))
+            .route("/directory", put(update_dir_service_handler))
+            .route("/directory", post(create_dir_service_handler))
+            .route("/directory", delete(remove_dir_service_handler))
+            .route("/directory/archive", get(archive_service_handler))
+            .route("/share", post(create_share_service_handler))
+            .route("/share", get(list_shares_service_handler))
+            .route("/share", delete(revoke_share_service_handler))
+            .route("/acl", post(create_grant_service_handler))
+            .route("/acl", get(list_grants_service_handler))
+            .route("/acl", delete(revoke_grant_service_handler))
+            .route("/token", post(create_token_service_handler))
+            .route("/token", get(list_tokens_service_handler))
+            .route("/token", delete(revoke_token_service_handler))
+            .route("/search", get(search_service_handler))
+            .route("/trash", get(list_trash_service_handler))
+            .route("/trash", delete(empty_trash_service_handler))
+            .route("/trash/item", delete(purge_trash_service_handler))
+            .route("/trash/restore", post(restore_trash_service_handler))
+            .merge(uploads)
+            .route_layer(middleware::from_fn_with_state(
+                self.application.clone(),
+                require_authentication,
+            ));
+        let dav = Router::new()
+            .route(DAV_PREFIX, any(dav_service_handler))
+            .route(&format!("{}/", DAV_PREFIX), any(dav_service_handler))
+            .route(&format!("{}/*path", DAV_PREFIX), any(dav_service_handler))
+            .route_layer(middleware::from_fn_with_state(
+                self.application.clone(),
+                require_authentication,
+            ));
         Ok(Router::new()
             .fallback(fallback_service_handler)
-            .route("/v1/file", post(upload_service_handler))
-            .route("/v1/file", get(download_service_handler))
-            .route("/v1/file", delete(delete_service_handler))
-            .route("/v1/directory", get(list_service_handler))
-            .route("/v1/directory", put(update_dir_service_handler))
-            .route("/v1/directory", post(create_dir_service_handler))
-            .route("/v1/directory", delete(remove_dir_service_handler))
+            .nest("/v1", v1)
+            .merge(dav)
+            .route("/login", post(login_service_handler))
+            .route("/logout", post(logout_service_handler))
+            .route("/s/:token", get(shared_service_handler))
             .route("/health_check", get(health_check_service_handler))
             .with_state(self.application.clone())
             .layer(middleware::from_fn(secure_headers_layer))
             .layer(tracing_layer()))
     }
 
+    /// Periodically removes the resumable uploads that were abandoned.
+    async fn expire_uploads(uploads: Uploads) {
+        let mut interval = tokio::time::interval(UPLOAD_EXPIRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            let expired = uploads.expire().await;
+            if expired > 0 {
+                tracing::info!("removed {} expired uploads", expired);
+            }
+        }
+    }
+
+    /// Periodically purges the entries that stayed in the trash longer than
+    /// the retention period.
+    async fn expire_trash(application: Application) {
+        let mut interval = tokio::time::interval(TRASH_EXPIRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            match application.expire_trash().await {
+                Ok(0) => {}
+                Ok(expired) => tracing::info!("purged {} expired trash entries", expired),
+                Err(e) => tracing::error!("failed to expire the trash: {:?}", e),
+            }
+        }
+    }
+
+    /// Periodically removes the versions of files that stayed longer than
+    /// the versioning policy keeps them.
+    async fn expire_versions(application: Application) {
+        let mut interval = tokio::time::interval(VERSION_EXPIRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            match application.expire_versions().await {
+                Ok(0) => {}
+                Ok(expired) => tracing::info!("removed {} expired file versions", expired),
+                Err(e) => tracing::error!("failed to expire file versions: {:?}", e),
+            }
+        }
+    }
+
+    /// Periodically removes the blobs of a deduplicated drive that no file
+    /// references anymore.
+    async fn collect_blobs(application: Application) {
+        let mut interval = tokio::time::interval(BLOB_COLLECTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            match application.collect_garbage().await {
+                Ok(0) => {}
+                Ok(removed) => tracing::info!("removed {} unreferenced blobs", removed),
+                Err(e) => tracing::error!("failed to collect unreferenced blobs: {:?}", e),
+            }
+        }
+    }
+
     async fn shutdown() {
         let ctrl_c = async {
             signal::ctrl_c().await.expect("Expecting CTRL+C");
@@ -113,6 +364,10 @@ impl Server {
     pub fn address(&self) -> SocketAddr {
         self.address
     }
+
+    pub fn application(&self) -> &Application {
+        &self.application
+    }
 }
 
 fn tracing_layer() -> TraceLayer<
@@ -0,0 +1,329 @@
+use crate::{
+    authentication::{hash_password, verify_password},
+    error::MiboxError,
+};
+use anyhow::{anyhow, Context};
+use drive::{backend::Backend, error::DriveError, records::Fields, Drive};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How long a share stays valid when no expiry is requested.
+pub const DEFAULT_SHARE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The kind of the records the shares are kept in (see `Shares::with_records`).
+const RECORD_KIND: &str = "shares";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ShareError {
+    #[error("{0}")]
+    ShareNotFound(String),
+    #[error("{0}")]
+    ShareExpired(String),
+    #[error("{0}")]
+    ShareExhausted(String),
+}
+
+/// A file or directory of a user's home made available to anyone holding
+/// its token.
+#[derive(Debug, Clone)]
+pub struct Share {
+    pub id: String,
+    pub token: String,
+    pub owner: String,
+    /// Path of the shared entry relative to the home of its owner.
+    pub path: PathBuf,
+    pub expires_at: SystemTime,
+    pub max_downloads: Option<u64>,
+    pub downloads: u64,
+    password_hash: Option<Secret<String>>,
+}
+
+impl Share {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= SystemTime::now()
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.max_downloads
+            .is_some_and(|max_downloads| self.downloads >= max_downloads)
+    }
+
+    pub fn is_password_protected(&self) -> bool {
+        self.password_hash.is_some()
+    }
+}
+
+/// The options of a new share.
+#[derive(Debug, Default)]
+pub struct ShareOptions {
+    pub ttl: Option<Duration>,
+    pub password: Option<Secret<String>>,
+    pub max_downloads: Option<u64>,
+}
+
+fn to_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// The active shares keyed by id.
+///
+/// Tokens are made of the share id and expiry signed with `hmac_secret`, so
+/// that forged or tampered tokens are rejected before any lookup and expired
+/// tokens are rejected even if the share is still stored.
+#[derive(Clone)]
+pub struct Shares {
+    key: Arc<Secret<String>>,
+    shares: Arc<RwLock<HashMap<String, Share>>>,
+    /// Keeps the shares across restarts when set.
+    records: Option<Drive<Backend>>,
+}
+
+impl Shares {
+    pub fn new(hmac_secret: &Secret<String>) -> Self {
+        Self {
+            key: Arc::new(hmac_secret.clone()),
+            shares: Arc::default(),
+            records: None,
+        }
+    }
+
+    /// Keeps the shares in the records of `drive`, starting from the ones
+    /// it holds. Expired shares are removed.
+    pub async fn with_records(mut self, drive: Drive<Backend>) -> Result<Self, DriveError> {
+        let mut shares = HashMap::new();
+        for (id, fields) in drive.records(RECORD_KIND).await? {
+            match self.share_of_record(&id, &fields) {
+                Some(share) if share.is_expired() => {
+                    drive.delete_record(RECORD_KIND, &id).await?;
+                }
+                Some(share) => {
+                    shares.insert(id, share);
+                }
+                None => tracing::warn!("ignoring the invalid record of share {}", id),
+            }
+        }
+        self.shares = Arc::new(RwLock::new(shares));
+        self.records = Some(drive);
+        Ok(self)
+    }
+
+    fn share_of_record(&self, id: &str, fields: &Fields) -> Option<Share> {
+        let expires_at = UNIX_EPOCH + Duration::from_secs(fields.get("ExpiresAt")?.parse().ok()?);
+        Some(Share {
+            id: id.to_owned(),
+            token: self.sign(id, expires_at),
+            owner: fields.get("Owner")?.clone(),
+            path: PathBuf::from(fields.get("Path")?),
+            expires_at,
+            max_downloads: match fields.get("MaxDownloads") {
+                Some(max_downloads) => Some(max_downloads.parse().ok()?),
+                None => None,
+            },
+            downloads: fields.get("Downloads")?.parse().ok()?,
+            password_hash: fields.get("PasswordHash").cloned().map(Secret::new),
+        })
+    }
+
+    /// Records `share`, when the shares are kept in records.
+    async fn persist(&self, share: &Share) -> Result<(), DriveError> {
+        let Some(drive) = &self.records else {
+            return Ok(());
+        };
+        let expires_at = to_secs(share.expires_at).to_string();
+        let downloads = share.downloads.to_string();
+        let max_downloads = share.max_downloads.map(|max| max.to_string());
+        let path = share.path.to_string_lossy();
+        let mut fields = vec![
+            ("Owner", share.owner.as_str()),
+            ("Path", path.as_ref()),
+            ("ExpiresAt", expires_at.as_str()),
+            ("Downloads", downloads.as_str()),
+        ];
+        if let Some(max_downloads) = &max_downloads {
+            fields.push(("MaxDownloads", max_downloads));
+        }
+        if let Some(password_hash) = &share.password_hash {
+            fields.push(("PasswordHash", password_hash.expose_secret()));
+        }
+        drive.put_record(RECORD_KIND, &share.id, &fields).await
+    }
+
+    /// Forgets the record of the share `id`, when the shares are kept in
+    /// records.
+    async fn forget(&self, id: &str) -> Result<(), DriveError> {
+        if let Some(drive) = &self.records {
+            drive.delete_record(RECORD_KIND, id).await?;
+        }
+        Ok(())
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.key.expose_secret().as_bytes())
+            .expect("hmac accepts keys of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    fn sign(&self, id: &str, expires_at: SystemTime) -> String {
+        let payload = format!("{}.{}", id, to_secs(expires_at));
+        let signature = hex::encode(self.mac(&payload).finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    /// Checks the signature and expiry of a token and returns the id of its
+    /// share.
+    fn verify<'a>(&self, token: &'a str) -> Result<&'a str, ShareError> {
+        let invalid = || ShareError::ShareNotFound("invalid share token".to_string());
+        let (payload, signature) = token.rsplit_once('.').ok_or_else(invalid)?;
+        let signature = hex::decode(signature).map_err(|_| invalid())?;
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| invalid())?;
+        let (id, expires_at) = payload.split_once('.').ok_or_else(invalid)?;
+        let expires_at = expires_at.parse::<u64>().map_err(|_| invalid())?;
+        if UNIX_EPOCH + Duration::from_secs(expires_at) <= SystemTime::now() {
+            return Err(ShareError::ShareExpired(format!("share {} expired", id)));
+        }
+        Ok(id)
+    }
+
+    /// Shares `path` of the home of `owner` and returns the new share.
+    pub async fn create(
+        &self,
+        owner: String,
+        path: PathBuf,
+        options: ShareOptions,
+    ) -> anyhow::Result<Share> {
+        let id = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(16)
+            .map(char::from)
+            .collect::<String>();
+        let expires_at = SystemTime::now() + options.ttl.unwrap_or(DEFAULT_SHARE_TTL);
+        let password_hash = match options.password {
+            Some(password) => Some(
+                tokio::task::spawn_blocking(move || hash_password(&password))
+                    .await
+                    .context("failed to spawn password hashing")??,
+            ),
+            None => None,
+        };
+        let share = Share {
+            token: self.sign(&id, expires_at),
+            id: id.clone(),
+            owner,
+            path,
+            expires_at,
+            max_downloads: options.max_downloads,
+            downloads: 0,
+            password_hash,
+        };
+        self.persist(&share).await?;
+        let expired = {
+            let mut shares = self.shares.write().expect("shares lock poisoned");
+            let expired = shares
+                .values()
+                .filter(|share| share.is_expired())
+                .map(|share| share.id.clone())
+                .collect::<Vec<_>>();
+            for id in &expired {
+                shares.remove(id);
+            }
+            shares.insert(id, share.clone());
+            expired
+        };
+        for id in expired {
+            self.forget(&id).await?;
+        }
+        Ok(share)
+    }
+
+    /// Returns the active shares of `owner`.
+    pub fn list(&self, owner: &str) -> Vec<Share> {
+        let shares = self.shares.read().expect("shares lock poisoned");
+        let mut owned = shares
+            .values()
+            .filter(|share| share.owner == owner && !share.is_expired())
+            .cloned()
+            .collect::<Vec<_>>();
+        owned.sort_by_key(|share| share.expires_at);
+        owned
+    }
+
+    /// Revokes a share of `owner`, its token stops working immediately.
+    pub async fn revoke(&self, owner: &str, id: &str) -> Result<(), MiboxError> {
+        let owned = {
+            let mut shares = self.shares.write().expect("shares lock poisoned");
+            match shares.get(id) {
+                Some(share) if share.owner == owner => shares.remove(id).is_some(),
+                _ => false,
+            }
+        };
+        if !owned {
+            return Err(ShareError::ShareNotFound(format!("share {} not found", id)).into());
+        }
+        self.forget(id).await?;
+        Ok(())
+    }
+
+    /// Returns the share of a token, checking its password if it has one.
+    pub async fn open(
+        &self,
+        token: &str,
+        password: Option<Secret<String>>,
+    ) -> Result<Share, MiboxError> {
+        let id = self.verify(token)?;
+        let share = {
+            let shares = self.shares.read().expect("shares lock poisoned");
+            shares.get(id).cloned()
+        };
+        let share =
+            share.ok_or_else(|| ShareError::ShareNotFound(format!("share {} not found", id)))?;
+        if let Some(expected) = share.password_hash.clone() {
+            let password = password.unwrap_or_else(|| Secret::new(String::new()));
+            let verified =
+                tokio::task::spawn_blocking(move || verify_password(Some(&expected), &password))
+                    .await
+                    .context("failed to spawn password verification")??;
+            if !verified {
+                return Err(MiboxError::AuthError(anyhow!(
+                    "invalid password for share {}",
+                    id
+                )));
+            }
+        }
+        Ok(share)
+    }
+
+    /// Counts a download of a share, failing once its download limit is
+    /// reached.
+    pub async fn record_download(&self, id: &str) -> Result<(), MiboxError> {
+        let share = {
+            let mut shares = self.shares.write().expect("shares lock poisoned");
+            let share = shares
+                .get_mut(id)
+                .ok_or_else(|| ShareError::ShareNotFound(format!("share {} not found", id)))?;
+            if share.is_exhausted() {
+                return Err(ShareError::ShareExhausted(format!(
+                    "share {} reached its download limit",
+                    id
+                ))
+                .into());
+            }
+            share.downloads += 1;
+            share.clone()
+        };
+        self.persist(&share).await?;
+        Ok(())
+    }
+}
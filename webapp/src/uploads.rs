@@ -0,0 +1,416 @@
+use crate::error::MiboxError;
+use anyhow::Context;
+use bytes::Buf;
+use drive::{backend::Backend, Drive};
+use futures::{Stream, StreamExt};
+use rand::{distributions::Alphanumeric, Rng};
+use std::{
+    collections::HashMap,
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+    pin::pin,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    fs,
+    io::{AsyncSeekExt, AsyncWriteExt},
+};
+use tokio_util::io::ReaderStream;
+
+/// How long an upload is kept after its last chunk was received.
+pub const UPLOAD_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The largest upload accepted.
+pub const MAX_UPLOAD_LENGTH: u64 = 16 * 1024 * 1024 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum UploadError {
+    #[error("{0}")]
+    UploadNotFound(String),
+    #[error("{0}")]
+    OffsetMismatch(String),
+    #[error("{0}")]
+    UploadBusy(String),
+    #[error("{0}")]
+    UploadTooLarge(String),
+}
+
+/// A file being uploaded in chunks, staged until all of its bytes have been
+/// received.
+#[derive(Clone)]
+pub struct Upload {
+    pub id: String,
+    pub username: String,
+    /// The drive the file is written to, resolved when the upload was
+    /// created.
+    pub drive: Drive<Backend>,
+    pub path: PathBuf,
+    pub length: u64,
+    pub offset: u64,
+    /// The metadata given by the client, echoed back as is.
+    pub metadata: Option<String>,
+    pub expires_at: SystemTime,
+    /// Whether a chunk is being received or the upload committed.
+    busy: bool,
+}
+
+impl Upload {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= SystemTime::now()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.offset == self.length
+    }
+}
+
+/// The uploads in progress keyed by id.
+///
+/// The bytes received so far are staged in a local file named after the
+/// upload id inside `staging`, whatever the backend of the drive, and only
+/// written to the drive once complete.
+#[derive(Clone)]
+pub struct Uploads {
+    staging: PathBuf,
+    uploads: Arc<RwLock<HashMap<String, Upload>>>,
+}
+
+impl Uploads {
+    pub fn new(staging: impl Into<PathBuf>) -> Self {
+        Self {
+            staging: staging.into(),
+            uploads: Arc::default(),
+        }
+    }
+
+    /// Stages the uploads in a directory of their own within the temporary
+    /// directory, so that no other instance shares it.
+    pub fn temporary() -> Self {
+        Self::new(std::env::temp_dir().join(format!("mibox-uploads-{}", random_id())))
+    }
+
+    fn staged(&self, id: &str) -> PathBuf {
+        self.staging.join(id)
+    }
+
+    /// Stages the whole of `stream` in a file of the staging directory, e.g.
+    /// to read an archive in any order.
+    pub async fn stage<B: Buf, S: Stream<Item = Result<B, io::Error>>>(
+        &self,
+        stream: S,
+    ) -> Result<Staged, MiboxError> {
+        fs::create_dir_all(&self.staging)
+            .await
+            .context("failed to create the upload staging directory")?;
+        let staged = Staged {
+            path: self.staged(&format!("staged-{}", random_id())),
+        };
+        let mut file = fs::File::create(&staged.path)
+            .await
+            .context("failed to stage upload")?;
+        let mut stream = pin!(stream);
+        while let Some(bytes) = stream.next().await {
+            let mut bytes = bytes.context("failed to receive upload")?;
+            file.write_all_buf(&mut bytes)
+                .await
+                .context("failed to write staged upload")?;
+        }
+        file.flush()
+            .await
+            .context("failed to write staged upload")?;
+        Ok(staged)
+    }
+
+    /// Starts an upload of `length` bytes to `path` of `drive`.
+    pub async fn create(
+        &self,
+        username: &str,
+        drive: Drive<Backend>,
+        path: PathBuf,
+        length: u64,
+        metadata: Option<String>,
+    ) -> Result<Upload, MiboxError> {
+        if length > MAX_UPLOAD_LENGTH {
+            return Err(UploadError::UploadTooLarge(format!(
+                "uploads are limited to {} bytes",
+                MAX_UPLOAD_LENGTH
+            ))
+            .into());
+        }
+        self.expire().await;
+        let id = random_id();
+        fs::create_dir_all(&self.staging)
+            .await
+            .context("failed to create the upload staging directory")?;
+        fs::File::create(self.staged(&id))
+            .await
+            .context("failed to stage upload")?;
+        let upload = Upload {
+            id: id.clone(),
+            username: username.to_owned(),
+            drive,
+            path,
+            length,
+            offset: 0,
+            metadata,
+            expires_at: SystemTime::now() + UPLOAD_TTL,
+            busy: false,
+        };
+        self.uploads
+            .write()
+            .expect("uploads lock poisoned")
+            .insert(id, upload.clone());
+        Ok(upload)
+    }
+
+    /// The upload `id` of `username`, unless it expired.
+    pub fn get(&self, username: &str, id: &str) -> Result<Upload, MiboxError> {
+        self.uploads
+            .read()
+            .expect("uploads lock poisoned")
+            .get(id)
+            .filter(|upload| upload.username == username && !upload.is_expired())
+            .cloned()
+            .ok_or_else(|| UploadError::UploadNotFound(format!("no upload {}", id)).into())
+    }
+
+    /// Appends a chunk starting at `offset` to the upload `id`, the file is
+    /// written to the drive once its last byte has been received.
+    ///
+    /// The bytes received before the stream fails are kept, so that the
+    /// client can resume from there.
+    pub async fn append<B: Buf, S: Stream<Item = Result<B, io::Error>>>(
+        &self,
+        username: &str,
+        id: &str,
+        offset: u64,
+        chunk: S,
+    ) -> Result<Upload, MiboxError> {
+        let upload = {
+            let mut uploads = self.uploads.write().expect("uploads lock poisoned");
+            let upload = uploads
+                .get_mut(id)
+                .filter(|upload| upload.username == username && !upload.is_expired())
+                .ok_or_else(|| UploadError::UploadNotFound(format!("no upload {}", id)))?;
+            if upload.busy {
+                return Err(UploadError::UploadBusy(format!(
+                    "a chunk of upload {} is being received",
+                    id
+                ))
+                .into());
+            }
+            if upload.offset != offset {
+                return Err(UploadError::OffsetMismatch(format!(
+                    "upload {} is at offset {}, not {}",
+                    id, upload.offset, offset
+                ))
+                .into());
+            }
+            upload.busy = true;
+            upload.clone()
+        };
+
+        // The guard records the bytes received even when the request is
+        // dropped halfway, e.g. because the client went away, and keeps the
+        // upload busy until it is committed so that it is committed once.
+        let mut receiving = Receiving {
+            uploads: self,
+            id,
+            received: 0,
+        };
+        let result = self.receive(&upload, chunk, &mut receiving.received).await;
+        let upload = receiving.record();
+        result?;
+        let upload =
+            upload.ok_or_else(|| UploadError::UploadNotFound(format!("no upload {}", id)))?;
+        if upload.is_complete() {
+            self.commit(&upload).await?;
+        }
+        Ok(upload)
+    }
+
+    /// Writes the bytes of `chunk` to the staged file, counting them in
+    /// `received` as they are written.
+    async fn receive<B: Buf, S: Stream<Item = Result<B, io::Error>>>(
+        &self,
+        upload: &Upload,
+        chunk: S,
+        received: &mut u64,
+    ) -> Result<(), MiboxError> {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .open(self.staged(&upload.id))
+            .await
+            .context("failed to open staged upload")?;
+        // Drops whatever was written past the offset by an interrupted chunk.
+        file.set_len(upload.offset)
+            .await
+            .context("failed to truncate staged upload")?;
+        file.seek(SeekFrom::End(0))
+            .await
+            .context("failed to seek staged upload")?;
+        let remaining = upload.length - upload.offset;
+        let mut chunk = pin!(chunk);
+        while let Some(bytes) = chunk.next().await {
+            let mut bytes = bytes.context("failed to receive upload chunk")?;
+            let length = bytes.remaining() as u64;
+            if length > remaining - *received {
+                return Err(UploadError::UploadTooLarge(format!(
+                    "upload {} is limited to {} bytes",
+                    upload.id, upload.length
+                ))
+                .into());
+            }
+            file.write_all_buf(&mut bytes)
+                .await
+                .context("failed to write staged upload")?;
+            *received += length;
+        }
+        file.flush()
+            .await
+            .context("failed to write staged upload")?;
+        Ok(())
+    }
+
+    /// Writes a complete upload to its drive and forgets it, it is kept when
+    /// the write fails so that it can be retried.
+    async fn commit(&self, upload: &Upload) -> Result<(), MiboxError> {
+        let staged = self.staged(&upload.id);
+        let file = fs::File::open(&staged)
+            .await
+            .context("failed to open staged upload")?;
+        upload
+            .drive
+            .write(ReaderStream::new(file), &upload.path)
+            .await?;
+        self.uploads
+            .write()
+            .expect("uploads lock poisoned")
+            .remove(&upload.id);
+        let _ = fs::remove_file(staged).await;
+        Ok(())
+    }
+
+    /// Cancels the upload `id` of `username`.
+    pub async fn terminate(&self, username: &str, id: &str) -> Result<(), MiboxError> {
+        {
+            let mut uploads = self.uploads.write().expect("uploads lock poisoned");
+            match uploads.get(id) {
+                Some(upload) if upload.username == username => {
+                    if upload.busy {
+                        return Err(UploadError::UploadBusy(format!(
+                            "a chunk of upload {} is being received",
+                            id
+                        ))
+                        .into());
+                    }
+                }
+                _ => return Err(UploadError::UploadNotFound(format!("no upload {}", id)).into()),
+            }
+            uploads.remove(id);
+        }
+        let _ = fs::remove_file(self.staged(id)).await;
+        Ok(())
+    }
+
+    /// Removes the staged files of no upload in progress, e.g. those left
+    /// behind by a previous run, returning how many were removed.
+    ///
+    /// Files staged meanwhile would be removed as well, this is meant to be
+    /// called before any upload is received.
+    pub async fn sweep(&self) -> usize {
+        let Ok(mut staged) = fs::read_dir(&self.staging).await else {
+            return 0;
+        };
+        let mut swept = 0;
+        while let Ok(Some(entry)) = staged.next_entry().await {
+            let tracked = entry.file_name().to_str().is_some_and(|id| {
+                self.uploads
+                    .read()
+                    .expect("uploads lock poisoned")
+                    .contains_key(id)
+            });
+            if !tracked && fs::remove_file(entry.path()).await.is_ok() {
+                swept += 1;
+            }
+        }
+        swept
+    }
+
+    /// Removes the uploads that expired along with their staged bytes,
+    /// returning how many were removed.
+    pub async fn expire(&self) -> usize {
+        let expired = {
+            let mut uploads = self.uploads.write().expect("uploads lock poisoned");
+            let expired = uploads
+                .values()
+                .filter(|upload| upload.is_expired() && !upload.busy)
+                .map(|upload| upload.id.clone())
+                .collect::<Vec<_>>();
+            for id in &expired {
+                uploads.remove(id);
+            }
+            expired
+        };
+        for id in &expired {
+            let _ = fs::remove_file(self.staged(id)).await;
+        }
+        expired.len()
+    }
+}
+
+fn random_id() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect()
+}
+
+/// A file staged by `Uploads::stage`, removed once dropped.
+pub struct Staged {
+    path: PathBuf,
+}
+
+impl Staged {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Staged {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Marks an upload as no longer busy once dropped, advancing its offset by
+/// the bytes received unless they were recorded already.
+struct Receiving<'a> {
+    uploads: &'a Uploads,
+    id: &'a str,
+    received: u64,
+}
+
+impl Receiving<'_> {
+    /// Advances the offset of the upload by the bytes received, leaving it
+    /// busy, and returns it unless it is gone.
+    fn record(&mut self) -> Option<Upload> {
+        let mut uploads = self.uploads.uploads.write().expect("uploads lock poisoned");
+        let upload = uploads.get_mut(self.id)?;
+        upload.offset += std::mem::take(&mut self.received);
+        upload.expires_at = SystemTime::now() + UPLOAD_TTL;
+        Some(upload.clone())
+    }
+}
+
+impl Drop for Receiving<'_> {
+    fn drop(&mut self) {
+        self.record();
+        let mut uploads = self.uploads.uploads.write().expect("uploads lock poisoned");
+        if let Some(upload) = uploads.get_mut(self.id) {
+            upload.busy = false;
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use crate::helpers::{
+    error_code, spawn_anonymous_app_with, temp_drive, test_user, TestApp, TEST_PASSWORD,
+    TEST_USERNAME,
+};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+
+/// Spawns the application with the test user owning a `docs` and a `private`
+/// directory, returning clients logged in as the owner, as `alice` and as
+/// `carol`, member of the `ops` group.
+async fn spawn_users() -> (TestApp, TestApp, TestApp) {
+    spawn_users_on("memory://").await
+}
+
+/// `spawn_users` on the drive at `drive`.
+async fn spawn_users_on(drive: &str) -> (TestApp, TestApp, TestApp) {
+    let owner = spawn_anonymous_app_with(|settings| {
+        settings.application.drive = drive.to_owned();
+        settings.application.users.push(test_user("alice"));
+        let mut carol = test_user("carol");
+        carol.groups = vec!["ops".to_string()];
+        settings.application.users.push(carol);
+    })
+    .await;
+    let alice = owner.with_new_client();
+    let carol = owner.with_new_client();
+    for (app, username) in [
+        (&owner, TEST_USERNAME),
+        (&alice, "alice"),
+        (&carol, "carol"),
+    ] {
+        let response = app.login(username, TEST_PASSWORD).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+    owner.client.create_dir(&owner.address, "docs").await;
+    owner.client.create_dir(&owner.address, "private").await;
+    let address = format!("{}/v1/file?path=docs", owner.address);
+    owner
+        .client
+        .upload_files(&address, vec![("Cargo.toml", "notes.txt")])
+        .await
+        .unwrap();
+    (owner, alice, carol)
+}
+
+async fn grant(owner: &TestApp, grant: Value) -> Value {
+    let response = owner
+        .client
+        .request_json(
+            Method::POST,
+            &format!("{}/v1/acl", owner.address),
+            &[],
+            grant,
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    response.json::<Value>().await.unwrap()["result"].clone()
+}
+
+async fn request(app: &TestApp, method: Method, path: &str) -> reqwest::Response {
+    app.client
+        .request_with_headers(method, &format!("{}{}", app.address, path), &[])
+        .await
+}
+
+#[tokio::test]
+async fn other_homes_are_forbidden_without_a_grant() {
+    let (_, alice, _) = spawn_users().await;
+    let response = request(&alice, Method::GET, "/v1/directory?owner=mibox&path=docs").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(error_code(response).await, "forbidden");
+    let response = request(
+        &alice,
+        Method::GET,
+        "/v1/file?owner=mibox&path=docs/notes.txt",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn read_grants_only_allow_reading_below_their_prefix() {
+    let (owner, alice, _) = spawn_users().await;
+    grant(
+        &owner,
+        json!({ "grantee": { "user": "alice" }, "permission": "read", "path_prefix": "docs" }),
+    )
+    .await;
+
+    let response = request(&alice, Method::GET, "/v1/directory?owner=mibox&path=docs").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = request(
+        &alice,
+        Method::GET,
+        "/v1/file?owner=mibox&path=docs/notes.txt",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = request(&alice, Method::GET, "/v1/directory?owner=mibox&path=").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = request(
+        &alice,
+        Method::GET,
+        "/v1/directory?owner=mibox&path=private",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = request(
+        &alice,
+        Method::DELETE,
+        "/v1/file?owner=mibox&path=docs/notes.txt",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = request(
+        &alice,
+        Method::POST,
+        "/v1/directory?owner=mibox&path=docs/new",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // her own home is left untouched by the grant
+    assert!(alice.client.list(&alice.address, "").await.is_empty());
+}
+
+#[tokio::test]
+async fn write_grants_allow_modifying_below_their_prefix() {
+    let (owner, alice, _) = spawn_users().await;
+    grant(
+        &owner,
+        json!({ "grantee": { "user": "alice" }, "permission": "write", "path_prefix": "docs" }),
+    )
+    .await;
+
+    let response = request(
+        &alice,
+        Method::POST,
+        "/v1/directory?owner=mibox&path=docs/a",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = request(
+        &alice,
+        Method::PUT,
+        "/v1/directory?owner=mibox&from=docs/a&to=docs/b",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = request(
+        &alice,
+        Method::PUT,
+        "/v1/directory?owner=mibox&from=docs/b&to=private/b",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let address = format!("{}/v1/file?owner=mibox&path=docs", alice.address);
+    let response = alice
+        .client
+        .upload_files(&address, vec![("Cargo.toml", "upload.txt")])
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let names = owner
+        .client
+        .list(&owner.address, "docs")
+        .await
+        .into_iter()
+        .map(|entry| entry.path)
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"b".to_string()));
+    assert!(names.contains(&"upload.txt".to_string()));
+}
+
+#[tokio::test]
+async fn grants_can_be_given_to_groups() {
+    let (owner, alice, carol) = spawn_users().await;
+    grant(
+        &owner,
+        json!({ "grantee": { "group": "ops" }, "permission": "read", "path_prefix": "docs" }),
+    )
+    .await;
+
+    let response = request(&carol, Method::GET, "/v1/directory?owner=mibox&path=docs").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = request(&alice, Method::GET, "/v1/directory?owner=mibox&path=docs").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn revoked_grants_stop_working() {
+    let (owner, alice, _) = spawn_users().await;
+    let created = grant(
+        &owner,
+        json!({ "grantee": { "user": "alice" }, "permission": "read", "path_prefix": "docs" }),
+    )
+    .await;
+
+    let response = request(&alice, Method::GET, "/v1/acl").await;
+    let grants = response.json::<Value>().await.unwrap()["result"].clone();
+    assert_eq!(grants["received"][0]["id"], created["id"]);
+    assert_eq!(grants["received"][0]["owner"], TEST_USERNAME);
+
+    let path = format!("/v1/acl?id={}", created["id"].as_str().unwrap());
+    let response = request(&owner, Method::DELETE, &path).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = request(&alice, Method::GET, "/v1/directory?owner=mibox&path=docs").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = request(&owner, Method::DELETE, &path).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn grants_require_an_existing_user_and_entry() {
+    let (owner, _, _) = spawn_users().await;
+    let address = format!("{}/v1/acl", owner.address);
+    let response = owner
+        .client
+        .request_json(
+            Method::POST,
+            &address,
+            &[],
+            json!({ "grantee": { "user": "nobody" }, "permission": "read", "path_prefix": "docs" }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = owner
+        .client
+        .request_json(
+            Method::POST,
+            &address,
+            &[],
+            json!({ "grantee": { "user": "alice" }, "permission": "read", "path_prefix": "missing" }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn grants_outlive_a_restart() {
+    let drive = temp_drive();
+    let (owner, _, _) = spawn_users_on(drive.to_str().unwrap()).await;
+    grant(
+        &owner,
+        json!({ "grantee": { "group": "ops" }, "permission": "write", "path_prefix": "docs" }),
+    )
+    .await;
+    let revoked = grant(
+        &owner,
+        json!({ "grantee": { "user": "alice" }, "permission": "read", "path_prefix": "docs" }),
+    )
+    .await;
+    let path = format!("/v1/acl?id={}", revoked["id"].as_str().unwrap());
+    let response = request(&owner, Method::DELETE, &path).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let (_, alice, carol) = spawn_users_on(drive.to_str().unwrap()).await;
+    let response = request(
+        &carol,
+        Method::POST,
+        "/v1/directory?owner=mibox&path=docs/new",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = request(
+        &carol,
+        Method::GET,
+        "/v1/directory?owner=mibox&path=private",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = request(&alice, Method::GET, "/v1/directory?owner=mibox&path=docs").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let _ = std::fs::remove_dir_all(drive);
+}
+
+#[tokio::test]
+async fn links_do_not_lead_out_of_a_grant() {
+    let drive = temp_drive();
+    let (owner, alice, _) = spawn_users_on(drive.to_str().unwrap()).await;
+    let home = drive.join(TEST_USERNAME);
+    std::fs::write(home.join("private/secret.txt"), "secret").unwrap();
+    std::os::unix::fs::symlink("../private", home.join("docs/link")).unwrap();
+    grant(
+        &owner,
+        json!({ "grantee": { "user": "alice" }, "permission": "read", "path_prefix": "docs" }),
+    )
+    .await;
+
+    let response = request(
+        &alice,
+        Method::GET,
+        "/v1/file?owner=mibox&path=docs/notes.txt",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = request(
+        &alice,
+        Method::GET,
+        "/v1/file?owner=mibox&path=docs/link/secret.txt",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = request(
+        &alice,
+        Method::GET,
+        "/v1/directory?owner=mibox&path=docs/link",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // the link is still followed by the owner
+    let response = request(&owner, Method::GET, "/v1/file?path=docs/link/secret.txt").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let _ = std::fs::remove_dir_all(drive);
+}
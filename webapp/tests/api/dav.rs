@@ -0,0 +1,435 @@
+use crate::helpers::{spawn_app, spawn_app_on, temp_drive, TestApp, TEST_PASSWORD, TEST_USERNAME};
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+use axum_extra::headers::{Authorization, HeaderMapExt};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+const LOCKINFO: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<D:lockinfo xmlns:D="DAV:">
+  <D:lockscope><D:exclusive/></D:lockscope>
+  <D:locktype><D:write/></D:locktype>
+  <D:owner><D:href>mailto:mibox@example.com</D:href></D:owner>
+</D:lockinfo>"#;
+
+impl TestApp {
+    async fn dav(&self, method: &str, path: &str, headers: &[(&str, &str)]) -> reqwest::Response {
+        self.dav_with_body(method, path, headers, "").await
+    }
+
+    async fn dav_with_body(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> reqwest::Response {
+        let address = format!("{}/dav{}", self.address, path);
+        self.client
+            .request_with_body(method, &address, headers, body)
+            .await
+    }
+}
+
+#[tokio::test]
+async fn options_advertises_webdav() {
+    let app = spawn_app().await;
+    let response = app.dav("OPTIONS", "/", &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["dav"], "1, 2");
+    assert!(response.headers()["allow"]
+        .to_str()
+        .unwrap()
+        .contains("PROPFIND"));
+}
+
+#[tokio::test]
+async fn webdav_requires_authentication() {
+    let app = spawn_app().await.with_new_client();
+    let response = app.dav("PROPFIND", "/", &[("Depth", "0")]).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.headers()["www-authenticate"]
+        .to_str()
+        .unwrap()
+        .starts_with("Basic"));
+
+    let credentials = basic(TEST_USERNAME, TEST_PASSWORD);
+    let response = app
+        .dav(
+            "PROPFIND",
+            "/",
+            &[("Depth", "0"), ("Authorization", &credentials)],
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+
+    let wrong = basic(TEST_USERNAME, "wrong");
+    let response = app
+        .dav(
+            "PROPFIND",
+            "/",
+            &[("Depth", "0"), ("Authorization", &wrong)],
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn propfind_lists_collections() {
+    let app = spawn_app().await;
+    assert_eq!(
+        app.dav("MKCOL", "/docs", &[]).await.status(),
+        StatusCode::CREATED
+    );
+    let response = app
+        .dav_with_body("PUT", "/docs/read%20me.txt", &[], "hello")
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let response = app.dav("PROPFIND", "/docs", &[("Depth", "0")]).await;
+    assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("<D:href>/dav/docs/</D:href>"));
+    assert!(body.contains("<D:collection/>"));
+    assert!(!body.contains("read%20me.txt"));
+
+    let response = app.dav("PROPFIND", "/docs/", &[("Depth", "1")]).await;
+    let body = response.text().await.unwrap();
+    assert!(body.contains("<D:href>/dav/docs/read%20me.txt</D:href>"));
+    assert!(body.contains("<D:getcontentlength>5</D:getcontentlength>"));
+    assert!(body.contains("<D:displayname>read me.txt</D:displayname>"));
+
+    let response = app.dav("PROPFIND", "/", &[("Depth", "infinity")]).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn propfind_reports_unknown_properties() {
+    let app = spawn_app().await;
+    app.dav_with_body("PUT", "/notes.txt", &[], "notes").await;
+    let propfind = r#"<?xml version="1.0" encoding="utf-8"?>
+<D:propfind xmlns:D="DAV:" xmlns:Z="urn:example">
+  <D:prop><D:getcontentlength/><Z:color/></D:prop>
+</D:propfind>"#;
+    let response = app
+        .dav_with_body("PROPFIND", "/notes.txt", &[("Depth", "0")], propfind)
+        .await;
+    assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("<D:getcontentlength>5</D:getcontentlength>"));
+    assert!(body.contains("404 Not Found"));
+    assert!(body.contains("color"));
+    assert!(!body.contains("getetag"));
+}
+
+#[tokio::test]
+async fn put_and_get_files() {
+    let app = spawn_app().await;
+    let response = app.dav_with_body("PUT", "/notes.txt", &[], "first").await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let response = app.dav_with_body("PUT", "/notes.txt", &[], "second").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = app.dav("GET", "/notes.txt", &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().contains_key("etag"));
+    assert_eq!(response.text().await.unwrap(), "second");
+
+    let response = app
+        .dav_with_body("PUT", "/missing/notes.txt", &[], "x")
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let response = app.dav("GET", "/missing.txt", &[]).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn files_are_served_sandboxed_as_attachments() {
+    let app = spawn_app().await;
+    let page = "<script>fetch('/v1/token', { method: 'POST' })</script>";
+    app.dav_with_body("PUT", "/page.html", &[], page).await;
+
+    let response = app.dav("GET", "/page.html", &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert_eq!(headers["content-type"], "text/html");
+    assert_eq!(
+        headers["content-disposition"],
+        "attachment; filename=\"page.html\""
+    );
+    assert_eq!(headers["x-content-type-options"], "nosniff");
+    assert_eq!(headers["content-security-policy"], "sandbox");
+}
+
+#[tokio::test]
+async fn mkcol_and_delete() {
+    let app = spawn_app().await;
+    assert_eq!(
+        app.dav("MKCOL", "/a", &[]).await.status(),
+        StatusCode::CREATED
+    );
+    assert_eq!(
+        app.dav("MKCOL", "/a", &[]).await.status(),
+        StatusCode::METHOD_NOT_ALLOWED
+    );
+    assert_eq!(
+        app.dav("MKCOL", "/missing/b", &[]).await.status(),
+        StatusCode::CONFLICT
+    );
+    app.dav_with_body("PUT", "/a/file.txt", &[], "x").await;
+
+    assert_eq!(
+        app.dav("DELETE", "/a", &[]).await.status(),
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        app.dav("PROPFIND", "/a", &[("Depth", "0")]).await.status(),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        app.dav("DELETE", "/", &[]).await.status(),
+        StatusCode::FORBIDDEN
+    );
+}
+
+#[tokio::test]
+async fn deleted_and_overwritten_entries_are_moved_to_the_trash() {
+    let app = spawn_app().await;
+    app.dav("MKCOL", "/a", &[]).await;
+    app.dav_with_body("PUT", "/a/file.txt", &[], "x").await;
+    app.dav_with_body("PUT", "/b.txt", &[], "old").await;
+    app.dav_with_body("PUT", "/c.txt", &[], "new").await;
+
+    assert_eq!(
+        app.dav("DELETE", "/a", &[]).await.status(),
+        StatusCode::NO_CONTENT
+    );
+    let destination = format!("{}/dav/b.txt", app.address);
+    assert_eq!(
+        app.dav("COPY", "/c.txt", &[("Destination", &destination)])
+            .await
+            .status(),
+        StatusCode::NO_CONTENT
+    );
+
+    let address = format!("{}/v1/trash", app.address);
+    let response = app
+        .client
+        .request_with_headers(reqwest::Method::GET, &address, &[])
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.json::<Value>().await.unwrap();
+    let mut trashed = body["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["path"].as_str().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    trashed.sort();
+    assert_eq!(trashed, ["a", "b.txt"]);
+}
+
+#[tokio::test]
+async fn destinations_on_other_servers_are_refused() {
+    let app = spawn_app().await;
+    app.dav_with_body("PUT", "/a.txt", &[], "a").await;
+
+    let response = app
+        .dav(
+            "COPY",
+            "/a.txt",
+            &[("Destination", "http://elsewhere.example/dav/b.txt")],
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    let response = app.dav("GET", "/b.txt", &[]).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn overwritten_destinations_are_restored_when_the_copy_fails() {
+    let drive = temp_drive();
+    let app = spawn_app_on(&drive).await;
+    app.dav("MKCOL", "/src", &[]).await;
+    app.dav_with_body("PUT", "/src/a.txt", &[], "a").await;
+    app.dav("MKCOL", "/dst", &[]).await;
+    app.dav_with_body("PUT", "/dst/kept.txt", &[], "kept").await;
+    // Links leading out of the drive cannot be copied.
+    std::os::unix::fs::symlink("/etc", drive.join(TEST_USERNAME).join("src/escape")).unwrap();
+
+    let destination = format!("{}/dav/dst", app.address);
+    let response = app
+        .dav("COPY", "/src", &[("Destination", &destination)])
+        .await;
+    assert!(!response.status().is_success());
+    let response = app.dav("GET", "/dst/kept.txt", &[]).await;
+    assert_eq!(response.text().await.unwrap(), "kept");
+    let response = app.dav("GET", "/dst/a.txt", &[]).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn copy_and_move() {
+    let app = spawn_app().await;
+    app.dav("MKCOL", "/src", &[]).await;
+    app.dav_with_body("PUT", "/src/file.txt", &[], "content")
+        .await;
+    let destination = format!("{}/dav/copy", app.address);
+
+    let response = app
+        .dav("COPY", "/src", &[("Destination", &destination)])
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let response = app.dav("GET", "/copy/file.txt", &[]).await;
+    assert_eq!(response.text().await.unwrap(), "content");
+
+    let response = app
+        .dav(
+            "COPY",
+            "/src",
+            &[("Destination", &destination), ("Overwrite", "F")],
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = app
+        .dav("COPY", "/src", &[("Destination", &destination)])
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = app
+        .dav(
+            "MOVE",
+            "/src/file.txt",
+            &[("Destination", "/dav/moved%20file.txt")],
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(
+        app.dav("GET", "/src/file.txt", &[]).await.status(),
+        StatusCode::NOT_FOUND
+    );
+    let response = app.dav("GET", "/moved%20file.txt", &[]).await;
+    assert_eq!(response.text().await.unwrap(), "content");
+
+    let response = app
+        .dav("MOVE", "/src", &[("Destination", "/dav/src/inner")])
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = app.dav("MOVE", "/src", &[]).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn copy_and_move_onto_an_ancestor_are_forbidden() {
+    let app = spawn_app().await;
+    app.dav("MKCOL", "/a", &[]).await;
+    app.dav("MKCOL", "/a/b", &[]).await;
+    app.dav_with_body("PUT", "/a/b/file.txt", &[], "content")
+        .await;
+
+    for method in ["COPY", "MOVE"] {
+        for destination in ["/dav/a", "/dav/a/b"] {
+            let response = app
+                .dav(
+                    method,
+                    "/a/b",
+                    &[("Destination", destination), ("Overwrite", "T")],
+                )
+                .await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+    }
+    let response = app.dav("GET", "/a/b/file.txt", &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "content");
+}
+
+#[tokio::test]
+async fn locks_protect_resources() {
+    let app = spawn_app().await;
+    let response = app
+        .dav_with_body(
+            "LOCK",
+            "/locked.txt",
+            &[("Timeout", "Second-600")],
+            LOCKINFO,
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let token = response.headers()["lock-token"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    assert!(token.starts_with("<opaquelocktoken:"));
+    let body = response.text().await.unwrap();
+    assert!(body.contains("<D:timeout>Second-600</D:timeout>"));
+    assert!(body.contains("mailto:mibox@example.com"));
+
+    let response = app.dav_with_body("PUT", "/locked.txt", &[], "x").await;
+    assert_eq!(response.status(), StatusCode::LOCKED);
+    let response = app.dav("DELETE", "/locked.txt", &[]).await;
+    assert_eq!(response.status(), StatusCode::LOCKED);
+    let response = app
+        .dav_with_body("LOCK", "/locked.txt", &[], LOCKINFO)
+        .await;
+    assert_eq!(response.status(), StatusCode::LOCKED);
+
+    let condition = format!("({})", token);
+    let response = app
+        .dav_with_body("PUT", "/locked.txt", &[("If", &condition)], "x")
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = app
+        .dav(
+            "LOCK",
+            "/locked.txt",
+            &[("If", &condition), ("Timeout", "Infinite")],
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .dav("UNLOCK", "/locked.txt", &[("Lock-Token", &token)])
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = app.dav_with_body("PUT", "/locked.txt", &[], "y").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = app
+        .dav("UNLOCK", "/locked.txt", &[("Lock-Token", &token)])
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn read_tokens_cannot_write_through_webdav() {
+    let app = spawn_app().await;
+    let response = app
+        .client
+        .create_token(&app.address, json!({ "name": "phone", "scope": "read" }))
+        .await;
+    let token = response.json::<Value>().await.unwrap()["result"]["token"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    let app = app.with_new_client();
+    let credentials = basic(TEST_USERNAME, &token);
+
+    let response = app
+        .dav(
+            "PROPFIND",
+            "/",
+            &[("Depth", "0"), ("Authorization", &credentials)],
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+    let response = app
+        .dav_with_body("PUT", "/notes.txt", &[("Authorization", &credentials)], "x")
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+/// The `Authorization` header value of basic credentials.
+fn basic(username: &str, password: &str) -> String {
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(Authorization::basic(username, password));
+    headers[AUTHORIZATION].to_str().unwrap().to_owned()
+}
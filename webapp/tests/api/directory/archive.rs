@@ -0,0 +1,165 @@
+use crate::helpers::{error_code, spawn_app, spawn_app_on, temp_drive, TestApp, TEST_USERNAME};
+use async_compression::tokio::bufread::GzipDecoder;
+use async_zip::base::read::mem::ZipFileReader;
+use reqwest::{header, StatusCode};
+use tokio::io::AsyncReadExt;
+
+/// Uploads `Cargo.toml` as `docs/a.toml` and `docs/nested/b.toml`, along with
+/// an empty `docs/empty` directory.
+async fn create_tree(app: &TestApp) -> String {
+    let cargo_toml = std::fs::read_to_string("Cargo.toml").unwrap();
+    for dir in ["docs", "docs/nested", "docs/empty"] {
+        app.client.create_dir(&app.address, dir).await;
+    }
+    for (dir, name) in [("docs", "a.toml"), ("docs/nested", "b.toml")] {
+        let address = format!("{}/v1/file?path={}", app.address, dir);
+        let response = app
+            .client
+            .upload_files(&address, vec![("Cargo.toml", name)])
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    cargo_toml
+}
+
+async fn download_archive(app: &TestApp, query: &str) -> reqwest::Response {
+    app.client
+        .download_file(&format!("{}/v1/directory/archive?{}", app.address, query))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn directories_download_as_zip() {
+    let app = spawn_app().await;
+    let cargo_toml = create_tree(&app).await;
+
+    let response = download_archive(&app, "path=docs").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/zip");
+    assert_eq!(
+        response.headers()[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"docs.zip\""
+    );
+    let archive = response.bytes().await.unwrap().to_vec();
+    let zip = ZipFileReader::new(archive).await.unwrap();
+    let names = zip
+        .file()
+        .entries()
+        .iter()
+        .map(|entry| entry.filename().as_str().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["a.toml", "empty/", "nested/", "nested/b.toml"]);
+    assert!(zip.file().entries()[0].last_modification_date().year() >= 2024);
+
+    let mut content = String::new();
+    let mut reader = zip.reader_with_entry(3).await.unwrap();
+    reader.read_to_string_checked(&mut content).await.unwrap();
+    assert_eq!(content, cargo_toml);
+}
+
+#[tokio::test]
+async fn directories_download_as_tar_gz() {
+    let app = spawn_app().await;
+    let cargo_toml = create_tree(&app).await;
+    let long_name = "l".repeat(80);
+    app.client
+        .create_dir(&app.address, &format!("docs/{}", long_name))
+        .await;
+    let address = format!("{}/v1/file?path=docs/{}", app.address, long_name);
+    app.client
+        .upload_files(&address, vec![("Cargo.toml", &long_name)])
+        .await
+        .unwrap();
+
+    let response = download_archive(&app, "path=docs&format=tar.gz").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/gzip");
+    let compressed = response.bytes().await.unwrap();
+    let mut archive = vec![];
+    GzipDecoder::new(&compressed[..])
+        .read_to_end(&mut archive)
+        .await
+        .unwrap();
+
+    let mut entries = vec![];
+    for entry in tar::Archive::new(&archive[..]).entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let path = entry.path().unwrap().to_string_lossy().into_owned();
+        assert!(entry.header().mtime().unwrap() > 0);
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut entry, &mut content).unwrap();
+        entries.push((path, content));
+    }
+    let long_path = format!("{0}/{0}", long_name);
+    let names = entries
+        .iter()
+        .map(|(path, _)| path.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "a.toml",
+            "empty/",
+            &format!("{}/", long_name),
+            &long_path,
+            "nested/",
+            "nested/b.toml"
+        ]
+    );
+    assert_eq!(entries[3].1, cargo_toml);
+    assert_eq!(entries[5].1, cargo_toml);
+}
+
+#[tokio::test]
+async fn the_home_downloads_as_an_archive() {
+    let app = spawn_app().await;
+    create_tree(&app).await;
+    let response = download_archive(&app, "path=").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"home.zip\""
+    );
+    let archive = response.bytes().await.unwrap().to_vec();
+    let zip = ZipFileReader::new(archive).await.unwrap();
+    assert_eq!(
+        zip.file().entries()[0].filename().as_str().unwrap(),
+        "docs/"
+    );
+}
+
+#[tokio::test]
+async fn files_and_unknown_formats_are_rejected() {
+    let app = spawn_app().await;
+    create_tree(&app).await;
+    let response = download_archive(&app, "path=docs/a.toml").await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(error_code(response).await, "entry_unexpected_type");
+    let response = download_archive(&app, "path=missing").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = download_archive(&app, "path=docs&format=rar").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn links_to_directories_are_left_out() {
+    let drive = temp_drive();
+    let app = spawn_app_on(&drive).await;
+    create_tree(&app).await;
+    std::os::unix::fs::symlink(".", drive.join(TEST_USERNAME).join("docs/loop")).unwrap();
+
+    let response = download_archive(&app, "path=docs").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let archive = response.bytes().await.unwrap().to_vec();
+    let zip = ZipFileReader::new(archive).await.unwrap();
+    let names = zip
+        .file()
+        .entries()
+        .iter()
+        .map(|entry| entry.filename().as_str().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["a.toml", "empty/", "nested/", "nested/b.toml"]);
+    let _ = std::fs::remove_dir_all(drive);
+}
@@ -1,27 +1,24 @@
-use webapp::handlers::directory::DirectoryView;
-
-use crate::helpers::spawn_app;
+use crate::helpers::{error_code, spawn_app};
 
 #[tokio::test]
 async fn when_query_parameters_are_missing_returns_a_400() {
     let app = spawn_app().await;
     let response = app.client.create_dir(&app.address, "").await;
     assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_query");
     assert_eq!(
-        "Failed to deserialize query string: missing field `path`",
-        response.text().await.unwrap()
+        body["error"]["message"],
+        "Failed to deserialize query string: missing field `path`"
     );
 }
 
 #[tokio::test]
-async fn when_path_parameter_is_forbidden_returns_500() {
+async fn when_path_parameter_is_forbidden_returns_400() {
     let app = spawn_app().await;
     let response = app.client.create_dir(&app.address, "/").await;
-    assert_eq!(
-        response.status(),
-        reqwest::StatusCode::INTERNAL_SERVER_ERROR
-    );
-    assert_eq!("Something went wrong", response.text().await.unwrap());
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "entry_name_invalid");
 }
 
 #[tokio::test]
@@ -31,9 +28,18 @@ async fn when_request_is_wellformed_returns_204() {
     let response = app.client.create_dir(&app.address, &dir).await;
     assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
     let response = app.client.list(&app.address, "").await;
-    let has_dir = response.contains(&DirectoryView {
-        path: dir,
-        is_directory: true,
-    });
+    let has_dir = response
+        .iter()
+        .any(|view| view.path == dir && view.is_directory);
     assert!(has_dir)
 }
+
+#[tokio::test]
+async fn when_directory_exists_returns_409() {
+    let app = spawn_app().await;
+    let dir = crate::helpers::random_name(10);
+    app.client.create_dir(&app.address, &dir).await;
+    let response = app.client.create_dir(&app.address, &dir).await;
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(error_code(response).await, "entry_exists");
+}
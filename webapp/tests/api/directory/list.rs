@@ -1 +1,102 @@
+use crate::helpers::{
+    random_name, spawn_anonymous_app_with, spawn_app, TEST_PASSWORD, TEST_USERNAME,
+};
 
+#[tokio::test]
+async fn when_request_is_wellformed_returns_entry_metadata() {
+    let app = spawn_app().await;
+    let dir = crate::helpers::random_name(10);
+    app.client.create_dir(&app.address, &dir).await;
+    let address = format!("{}/v1/file?path={dir}", app.address);
+    app.client
+        .upload_files(&address, vec![("Cargo.toml", "Cargo.toml")])
+        .await
+        .expect("failed to send request");
+
+    let response = app.client.list(&app.address, &dir).await;
+    assert_eq!(response.len(), 1);
+    let file = &response[0];
+    assert_eq!(file.path, "Cargo.toml");
+    assert!(!file.is_directory);
+    assert!(!file.is_symlink);
+    assert_eq!(file.size, std::fs::metadata("Cargo.toml").unwrap().len());
+    assert!(file.modified.is_some());
+    assert_eq!(file.mime_type.as_deref(), Some("text/x-toml"));
+
+    app.client
+        .upload_bytes(&address, "scan", b"%PDF-1.4".to_vec())
+        .await;
+    let response = app.client.list(&app.address, &dir).await;
+    let file = response.iter().find(|view| view.path == "scan").unwrap();
+    assert_eq!(file.mime_type.as_deref(), Some("application/pdf"));
+
+    let response = app.client.list(&app.address, "").await;
+    let directory = response.iter().find(|view| view.path == dir).unwrap();
+    assert!(directory.is_directory);
+    assert_eq!(directory.size, 0);
+    assert_eq!(directory.mime_type, None);
+}
+
+#[tokio::test]
+async fn when_listing_is_unchanged_returns_304() {
+    let app = spawn_app().await;
+    let dir = crate::helpers::random_name(10);
+    app.client.create_dir(&app.address, &dir).await;
+    let address = format!("{}/v1/directory?path={dir}", app.address);
+    let response = app
+        .client
+        .request_with_headers(reqwest::Method::GET, &address, &[])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let etag = response.headers()["etag"].to_str().unwrap().to_owned();
+
+    let response = app
+        .client
+        .request_with_headers(reqwest::Method::GET, &address, &[("If-None-Match", &etag)])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_MODIFIED);
+
+    app.client
+        .upload_files(
+            &format!("{}/v1/file?path={dir}", app.address),
+            vec![("Cargo.toml", "Cargo.toml")],
+        )
+        .await
+        .expect("failed to send request");
+    let response = app
+        .client
+        .request_with_headers(reqwest::Method::GET, &address, &[("If-None-Match", &etag)])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_ne!(response.headers()["etag"], etag.as_str());
+}
+
+#[tokio::test]
+async fn when_drive_is_indexed_listings_follow_changes() {
+    let index_dir = std::env::temp_dir().join(format!("mibox-index-{}", random_name(10)));
+    std::fs::create_dir(&index_dir).unwrap();
+    let path = index_dir.join("index.sqlite3").to_str().unwrap().to_owned();
+    let app = spawn_anonymous_app_with(|settings| settings.application.index = Some(path)).await;
+    app.login(TEST_USERNAME, TEST_PASSWORD).await;
+    let dir = random_name(10);
+    app.client.create_dir(&app.address, &dir).await;
+    let address = format!("{}/v1/file?path={dir}", app.address);
+    app.client
+        .upload_bytes(&address, "scan", b"%PDF-1.4".to_vec())
+        .await;
+
+    let response = app.client.list(&app.address, &dir).await;
+    assert_eq!(response.len(), 1);
+    assert_eq!(response[0].path, "scan");
+    assert_eq!(response[0].size, 8);
+    assert_eq!(response[0].mime_type.as_deref(), Some("application/pdf"));
+
+    let renamed = random_name(10);
+    let response = app.client.update_dir(&app.address, &dir, &renamed).await;
+    assert!(response.status().is_success());
+    let response = app.client.list(&app.address, "").await;
+    assert!(response.iter().any(|view| view.path == renamed));
+    assert!(!response.iter().any(|view| view.path == dir));
+    assert_eq!(app.client.list(&app.address, &renamed).await.len(), 1);
+    let _ = std::fs::remove_dir_all(index_dir);
+}
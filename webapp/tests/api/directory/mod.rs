@@ -1,3 +1,4 @@
+mod archive;
 mod create_dir;
 mod list;
 mod remove_dir;
@@ -1,5 +1,4 @@
-use crate::helpers::spawn_app;
-use webapp::handlers::directory::DirectoryView;
+use crate::helpers::{error_code, spawn_app};
 
 #[tokio::test]
 async fn when_query_parameters_are_missing_returns_a_400() {
@@ -7,22 +6,21 @@ async fn when_query_parameters_are_missing_returns_a_400() {
 
     let response = app.client.delete_dir(&app.address, "").await;
     assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_query");
     assert_eq!(
-        "Failed to deserialize query string: missing field `path`",
-        response.text().await.unwrap()
+        body["error"]["message"],
+        "Failed to deserialize query string: missing field `path`"
     );
 }
 
 #[tokio::test]
-async fn when_path_parameter_is_forbidden_returns_500() {
+async fn when_path_parameter_is_forbidden_returns_400() {
     let app = spawn_app().await;
 
     let response = app.client.delete_dir(&app.address, "/").await;
-    assert_eq!(
-        response.status(),
-        reqwest::StatusCode::INTERNAL_SERVER_ERROR
-    );
-    assert_eq!("Something went wrong", response.text().await.unwrap());
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "entry_name_invalid");
 }
 
 #[tokio::test]
@@ -34,9 +32,77 @@ async fn when_request_is_wellformed_returns_204() {
     let response = app.client.delete_dir(&app.address, &dir).await;
     assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
     let response = app.client.list(&app.address, "").await;
-    let has_dir = !response.contains(&DirectoryView {
-        path: dir,
-        is_directory: true,
-    });
+    let has_dir = !response
+        .iter()
+        .any(|view| view.path == dir && view.is_directory);
     assert!(has_dir)
 }
+
+#[tokio::test]
+async fn when_if_match_does_not_match_returns_412() {
+    let app = spawn_app().await;
+    let dir = crate::helpers::random_name(10);
+    app.client.create_dir(&app.address, &dir).await;
+    let address = format!("{}/v1/directory?path={dir}", app.address);
+    let response = app
+        .client
+        .request_with_headers(
+            reqwest::Method::DELETE,
+            &address,
+            &[("If-Match", "\"stale\"")],
+        )
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::PRECONDITION_FAILED);
+    let response = app.client.list(&app.address, "").await;
+    assert!(response.iter().any(|view| view.path == dir));
+}
+
+#[tokio::test]
+async fn when_path_walks_out_of_the_drive_returns_400() {
+    let app = spawn_app().await;
+
+    let response = app.client.delete_dir(&app.address, "../..").await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "entry_name_invalid");
+}
+
+#[tokio::test]
+async fn when_path_is_a_file_returns_422() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=", app.address);
+    app.client
+        .upload_files(&address, vec![("Cargo.toml", "dummy.txt")])
+        .await
+        .expect("failed to send request");
+
+    let response = app.client.delete_dir(&app.address, "dummy.txt").await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(error_code(response).await, "entry_unexpected_type");
+}
+
+#[tokio::test]
+async fn when_not_recursive_and_directory_is_not_empty_returns_409() {
+    let app = spawn_app().await;
+    let dir = crate::helpers::random_name(10);
+    app.client.create_dir(&app.address, &dir).await;
+    app.client
+        .create_dir(&app.address, &format!("{dir}/inner"))
+        .await;
+    let address = format!("{}/v1/directory?path={dir}&recursive=false", app.address);
+
+    let response = app
+        .client
+        .request_with_headers(reqwest::Method::DELETE, &address, &[])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(error_code(response).await, "entry_not_empty");
+
+    app.client
+        .delete_dir(&app.address, &format!("{dir}/inner"))
+        .await;
+    let response = app
+        .client
+        .request_with_headers(reqwest::Method::DELETE, &address, &[])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+}
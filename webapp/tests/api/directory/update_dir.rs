@@ -1,6 +1,4 @@
-use webapp::handlers::directory::DirectoryView;
-
-use crate::helpers::spawn_app;
+use crate::helpers::{error_code, spawn_app};
 
 #[tokio::test]
 async fn when_to_parameter_is_missing_returns_a_400() {
@@ -8,9 +6,11 @@ async fn when_to_parameter_is_missing_returns_a_400() {
 
     let response = app.client.update_dir(&app.address, "/", "").await;
     assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_query");
     assert_eq!(
-        "Failed to deserialize query string: missing field `to`",
-        response.text().await.unwrap()
+        body["error"]["message"],
+        "Failed to deserialize query string: missing field `to`"
     );
 }
 #[tokio::test]
@@ -19,38 +19,34 @@ async fn when_from_parameter_is_missing_returns_a_400() {
 
     let response = app.client.update_dir(&app.address, "", "/").await;
     assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_query");
     assert_eq!(
-        "Failed to deserialize query string: missing field `from`",
-        response.text().await.unwrap()
+        body["error"]["message"],
+        "Failed to deserialize query string: missing field `from`"
     );
 }
 
 #[tokio::test]
-async fn when_to_parameter_is_forbidden_returns_500() {
+async fn when_to_parameter_is_forbidden_returns_400() {
     let app = spawn_app().await;
     let dir = crate::helpers::random_name(10);
     app.client.create_dir(&app.address, &dir).await;
 
     let response = app.client.update_dir(&app.address, &dir, "/no-dir").await;
-    assert_eq!(
-        response.status(),
-        reqwest::StatusCode::INTERNAL_SERVER_ERROR
-    );
-    assert_eq!("Something went wrong", response.text().await.unwrap());
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "entry_name_invalid");
 }
 
 #[tokio::test]
-async fn when_from_parameter_is_forbidden_returns_500() {
+async fn when_from_parameter_is_forbidden_returns_400() {
     let app = spawn_app().await;
     let dir = crate::helpers::random_name(10);
     app.client.create_dir(&app.address, &dir).await;
 
     let response = app.client.update_dir(&app.address, "/no-dir", &dir).await;
-    assert_eq!(
-        response.status(),
-        reqwest::StatusCode::INTERNAL_SERVER_ERROR
-    );
-    assert_eq!("Something went wrong", response.text().await.unwrap());
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "entry_name_invalid");
 }
 
 #[tokio::test]
@@ -64,9 +60,40 @@ async fn when_request_is_wellformed_returns_204() {
     assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
 
     let response = app.client.list(&app.address, "").await;
-    let has_dir = response.contains(&DirectoryView {
-        path: new_dir,
-        is_directory: true,
-    });
+    let has_dir = response
+        .iter()
+        .any(|view| view.path == new_dir && view.is_directory);
     assert!(has_dir)
 }
+
+#[tokio::test]
+async fn when_if_match_does_not_match_returns_412() {
+    let app = spawn_app().await;
+    let dir = crate::helpers::random_name(10);
+    let new_dir = crate::helpers::random_name(10);
+    app.client.create_dir(&app.address, &dir).await;
+    let address = format!("{}/v1/directory?from={dir}&to={new_dir}", app.address);
+    let response = app
+        .client
+        .request_with_headers(reqwest::Method::PUT, &address, &[("If-Match", "\"stale\"")])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::PRECONDITION_FAILED);
+
+    let etag = app
+        .client
+        .request_with_headers(
+            reqwest::Method::GET,
+            &format!("{}/v1/directory?path={dir}", app.address),
+            &[],
+        )
+        .await
+        .headers()["etag"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let response = app
+        .client
+        .request_with_headers(reqwest::Method::PUT, &address, &[("If-Match", &etag)])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+}
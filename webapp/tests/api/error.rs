@@ -0,0 +1,90 @@
+use crate::helpers::{error_code, spawn_app, TestApp};
+use reqwest::{header::WWW_AUTHENTICATE, Method, StatusCode};
+use serde_json::{json, Value};
+
+async fn request(
+    app: &TestApp,
+    method: Method,
+    path: &str,
+    headers: &[(&str, &str)],
+) -> reqwest::Response {
+    app.client
+        .request_with_headers(method, &format!("{}{}", app.address, path), headers)
+        .await
+}
+
+#[tokio::test]
+async fn invalid_requests_are_described_in_json() {
+    let app = spawn_app().await;
+    let response = request(&app, Method::GET, "/v1/search?min_size=2&max_size=1", &[]).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "invalid_request");
+
+    let response = request(&app, Method::GET, "/v1/directory", &[]).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "invalid_query");
+}
+
+#[tokio::test]
+async fn authentication_failures_are_described_in_json() {
+    let app = spawn_app().await;
+    let anonymous = app.with_new_client();
+    let response = request(&anonymous, Method::GET, "/v1/directory?path=", &[]).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.headers().contains_key(WWW_AUTHENTICATE));
+    assert_eq!(error_code(response).await, "unauthenticated");
+
+    let response = request(
+        &anonymous,
+        Method::GET,
+        "/v1/directory?path=",
+        &[("Authorization", "Bearer unknown")],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(error_code(response).await, "invalid_token");
+}
+
+#[tokio::test]
+async fn insufficient_scopes_are_described_in_json() {
+    let app = spawn_app().await;
+    app.client.create_dir(&app.address, "docs").await;
+    let response = app
+        .client
+        .create_token(&app.address, json!({ "name": "backup", "scope": "read" }))
+        .await;
+    let body = response.json::<Value>().await.unwrap();
+    let authorization = format!("Bearer {}", body["result"]["token"].as_str().unwrap());
+
+    let response = request(
+        &app.with_new_client(),
+        Method::DELETE,
+        "/v1/directory?path=docs",
+        &[("Authorization", &authorization)],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(response.headers().contains_key(WWW_AUTHENTICATE));
+    assert_eq!(error_code(response).await, "insufficient_scope");
+}
+
+#[tokio::test]
+async fn failed_preconditions_are_described_in_json() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=", app.address);
+    let response = app
+        .client
+        .upload_bytes(&address, "a.txt", b"a".to_vec())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = request(
+        &app,
+        Method::DELETE,
+        "/v1/file?path=a.txt",
+        &[("If-Match", "\"stale\"")],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(error_code(response).await, "precondition_failed");
+}
@@ -1,7 +1,7 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{error_code, spawn_app};
 
 #[tokio::test]
-async fn when_file_does_not_exist_returns_500() {
+async fn when_file_does_not_exist_returns_404() {
     let app = spawn_app().await;
     let file = crate::helpers::random_name(10);
     let address = format!("{}/v1/file?path={file}", app.address);
@@ -10,10 +10,8 @@ async fn when_file_does_not_exist_returns_500() {
         .delete_file(&address)
         .await
         .expect("failed to delete hello.txt");
-    assert_eq!(
-        response.status(),
-        reqwest::StatusCode::INTERNAL_SERVER_ERROR
-    );
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(error_code(response).await, "entry_not_found");
 }
 
 #[tokio::test]
@@ -26,14 +24,16 @@ async fn when_query_parameters_are_missing_returns_a_400() {
         .await
         .expect("failed to delete");
     assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_query");
     assert_eq!(
-        "Failed to deserialize query string: missing field `path`",
-        response.text().await.unwrap()
+        body["error"]["message"],
+        "Failed to deserialize query string: missing field `path`"
     );
 }
 
 #[tokio::test]
-async fn when_query_path_is_a_directory_return_400() {
+async fn when_query_path_is_forbidden_returns_400() {
     let app = spawn_app().await;
     let address = format!("{}/v1/file?path=/", app.address);
     let response = app
@@ -41,11 +41,8 @@ async fn when_query_path_is_a_directory_return_400() {
         .delete_file(&address)
         .await
         .expect("failed to send request");
-    assert_eq!(
-        response.status(),
-        reqwest::StatusCode::INTERNAL_SERVER_ERROR
-    );
-    assert_eq!("Something went wrong", response.text().await.unwrap());
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "entry_name_invalid");
 }
 
 #[tokio::test]
@@ -64,3 +61,57 @@ async fn when_request_is_wellformed_returns_200() {
         .expect("failed to send request");
     assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
 }
+
+#[tokio::test]
+async fn when_if_match_does_not_match_returns_412() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=", app.address);
+    app.client
+        .upload_files(&address, vec![("Cargo.toml", "dummy.txt")])
+        .await
+        .expect("failed to send request");
+    let address = format!("{}/v1/file?path=dummy.txt", app.address);
+    let response = app
+        .client
+        .request_with_headers(
+            reqwest::Method::DELETE,
+            &address,
+            &[("If-Match", "\"stale\"")],
+        )
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::PRECONDITION_FAILED);
+    let response = app
+        .client
+        .download_file(&address)
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn when_path_walks_out_of_the_drive_returns_400() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=../../Cargo.toml", app.address);
+    let response = app
+        .client
+        .delete_file(&address)
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "entry_name_invalid");
+}
+
+#[tokio::test]
+async fn when_query_path_is_a_directory_returns_422() {
+    let app = spawn_app().await;
+    let dir = crate::helpers::random_name(10);
+    app.client.create_dir(&app.address, &dir).await;
+    let address = format!("{}/v1/file?path={dir}", app.address);
+    let response = app
+        .client
+        .delete_file(&address)
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(error_code(response).await, "entry_unexpected_type");
+}
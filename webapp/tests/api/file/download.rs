@@ -1,7 +1,8 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{error_code, spawn_app};
+use reqwest::header;
 
 #[tokio::test]
-async fn when_file_does_not_exist_returns_500() {
+async fn when_file_does_not_exist_returns_404() {
     let app = spawn_app().await;
     let address = format!("{}/v1/file?path=hello.txt", app.address);
     let response = app
@@ -9,10 +10,8 @@ async fn when_file_does_not_exist_returns_500() {
         .download_file(&address)
         .await
         .expect("failed to send request");
-    assert_eq!(
-        response.status(),
-        reqwest::StatusCode::INTERNAL_SERVER_ERROR
-    );
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(error_code(response).await, "entry_not_found");
 }
 
 #[tokio::test]
@@ -25,14 +24,16 @@ async fn when_query_parameters_are_missing_returns_a_400() {
         .await
         .expect("failed to send request");
     assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_query");
     assert_eq!(
-        "Failed to deserialize query string: missing field `path`",
-        response.text().await.unwrap()
+        body["error"]["message"],
+        "Failed to deserialize query string: missing field `path`"
     );
 }
 
 #[tokio::test]
-async fn when_query_path_is_a_directory_return_500() {
+async fn when_query_path_is_forbidden_returns_400() {
     let app = spawn_app().await;
     let address = format!("{}/v1/file?path=/", app.address);
     let response = app
@@ -40,15 +41,140 @@ async fn when_query_path_is_a_directory_return_500() {
         .download_file(&address)
         .await
         .expect("failed to send request");
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "entry_name_invalid");
+}
+
+#[tokio::test]
+async fn when_request_is_wellformed_returns_200() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=", app.address);
+    app.client
+        .upload_files(&address, vec![("Cargo.toml", "dummy.txt")])
+        .await
+        .expect("failed to send request");
+    let address = format!("{}/v1/file?path=dummy.txt", app.address);
+    let response = app
+        .client
+        .download_file(&address)
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn when_request_is_wellformed_advertises_byte_ranges() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=", app.address);
+    app.client
+        .upload_files(&address, vec![("Cargo.toml", "dummy.txt")])
+        .await
+        .expect("failed to send request");
+    let address = format!("{}/v1/file?path=dummy.txt", app.address);
+    let response = app
+        .client
+        .download_file(&address)
+        .await
+        .expect("failed to send request");
+    let content = std::fs::read("Cargo.toml").unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()["accept-ranges"], "bytes");
+    assert_eq!(
+        response.headers()["content-length"],
+        content.len().to_string().as_str()
+    );
+}
+
+#[tokio::test]
+async fn when_range_is_requested_returns_206_with_the_range() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=", app.address);
+    app.client
+        .upload_files(&address, vec![("Cargo.toml", "dummy.txt")])
+        .await
+        .expect("failed to send request");
+    let address = format!("{}/v1/file?path=dummy.txt", app.address);
+    let content = std::fs::read("Cargo.toml").unwrap();
+
+    let response = app
+        .client
+        .request_with_headers(reqwest::Method::GET, &address, &[("Range", "bytes=2-9")])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        response.headers()["content-range"],
+        format!("bytes 2-9/{}", content.len()).as_str()
+    );
+    assert_eq!(response.bytes().await.unwrap(), content[2..10]);
+
+    let response = app
+        .client
+        .request_with_headers(reqwest::Method::GET, &address, &[("Range", "bytes=-5")])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        response.bytes().await.unwrap(),
+        content[content.len() - 5..]
+    );
+}
+
+#[tokio::test]
+async fn when_range_is_not_satisfiable_returns_416() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=", app.address);
+    app.client
+        .upload_files(&address, vec![("Cargo.toml", "dummy.txt")])
+        .await
+        .expect("failed to send request");
+    let address = format!("{}/v1/file?path=dummy.txt", app.address);
+    let content = std::fs::read("Cargo.toml").unwrap();
+
+    let response = app
+        .client
+        .request_with_headers(
+            reqwest::Method::GET,
+            &address,
+            &[("Range", "bytes=100000-")],
+        )
+        .await;
     assert_eq!(
         response.status(),
-        reqwest::StatusCode::INTERNAL_SERVER_ERROR
+        reqwest::StatusCode::RANGE_NOT_SATISFIABLE
+    );
+    assert_eq!(
+        response.headers()["content-range"],
+        format!("bytes */{}", content.len()).as_str()
     );
-    assert_eq!("Something went wrong", response.text().await.unwrap());
 }
 
 #[tokio::test]
-async fn when_request_is_wellformed_returns_200() {
+async fn when_if_range_is_stale_returns_the_whole_file() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=", app.address);
+    app.client
+        .upload_files(&address, vec![("Cargo.toml", "dummy.txt")])
+        .await
+        .expect("failed to send request");
+    let address = format!("{}/v1/file?path=dummy.txt", app.address);
+    let content = std::fs::read("Cargo.toml").unwrap();
+
+    let response = app
+        .client
+        .request_with_headers(
+            reqwest::Method::GET,
+            &address,
+            &[
+                ("Range", "bytes=2-9"),
+                ("If-Range", "Thu, 01 Jan 1970 00:00:00 GMT"),
+            ],
+        )
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.bytes().await.unwrap(), content);
+}
+
+#[tokio::test]
+async fn when_validators_match_returns_304() {
     let app = spawn_app().await;
     let address = format!("{}/v1/file?path=", app.address);
     app.client
@@ -61,5 +187,142 @@ async fn when_request_is_wellformed_returns_200() {
         .download_file(&address)
         .await
         .expect("failed to send request");
+    let etag = response.headers()["etag"].to_str().unwrap().to_owned();
+    let last_modified = response.headers()["last-modified"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    let response = app
+        .client
+        .request_with_headers(reqwest::Method::GET, &address, &[("If-None-Match", &etag)])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()["etag"], etag.as_str());
+
+    let response = app
+        .client
+        .request_with_headers(
+            reqwest::Method::GET,
+            &address,
+            &[("If-Modified-Since", &last_modified)],
+        )
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_MODIFIED);
+
+    let response = app
+        .client
+        .request_with_headers(
+            reqwest::Method::GET,
+            &address,
+            &[("If-None-Match", "\"stale\"")],
+        )
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn when_query_path_is_a_directory_returns_422() {
+    let app = spawn_app().await;
+    let dir = crate::helpers::random_name(10);
+    app.client.create_dir(&app.address, &dir).await;
+    let address = format!("{}/v1/file?path={dir}", app.address);
+    let response = app
+        .client
+        .download_file(&address)
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(error_code(response).await, "entry_unexpected_type");
+}
+
+#[tokio::test]
+async fn content_type_is_detected_from_the_extension_then_the_content() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=", app.address);
+    for (name, content, expected) in [
+        ("Cargo.toml", b"[package]".to_vec(), "text/x-toml"),
+        ("photo.png", b"not really a png".to_vec(), "image/png"),
+        ("scan", b"%PDF-1.4 ...".to_vec(), "application/pdf"),
+        ("notes", b"plain text".to_vec(), "text/plain"),
+        ("blob", vec![0, 1, 2, 3], "application/octet-stream"),
+    ] {
+        let response = app.client.upload_bytes(&address, name, content).await;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let address = format!("{}/v1/file?path={}", app.address, name);
+        let response = app.client.download_file(&address).await.unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            expected,
+            "{}",
+            name
+        );
+        assert_eq!(
+            response.headers()[header::X_CONTENT_TYPE_OPTIONS],
+            "nosniff"
+        );
+    }
+}
+
+#[tokio::test]
+async fn files_are_downloaded_inline_when_requested() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=", app.address);
+    app.client
+        .upload_bytes(&address, "a.txt", b"a".to_vec())
+        .await;
+
+    let address = format!("{}/v1/file?path=a.txt", app.address);
+    let response = app.client.download_file(&address).await.unwrap();
+    assert_eq!(
+        response.headers()[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"a.txt\""
+    );
+    let address = format!("{}/v1/file?path=a.txt&disposition=inline", app.address);
+    let response = app.client.download_file(&address).await.unwrap();
+    assert_eq!(
+        response.headers()[header::CONTENT_DISPOSITION],
+        "inline; filename=\"a.txt\""
+    );
+}
+
+#[tokio::test]
+async fn files_shown_inline_are_sandboxed() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=", app.address);
+    let page = b"<script>fetch('/v1/token', { method: 'POST' })</script>";
+    app.client
+        .upload_bytes(&address, "page.html", page.to_vec())
+        .await;
+
+    let address = format!("{}/v1/file?path=page.html&disposition=inline", app.address);
+    let response = app.client.download_file(&address).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html");
+    assert_eq!(
+        response.headers()[header::CONTENT_SECURITY_POLICY],
+        "sandbox"
+    );
+}
+
+#[tokio::test]
+async fn non_ascii_file_names_are_encoded_following_rfc_5987() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=", app.address);
+    app.client
+        .upload_bytes(&address, "résumé \"final\".txt", b"cv".to_vec())
+        .await;
+
+    let address = format!(
+        "{}/v1/file?path=r%C3%A9sum%C3%A9%20%22final%22.txt",
+        app.address
+    );
+    let response = app.client.download_file(&address).await.unwrap();
     assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"r_sum_ _final_.txt\"; \
+         filename*=UTF-8''r%C3%A9sum%C3%A9%20%22final%22.txt"
+    );
 }
@@ -0,0 +1,180 @@
+use crate::helpers::{
+    error_code, spawn_anonymous_app_with, spawn_app, TestApp, TEST_PASSWORD, TEST_USERNAME,
+};
+use async_compression::tokio::write::GzipEncoder;
+use async_zip::{base::write::ZipFileWriter, Compression, ZipEntryBuilder};
+use reqwest::StatusCode;
+use tokio::io::AsyncWriteExt;
+
+/// A zip archive of `files`, given as names and contents.
+async fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut writer = ZipFileWriter::new(Vec::new());
+    for (name, content) in files {
+        let entry = ZipEntryBuilder::new((*name).into(), Compression::Deflate);
+        writer
+            .write_entry_whole(entry, content.as_bytes())
+            .await
+            .unwrap();
+    }
+    writer.close().await.unwrap()
+}
+
+/// A tar.gz archive of `files`, given as names and contents. The names are
+/// written as is so that unsafe ones can be archived.
+async fn tar_gz(files: &[(&str, &str)]) -> Vec<u8> {
+    let entries = files
+        .iter()
+        .map(|(name, content)| {
+            let kind = if name.ends_with('/') {
+                tar::EntryType::Directory
+            } else {
+                tar::EntryType::Regular
+            };
+            (*name, kind, *content)
+        })
+        .collect::<Vec<_>>();
+    tar_gz_entries(&entries).await
+}
+
+/// A tar.gz archive of `entries`, given as names, types and contents.
+async fn tar_gz_entries(entries: &[(&str, tar::EntryType, &str)]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, kind, content) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_entry_type(*kind);
+        header.set_cksum();
+        builder.append(&header, content.as_bytes()).unwrap();
+    }
+    let archive = builder.into_inner().unwrap();
+    let mut encoder = GzipEncoder::new(Vec::new());
+    encoder.write_all(&archive).await.unwrap();
+    encoder.shutdown().await.unwrap();
+    encoder.into_inner()
+}
+
+async fn extract(app: &TestApp, path: &str, name: &str, archive: Vec<u8>) -> reqwest::Response {
+    app.client.create_dir(&app.address, path).await;
+    let address = format!("{}/v1/file?path={}&extract=true", app.address, path);
+    app.client.upload_bytes(&address, name, archive).await
+}
+
+async fn download(app: &TestApp, path: &str) -> reqwest::Response {
+    let address = format!("{}/v1/file?path={}", app.address, path);
+    app.client.download_file(&address).await.unwrap()
+}
+
+#[tokio::test]
+async fn zip_archives_are_extracted_into_the_directory() {
+    let app = spawn_app().await;
+    let archive = zip(&[("a.txt", "a"), ("nested/deeper/b.txt", "b")]).await;
+
+    let response = extract(&app, "docs", "archive.zip", archive).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = download(&app, "docs/a.txt").await;
+    assert_eq!(response.text().await.unwrap(), "a");
+    let response = download(&app, "docs/nested/deeper/b.txt").await;
+    assert_eq!(response.text().await.unwrap(), "b");
+    assert_eq!(
+        download(&app, "docs/archive.zip").await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn tar_gz_archives_are_extracted_into_the_directory() {
+    let app = spawn_app().await;
+    let archive = tar_gz(&[("./dir/", ""), ("./dir/a.txt", "a"), ("b.txt", "b")]).await;
+
+    let response = extract(&app, "docs", "archive.tar.gz", archive).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = download(&app, "docs/dir/a.txt").await;
+    assert_eq!(response.text().await.unwrap(), "a");
+    let response = download(&app, "docs/b.txt").await;
+    assert_eq!(response.text().await.unwrap(), "b");
+}
+
+#[tokio::test]
+async fn entries_escaping_the_directory_reject_the_whole_archive() {
+    let app = spawn_app().await;
+    for (name, archive) in [
+        (
+            "slip.zip",
+            zip(&[("a.txt", "a"), ("../evil.txt", "evil")]).await,
+        ),
+        (
+            "slip.tar.gz",
+            tar_gz(&[("a.txt", "a"), ("/evil.txt", "evil")]).await,
+        ),
+        (
+            "slip.zip",
+            zip(&[("a.txt", "a"), ("x/../../evil.txt", "evil")]).await,
+        ),
+    ] {
+        let response = extract(&app, "docs", name, archive).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(error_code(response).await, "unsafe_archive_entry");
+        assert_eq!(
+            download(&app, "docs/a.txt").await.status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            download(&app, "evil.txt").await.status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+}
+
+#[tokio::test]
+async fn links_are_skipped_whatever_their_name() {
+    let app = spawn_app().await;
+    let archive = tar_gz_entries(&[
+        ("a.txt", tar::EntryType::Regular, "a"),
+        ("../link", tar::EntryType::Symlink, ""),
+        ("/fifo", tar::EntryType::Fifo, ""),
+    ])
+    .await;
+
+    let response = extract(&app, "docs", "links.tar.gz", archive).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = download(&app, "docs/a.txt").await;
+    assert_eq!(response.text().await.unwrap(), "a");
+}
+
+#[tokio::test]
+async fn archives_over_the_limits_are_rejected() {
+    let app = spawn_anonymous_app_with(|settings| {
+        settings.application.extract.max_size = 8;
+        settings.application.extract.max_entries = 2;
+    })
+    .await;
+    app.login(TEST_USERNAME, TEST_PASSWORD).await;
+
+    let archive = zip(&[("a.txt", "0123456789")]).await;
+    let response = extract(&app, "docs", "big.zip", archive).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(error_code(response).await, "archive_too_large");
+
+    let archive = tar_gz(&[("a.txt", "a"), ("b.txt", "b"), ("c.txt", "c")]).await;
+    let response = extract(&app, "docs", "many.tar.gz", archive).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(error_code(response).await, "archive_too_large");
+    assert_eq!(
+        download(&app, "docs/a.txt").await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn files_that_are_not_archives_are_rejected() {
+    let app = spawn_app().await;
+    let response = extract(&app, "docs", "notes.txt", b"notes".to_vec()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "unsupported_archive");
+
+    let response = extract(&app, "docs", "broken.zip", b"not a zip".to_vec()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "invalid_archive");
+}
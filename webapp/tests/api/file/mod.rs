@@ -1,3 +1,5 @@
 mod delete;
 mod download;
+mod extract;
 mod upload;
+mod versions;
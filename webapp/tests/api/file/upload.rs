@@ -1,4 +1,6 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{
+    error_code, spawn_anonymous_app_with, spawn_app, TEST_PASSWORD, TEST_USERNAME,
+};
 
 #[tokio::test]
 async fn when_query_parameters_are_missing_returns_a_400() {
@@ -10,9 +12,11 @@ async fn when_query_parameters_are_missing_returns_a_400() {
         .await
         .expect("error sending files");
     assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_query");
     assert_eq!(
-        "Failed to deserialize query string: missing field `path`",
-        response.text().await.unwrap()
+        body["error"]["message"],
+        "Failed to deserialize query string: missing field `path`"
     );
 }
 
@@ -33,7 +37,7 @@ async fn when_multipart_is_missing_returns_400() {
 }
 
 #[tokio::test]
-async fn when_path_parameter_is_forbidden_returns_500() {
+async fn when_path_parameter_is_forbidden_returns_400() {
     let app = spawn_app().await;
     let address = format!("{}/v1/file?path=/", app.address);
     let response = app
@@ -41,11 +45,8 @@ async fn when_path_parameter_is_forbidden_returns_500() {
         .upload_files(&address, vec![("Cargo.toml", "Cargo.toml")])
         .await
         .expect("error sending files");
-    assert_eq!(
-        response.status(),
-        reqwest::StatusCode::INTERNAL_SERVER_ERROR
-    );
-    assert_eq!("Something went wrong", response.text().await.unwrap());
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "entry_name_invalid");
 }
 
 #[tokio::test]
@@ -59,3 +60,73 @@ async fn when_request_is_wellformed_returns_200() {
         .expect("error sending files");
     assert_eq!(response.status(), reqwest::StatusCode::OK);
 }
+
+#[tokio::test]
+async fn when_if_match_does_not_match_returns_412() {
+    let app = spawn_app().await;
+    let address = format!("{}/v1/file?path=", app.address);
+    app.client
+        .upload_files(&address, vec![("Cargo.toml", "dummy.txt")])
+        .await
+        .expect("error sending files");
+    let etag = app
+        .client
+        .download_file(&format!("{}/v1/file?path=dummy.txt", app.address))
+        .await
+        .expect("failed to send request")
+        .headers()["etag"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    let response = app
+        .client
+        .upload_files_with_headers(
+            &address,
+            vec![("Cargo.toml", "dummy.txt")],
+            &[("If-Match", "\"stale\"")],
+        )
+        .await
+        .expect("error sending files");
+    assert_eq!(response.status(), reqwest::StatusCode::PRECONDITION_FAILED);
+
+    let response = app
+        .client
+        .upload_files_with_headers(
+            &address,
+            vec![("Cargo.toml", "dummy.txt")],
+            &[("If-Match", &etag)],
+        )
+        .await
+        .expect("error sending files");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn identical_uploads_to_a_deduplicated_drive_download_intact() {
+    let app = spawn_anonymous_app_with(|settings| settings.application.dedup = true).await;
+    app.login(TEST_USERNAME, TEST_PASSWORD).await;
+    let content = "identical asset ".repeat(100);
+    let address = format!("{}/v1/file?path=", app.address);
+    for name in ["a.txt", "b.txt"] {
+        let response = app
+            .client
+            .upload_bytes(&address, name, content.as_bytes().to_vec())
+            .await;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+    }
+
+    for name in ["a.txt", "b.txt"] {
+        let response = app
+            .client
+            .download_file(&format!("{}/v1/file?path={}", app.address, name))
+            .await
+            .expect("failed to send request");
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response.headers()["content-length"],
+            content.len().to_string().as_str()
+        );
+        assert_eq!(response.text().await.unwrap(), content);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::helpers::{
+    error_code, spawn_anonymous_app_with, spawn_app, TestApp, TEST_PASSWORD, TEST_USERNAME,
+};
+use reqwest::{header, Method, StatusCode};
+use webapp::handlers::versions::VersionView;
+
+async fn upload(app: &TestApp, name: &str, content: &str) {
+    let address = format!("{}/v1/file?path=", app.address);
+    let response = app
+        .client
+        .upload_bytes(&address, name, content.as_bytes().to_vec())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+async fn list_versions(app: &TestApp, path: &str) -> Vec<VersionView> {
+    let address = format!("{}/v1/file/versions?path={}", app.address, path);
+    let response = app
+        .client
+        .request_with_headers(Method::GET, &address, &[])
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    serde_json::from_value(body["result"].clone()).unwrap()
+}
+
+async fn download_version(app: &TestApp, path: &str, id: &str) -> reqwest::Response {
+    let address = format!(
+        "{}/v1/file/versions/content?path={}&id={}",
+        app.address, path, id
+    );
+    app.client.download_file(&address).await.unwrap()
+}
+
+async fn download(app: &TestApp, path: &str) -> String {
+    let address = format!("{}/v1/file?path={}", app.address, path);
+    let response = app.client.download_file(&address).await.unwrap();
+    response.text().await.unwrap()
+}
+
+#[tokio::test]
+async fn overwritten_files_are_kept_as_versions() {
+    let app = spawn_app().await;
+    upload(&app, "a.txt", "first").await;
+    assert!(list_versions(&app, "a.txt").await.is_empty());
+    upload(&app, "a.txt", "second").await;
+    upload(&app, "a.txt", "third").await;
+
+    let versions = list_versions(&app, "a.txt").await;
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0].size, "second".len() as u64);
+    assert_eq!(versions[1].size, "first".len() as u64);
+    assert!(versions
+        .iter()
+        .all(|version| version.author.as_deref() == Some(TEST_USERNAME)));
+    assert_eq!(download(&app, "a.txt").await, "third");
+}
+
+#[tokio::test]
+async fn versions_can_be_downloaded() {
+    let app = spawn_app().await;
+    upload(&app, "a.txt", "first").await;
+    upload(&app, "a.txt", "second").await;
+    let versions = list_versions(&app, "a.txt").await;
+
+    let response = download_version(&app, "a.txt", &versions[0].id).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain");
+    assert_eq!(
+        response.headers()[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"a.txt\""
+    );
+    assert_eq!(response.text().await.unwrap(), "first");
+
+    let response = download_version(&app, "a.txt", "42").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(error_code(response).await, "entry_not_found");
+}
+
+#[tokio::test]
+async fn restoring_a_version_keeps_the_replaced_contents() {
+    let app = spawn_app().await;
+    upload(&app, "a.txt", "first").await;
+    upload(&app, "a.txt", "second").await;
+    let id = &list_versions(&app, "a.txt").await[0].id;
+
+    let address = format!(
+        "{}/v1/file/versions/restore?path=a.txt&id={}",
+        app.address, id
+    );
+    let response = app
+        .client
+        .request_with_headers(Method::POST, &address, &[])
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(download(&app, "a.txt").await, "first");
+
+    let versions = list_versions(&app, "a.txt").await;
+    assert_eq!(versions.len(), 2);
+    let response = download_version(&app, "a.txt", &versions[0].id).await;
+    assert_eq!(response.text().await.unwrap(), "second");
+}
+
+#[tokio::test]
+async fn only_the_configured_number_of_versions_is_kept() {
+    let app = spawn_anonymous_app_with(|settings| {
+        settings.application.versions.keep_last = 1;
+    })
+    .await;
+    app.login(TEST_USERNAME, TEST_PASSWORD).await;
+    for content in ["first", "second", "third"] {
+        upload(&app, "a.txt", content).await;
+    }
+
+    let versions = list_versions(&app, "a.txt").await;
+    assert_eq!(versions.len(), 1);
+    let response = download_version(&app, "a.txt", &versions[0].id).await;
+    assert_eq!(response.text().await.unwrap(), "second");
+}
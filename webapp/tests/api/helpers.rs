@@ -1,9 +1,11 @@
+use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
 use once_cell::sync::Lazy;
 use rand::Rng;
+use secrecy::Secret;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use webapp::configuration::get_configuration;
+use webapp::configuration::{get_configuration, Settings, UserSettings};
 use webapp::handlers::directory::DirectoryView;
 use webapp::server::Server;
 use webapp::telemetry;
@@ -18,31 +20,122 @@ static TRACING: Lazy<()> = Lazy::new(|| {
     }
 });
 
+pub const TEST_USERNAME: &str = "mibox";
+pub const TEST_PASSWORD: &str = "correct horse battery staple";
+
+/// The hash of the test user password, computed with cheap parameters to keep
+/// the tests fast.
+static TEST_PASSWORD_HASH: Lazy<String> = Lazy::new(|| {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(8, 1, 1, None).unwrap(),
+    )
+    .hash_password(TEST_PASSWORD.as_bytes(), &salt)
+    .unwrap()
+    .to_string()
+});
+
 pub struct TestApp {
     pub address: String,
     pub client: HttpClient,
 }
 
-impl TestApp {}
+/// A user logging in with the test password.
+pub fn test_user(username: &str) -> UserSettings {
+    UserSettings {
+        username: username.to_string(),
+        password_hash: Secret::new(TEST_PASSWORD_HASH.clone()),
+        groups: vec![],
+    }
+}
+
+fn http_client() -> HttpClient {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    HttpClient { inner: client }
+}
 
+impl TestApp {
+    /// The same application seen by a client with no cookies.
+    pub fn with_new_client(&self) -> TestApp {
+        TestApp {
+            address: self.address.clone(),
+            client: http_client(),
+        }
+    }
+
+    pub async fn login(&self, username: &str, password: &str) -> reqwest::Response {
+        self.client
+            .inner
+            .post(format!("{}/login", self.address))
+            .json(&serde_json::json!({ "username": username, "password": password }))
+            .send()
+            .await
+            .expect("failed to log in")
+    }
+
+    pub async fn logout(&self) -> reqwest::Response {
+        self.client
+            .inner
+            .post(format!("{}/logout", self.address))
+            .send()
+            .await
+            .expect("failed to log out")
+    }
+}
+
+/// Spawns the application and logs in as the test user.
 pub async fn spawn_app() -> TestApp {
+    let app = spawn_anonymous_app().await;
+    let response = app.login(TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    app
+}
+
+/// Spawns the application on the local drive at `drive` and logs in as the
+/// test user, spawning it again on the same drive stands for a restart.
+pub async fn spawn_app_on(drive: &Path) -> TestApp {
+    let drive = drive.to_str().unwrap().to_owned();
+    let app = spawn_anonymous_app_with(|settings| settings.application.drive = drive).await;
+    let response = app.login(TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    app
+}
+
+/// A new empty directory to keep a local drive in.
+pub fn temp_drive() -> PathBuf {
+    let drive = std::env::temp_dir().join(format!("mibox-drive-{}", random_name(10)));
+    std::fs::create_dir(&drive).unwrap();
+    drive
+}
+
+/// Spawns the application without logging in.
+pub async fn spawn_anonymous_app() -> TestApp {
+    spawn_anonymous_app_with(|_| {}).await
+}
+
+/// Spawns the application without logging in, after customizing its settings.
+pub async fn spawn_anonymous_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let mut configuration = get_configuration().expect("could not read configuration");
     configuration.application.port = rand::thread_rng().gen_range(1024..u16::MAX);
+    configuration.application.drive = "memory://".to_string();
+    configuration.application.users = vec![test_user(TEST_USERNAME)];
+    configure(&mut configuration);
     let p = rand::thread_rng().gen_range(0..500) + 100;
-    let client = reqwest::Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .cookie_store(true)
-        .build()
-        .unwrap();
     let server = Server::with_settings(configuration.clone())
         .await
         .expect("error configuring server");
     let address = format!("http://localhost:{}", server.address().port());
     let app = TestApp {
         address,
-        client: HttpClient { inner: client },
+        client: http_client(),
     };
     tokio::spawn(async move { server.serve().await.unwrap() });
     tokio::time::sleep(Duration::from_millis(p)).await;
@@ -59,8 +152,38 @@ impl HttpClient {
         address: &str,
         files: Vec<(&str, &str)>,
     ) -> anyhow::Result<reqwest::Response> {
+        self.upload_files_with_headers(address, files, &[]).await
+    }
+
+    /// Uploads `content` as a file named `name`.
+    pub async fn upload_bytes(
+        &self,
+        address: &str,
+        name: &str,
+        content: Vec<u8>,
+    ) -> reqwest::Response {
+        let part = reqwest::multipart::Part::bytes(content).file_name(name.to_owned());
+        self.inner
+            .post(address)
+            .multipart(reqwest::multipart::Form::new().part("file", part))
+            .send()
+            .await
+            .expect("error uploading file")
+    }
+
+    pub async fn upload_files_with_headers(
+        &self,
+        address: &str,
+        files: Vec<(&str, &str)>,
+        headers: &[(&str, &str)],
+    ) -> anyhow::Result<reqwest::Response> {
+        let request = headers
+            .iter()
+            .fold(self.inner.post(address), |request, (name, value)| {
+                request.header(*name, *value)
+            });
         if files.is_empty() {
-            return Ok(self.inner.post(address).send().await?);
+            return Ok(request.send().await?);
         }
 
         //create the multipart form
@@ -82,9 +205,7 @@ impl HttpClient {
         }
 
         //send request
-        let response = self
-            .inner
-            .post(address)
+        let response = request
             .multipart(form)
             .send()
             .await
@@ -102,6 +223,64 @@ impl HttpClient {
             .expect("failed to download file"))
     }
 
+    pub async fn request_with_headers(
+        &self,
+        method: reqwest::Method,
+        address: &str,
+        headers: &[(&str, &str)],
+    ) -> reqwest::Response {
+        headers
+            .iter()
+            .fold(
+                self.inner.request(method, address),
+                |request, (name, value)| request.header(*name, *value),
+            )
+            .send()
+            .await
+            .expect("failed to send request")
+    }
+
+    pub async fn request_json(
+        &self,
+        method: reqwest::Method,
+        address: &str,
+        headers: &[(&str, &str)],
+        body: serde_json::Value,
+    ) -> reqwest::Response {
+        headers
+            .iter()
+            .fold(
+                self.inner.request(method, address),
+                |request, (name, value)| request.header(*name, *value),
+            )
+            .json(&body)
+            .send()
+            .await
+            .expect("failed to send request")
+    }
+
+    /// Sends a request with a body, `method` may be any extension method such
+    /// as the WebDAV ones.
+    pub async fn request_with_body(
+        &self,
+        method: &str,
+        address: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> reqwest::Response {
+        let method = reqwest::Method::from_bytes(method.as_bytes()).unwrap();
+        headers
+            .iter()
+            .fold(
+                self.inner.request(method, address),
+                |request, (name, value)| request.header(*name, *value),
+            )
+            .body(body.to_owned())
+            .send()
+            .await
+            .expect("failed to send request")
+    }
+
     pub async fn list(&self, address: &str, path: &str) -> Vec<DirectoryView> {
         let address = format!("{}/v1/directory?path={path}", address);
         self.inner
@@ -112,7 +291,7 @@ impl HttpClient {
             .text()
             .await
             .map(|r| serde_json::from_str::<serde_json::Value>(&r).unwrap()["result"].clone())
-            .map(|r| serde_json::from_value::<Vec<DirectoryView>>(r))
+            .map(serde_json::from_value::<Vec<DirectoryView>>)
             .unwrap()
             .unwrap()
     }
@@ -164,6 +343,33 @@ impl HttpClient {
             .expect("failed to delete file")
     }
 
+    pub async fn create_share(&self, address: &str, share: serde_json::Value) -> reqwest::Response {
+        self.inner
+            .post(format!("{}/v1/share", address))
+            .json(&share)
+            .send()
+            .await
+            .expect("failed to create share")
+    }
+
+    pub async fn create_token(&self, address: &str, token: serde_json::Value) -> reqwest::Response {
+        self.inner
+            .post(format!("{}/v1/token", address))
+            .json(&token)
+            .send()
+            .await
+            .expect("failed to create token")
+    }
+
+    pub async fn download_shared(&self, url: &str, password: Option<&str>) -> reqwest::Response {
+        let request = self.inner.get(url);
+        let request = match password {
+            Some(password) => request.basic_auth("", Some(password)),
+            None => request,
+        };
+        request.send().await.expect("failed to download share")
+    }
+
     pub async fn delete_file(&self, address: &str) -> anyhow::Result<reqwest::Response> {
         Ok(self
             .inner
@@ -174,6 +380,12 @@ impl HttpClient {
     }
 }
 
+/// Returns the machine-readable code of an error response.
+pub async fn error_code(response: reqwest::Response) -> String {
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    body["error"]["code"].as_str().unwrap().to_owned()
+}
+
 pub fn random_name(len: usize) -> String {
     let chars: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789"; // characters to choose from
 
@@ -191,6 +403,6 @@ pub fn random_file(base_path: String) -> PathBuf {
     let name = random_name(10);
     let path = PathBuf::from(base_path).join(name);
     let mut file = std::fs::File::create(path.clone()).unwrap();
-    file.write(b"RANDOM CONTENT").unwrap();
-    return path;
+    file.write_all(b"RANDOM CONTENT").unwrap();
+    path
 }
@@ -0,0 +1,82 @@
+use crate::helpers::{
+    error_code, spawn_anonymous_app_with, test_user, TestApp, TEST_PASSWORD, TEST_USERNAME,
+};
+use reqwest::StatusCode;
+use webapp::{configuration::get_configuration, server::Server};
+
+async fn spawn_two_users() -> (TestApp, TestApp) {
+    let alice = spawn_anonymous_app_with(|settings| {
+        settings.application.users.push(test_user("alice"));
+    })
+    .await;
+    let mibox = alice.with_new_client();
+    assert_eq!(
+        alice.login("alice", TEST_PASSWORD).await.status(),
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        mibox.login(TEST_USERNAME, TEST_PASSWORD).await.status(),
+        StatusCode::NO_CONTENT
+    );
+    (alice, mibox)
+}
+
+#[tokio::test]
+async fn users_only_see_their_own_files() {
+    let (alice, mibox) = spawn_two_users().await;
+    let response = alice
+        .client
+        .upload_files(
+            &format!("{}/v1/file?path=", alice.address),
+            vec![("Cargo.toml", "secret.txt")],
+        )
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    assert_eq!(alice.client.list(&alice.address, "").await.len(), 1);
+    assert!(mibox.client.list(&mibox.address, "").await.is_empty());
+    let response = mibox
+        .client
+        .download_file(&format!("{}/v1/file?path=secret.txt", mibox.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn users_cannot_walk_into_other_homes() {
+    let (alice, mibox) = spawn_two_users().await;
+    alice.client.create_dir(&alice.address, "private").await;
+
+    let response = mibox
+        .client
+        .download_file(&format!("{}/v1/directory?path=../alice", mibox.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "entry_name_invalid");
+    let response = mibox
+        .client
+        .download_file(&format!(
+            "{}/v1/directory?path=alice/private",
+            mibox.address
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn usernames_which_cannot_name_a_home_are_rejected_at_startup() {
+    for username in ["../alice", "alice/bob", ".mibox", ""] {
+        let mut settings = get_configuration().unwrap();
+        settings.application.drive = "memory://".to_string();
+        settings.application.users = vec![test_user(username)];
+        assert!(
+            Server::with_settings(settings).await.is_err(),
+            "{:?} was accepted",
+            username
+        );
+    }
+}
@@ -1,4 +1,14 @@
+mod acl;
+mod dav;
 mod directory;
+mod error;
 mod file;
 mod health;
 mod helpers;
+mod home;
+mod search;
+mod session;
+mod share;
+mod token;
+mod trash;
+mod upload;
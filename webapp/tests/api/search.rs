@@ -0,0 +1,130 @@
+use crate::helpers::{spawn_app, TestApp};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use webapp::handlers::search::SearchView;
+
+async fn upload(app: &TestApp, path: &str, name: &str, content: &str) {
+    let address = format!("{}/v1/file?path={}", app.address, path);
+    let response = app
+        .client
+        .upload_bytes(&address, name, content.as_bytes().to_vec())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+async fn search(app: &TestApp, query: &str) -> reqwest::Response {
+    let address = format!("{}/v1/search?{}", app.address, query);
+    app.client
+        .request_with_headers(Method::GET, &address, &[])
+        .await
+}
+
+async fn results(app: &TestApp, query: &str) -> (Vec<SearchView>, u64) {
+    let response = search(app, query).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.json::<Value>().await.unwrap();
+    let views = serde_json::from_value(body["result"].clone()).unwrap();
+    (views, body["total"].as_u64().unwrap())
+}
+
+fn paths(views: &[SearchView]) -> Vec<&str> {
+    views.iter().map(|view| view.path.as_str()).collect()
+}
+
+async fn populate(app: &TestApp) {
+    app.client.create_dir(&app.address, "docs").await;
+    app.client.create_dir(&app.address, "docs/notes").await;
+    upload(app, "", "notes.txt", "top level notes").await;
+    upload(app, "docs", "meeting-notes.md", "minutes").await;
+    upload(app, "docs/notes", "todo.txt", "buy milk").await;
+}
+
+#[tokio::test]
+async fn files_are_found_by_name_best_matches_first() {
+    let app = spawn_app().await;
+    populate(&app).await;
+
+    let (views, total) = results(&app, "q=notes").await;
+    assert_eq!(total, 3);
+    assert_eq!(
+        paths(&views),
+        ["docs/notes", "notes.txt", "docs/meeting-notes.md"]
+    );
+    assert!(views[0].is_directory);
+    assert_eq!(views[1].size, 15);
+    assert_eq!(views[1].mime_type.as_deref(), Some("text/plain"));
+    assert!(views[0].score > views[2].score);
+
+    let (views, _) = results(&app, "q=*.txt&path=docs").await;
+    assert_eq!(paths(&views), ["docs/notes/todo.txt"]);
+    let (views, _) = results(&app, "q=notes&type=file&min_size=10").await;
+    assert_eq!(paths(&views), ["notes.txt"]);
+    let (views, _) = results(&app, "modified_after=2000-01-01T00:00:00Z&type=directory").await;
+    assert_eq!(paths(&views), ["docs", "docs/notes"]);
+    let (views, total) = results(&app, "modified_before=2000-01-01T00:00:00Z").await;
+    assert!(views.is_empty());
+    assert_eq!(total, 0);
+}
+
+#[tokio::test]
+async fn results_are_paginated() {
+    let app = spawn_app().await;
+    populate(&app).await;
+
+    let (first, total) = results(&app, "limit=2").await;
+    assert_eq!(total, 5);
+    assert_eq!(first.len(), 2);
+    let (second, _) = results(&app, "limit=2&offset=2").await;
+    assert_eq!(second.len(), 2);
+    let (last, _) = results(&app, "limit=2&offset=4").await;
+    assert_eq!(last.len(), 1);
+    let mut all = [paths(&first), paths(&second), paths(&last)].concat();
+    all.sort();
+    all.dedup();
+    assert_eq!(all.len(), 5);
+}
+
+#[tokio::test]
+async fn invalid_searches_are_rejected() {
+    let app = spawn_app().await;
+    populate(&app).await;
+
+    for query in ["min_size=10&max_size=1", "limit=0", "type=link"] {
+        let response = search(&app, query).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+    let response = search(&app, "path=notes.txt").await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = search(&app, "path=missing").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn tokens_only_find_entries_within_their_path_prefix() {
+    let app = spawn_app().await;
+    populate(&app).await;
+    let response = app
+        .client
+        .create_token(
+            &app.address,
+            json!({ "name": "docs", "scope": "read", "path_prefix": "docs" }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = response.json::<Value>().await.unwrap();
+    let token = body["result"]["token"].as_str().unwrap();
+
+    let response = app
+        .with_new_client()
+        .client
+        .request_with_headers(
+            Method::GET,
+            &format!("{}/v1/search?q=notes", app.address),
+            &[("Authorization", &format!("Bearer {}", token))],
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.json::<Value>().await.unwrap();
+    let views: Vec<SearchView> = serde_json::from_value(body["result"].clone()).unwrap();
+    assert_eq!(paths(&views), ["docs/notes", "docs/meeting-notes.md"]);
+}
@@ -0,0 +1,94 @@
+use crate::helpers::{
+    spawn_anonymous_app, spawn_anonymous_app_with, spawn_app, TEST_PASSWORD, TEST_USERNAME,
+};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+#[tokio::test]
+async fn api_requires_a_session() {
+    let app = spawn_anonymous_app().await;
+    let response = app
+        .client
+        .download_file(&format!("{}/v1/directory?path=", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn health_check_does_not_require_a_session() {
+    let app = spawn_anonymous_app().await;
+    let response = app
+        .client
+        .download_file(&format!("{}/health_check", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn login_opens_a_session() {
+    let app = spawn_anonymous_app().await;
+    let response = app.login(TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let cookie = response
+        .headers()
+        .get(reqwest::header::SET_COOKIE)
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(cookie.contains("HttpOnly"));
+    let response = app
+        .client
+        .download_file(&format!("{}/v1/directory?path=", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn login_fails_with_a_wrong_password() {
+    let app = spawn_anonymous_app().await;
+    let response = app.login(TEST_USERNAME, "wrong password").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = app
+        .client
+        .download_file(&format!("{}/v1/directory?path=", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn login_fails_for_an_unknown_user() {
+    let app = spawn_anonymous_app().await;
+    let response = app.login("unknown", TEST_PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn logout_closes_the_session() {
+    let app = spawn_app().await;
+    let response = app.logout().await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = app
+        .client
+        .download_file(&format!("{}/v1/directory?path=", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn sessions_expire() {
+    let app = spawn_anonymous_app_with(|settings| settings.application.session_ttl = 1).await;
+    let response = app.login(TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let response = app
+        .client
+        .download_file(&format!("{}/v1/directory?path=", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,242 @@
+use crate::helpers::{
+    error_code, spawn_anonymous_app, spawn_app, spawn_app_on, temp_drive, TestApp,
+};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Uploads `Cargo.toml` as `path` and shares it, returning the share.
+async fn share_file(app: &TestApp, path: &str, share: Value) -> Value {
+    let address = format!("{}/v1/file?path={}", app.address, path);
+    let response = app
+        .client
+        .upload_files(&address, vec![("Cargo.toml", "Cargo.toml")])
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    let response = app.client.create_share(&app.address, share).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    response.json::<Value>().await.unwrap()["result"].clone()
+}
+
+fn share_url(app: &TestApp, share: &Value) -> String {
+    format!("{}/s/{}", app.address, share["token"].as_str().unwrap())
+}
+
+#[tokio::test]
+async fn shared_files_can_be_downloaded_anonymously() {
+    let app = spawn_app().await;
+    let share = share_file(&app, "", json!({ "path": "Cargo.toml" })).await;
+    assert_eq!(share["password_protected"], false);
+    assert!(share["url"]
+        .as_str()
+        .unwrap()
+        .ends_with(share["token"].as_str().unwrap()));
+
+    let anonymous = app.with_new_client();
+    let response = anonymous
+        .client
+        .download_shared(&share_url(&app, &share), None)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.text().await.unwrap(),
+        std::fs::read_to_string("Cargo.toml").unwrap()
+    );
+
+    let response = app
+        .client
+        .download_file(&format!("{}/v1/share", app.address))
+        .await
+        .unwrap();
+    let shares = response.json::<Value>().await.unwrap()["result"].clone();
+    assert_eq!(shares.as_array().unwrap().len(), 1);
+    assert_eq!(shares[0]["downloads"], 1);
+}
+
+#[tokio::test]
+async fn shares_require_a_session_to_be_created() {
+    let app = spawn_anonymous_app().await;
+    let response = app
+        .client
+        .create_share(&app.address, json!({ "path": "Cargo.toml" }))
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn sharing_a_missing_entry_returns_a_404() {
+    let app = spawn_app().await;
+    let response = app
+        .client
+        .create_share(&app.address, json!({ "path": "missing.txt" }))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(error_code(response).await, "entry_not_found");
+}
+
+#[tokio::test]
+async fn shares_stop_working_after_their_download_limit() {
+    let app = spawn_app().await;
+    let share = share_file(
+        &app,
+        "",
+        json!({ "path": "Cargo.toml", "max_downloads": 1 }),
+    )
+    .await;
+    let url = share_url(&app, &share);
+    let response = app.client.download_shared(&url, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.client.download_shared(&url, None).await;
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert_eq!(error_code(response).await, "share_exhausted");
+}
+
+#[tokio::test]
+async fn password_protected_shares_require_the_password() {
+    let app = spawn_app().await;
+    let share = share_file(
+        &app,
+        "",
+        json!({ "path": "Cargo.toml", "password": "open sesame" }),
+    )
+    .await;
+    assert_eq!(share["password_protected"], true);
+    let url = share_url(&app, &share);
+    let response = app.client.download_shared(&url, None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = app.client.download_shared(&url, Some("wrong")).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = app.client.download_shared(&url, Some("open sesame")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn revoked_shares_stop_working() {
+    let app = spawn_app().await;
+    let share = share_file(&app, "", json!({ "path": "Cargo.toml" })).await;
+    let response = app
+        .client
+        .request_with_headers(
+            reqwest::Method::DELETE,
+            &format!(
+                "{}/v1/share?id={}",
+                app.address,
+                share["id"].as_str().unwrap()
+            ),
+            &[],
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = app
+        .client
+        .download_shared(&share_url(&app, &share), None)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(error_code(response).await, "share_not_found");
+}
+
+#[tokio::test]
+async fn tampered_tokens_are_rejected() {
+    let app = spawn_app().await;
+    let share = share_file(&app, "", json!({ "path": "Cargo.toml" })).await;
+    let token = share["token"].as_str().unwrap();
+    let (payload, _) = token.rsplit_once('.').unwrap();
+    let (id, _) = payload.split_once('.').unwrap();
+    let forged = format!("{}/s/{}.{}.{}", app.address, id, u32::MAX, "00".repeat(32));
+    let response = app.client.download_shared(&forged, None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(error_code(response).await, "share_not_found");
+}
+
+#[tokio::test]
+async fn shares_expire() {
+    let app = spawn_app().await;
+    let share = share_file(&app, "", json!({ "path": "Cargo.toml", "expires_in": 1 })).await;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let response = app
+        .client
+        .download_shared(&share_url(&app, &share), None)
+        .await;
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert_eq!(error_code(response).await, "share_expired");
+}
+
+#[tokio::test]
+async fn shared_directories_expose_only_their_contents() {
+    let app = spawn_app().await;
+    app.client.create_dir(&app.address, "public").await;
+    app.client.create_dir(&app.address, "private").await;
+    let share = share_file(&app, "public", json!({ "path": "public" })).await;
+    let url = share_url(&app, &share);
+
+    let response = app.client.download_shared(&url, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let entries = response.json::<Value>().await.unwrap()["result"].clone();
+    assert_eq!(entries[0]["path"], "Cargo.toml");
+    let response = app
+        .client
+        .download_shared(&format!("{}?path=Cargo.toml", url), None)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app
+        .client
+        .download_shared(&format!("{}?path=../private", url), None)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn shares_outlive_a_restart() {
+    let drive = temp_drive();
+    let app = spawn_app_on(&drive).await;
+    let share = share_file(
+        &app,
+        "",
+        json!({ "path": "Cargo.toml", "max_downloads": 2, "password": "secret" }),
+    )
+    .await;
+    let anonymous = app.with_new_client();
+    let response = anonymous
+        .client
+        .download_shared(&share_url(&app, &share), Some("secret"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let restarted = spawn_app_on(&drive).await;
+    let anonymous = restarted.with_new_client();
+    let response = anonymous
+        .client
+        .download_shared(&share_url(&restarted, &share), None)
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = anonymous
+        .client
+        .download_shared(&share_url(&restarted, &share), Some("secret"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = anonymous
+        .client
+        .download_shared(&share_url(&restarted, &share), Some("secret"))
+        .await;
+    assert_eq!(response.status(), StatusCode::GONE);
+
+    let response = restarted
+        .client
+        .delete_file(&format!(
+            "{}/v1/share?id={}",
+            restarted.address,
+            share["id"].as_str().unwrap()
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let restarted = spawn_app_on(&drive).await;
+    let response = restarted
+        .client
+        .download_shared(&share_url(&restarted, &share), Some("secret"))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let _ = std::fs::remove_dir_all(drive);
+}
@@ -0,0 +1,203 @@
+use crate::helpers::{spawn_app, spawn_app_on, temp_drive, TestApp};
+use reqwest::{header::WWW_AUTHENTICATE, Method, StatusCode};
+use serde_json::{json, Value};
+
+/// Creates a token through the session of `app` and returns it.
+async fn create_token(app: &TestApp, token: Value) -> String {
+    let response = app.client.create_token(&app.address, token).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = response.json::<Value>().await.unwrap();
+    body["result"]["token"].as_str().unwrap().to_owned()
+}
+
+/// Sends a request authenticated with `token` from a client without session.
+async fn bearer(app: &TestApp, method: Method, path: &str, token: &str) -> reqwest::Response {
+    app.with_new_client()
+        .client
+        .request_with_headers(
+            method,
+            &format!("{}{}", app.address, path),
+            &[("Authorization", &format!("Bearer {}", token))],
+        )
+        .await
+}
+
+#[tokio::test]
+async fn tokens_authenticate_requests() {
+    let app = spawn_app().await;
+    let token = create_token(&app, json!({ "name": "ci", "scope": "write" })).await;
+    assert!(token.starts_with("mibox_"));
+
+    let response = bearer(&app, Method::GET, "/v1/directory?path=", &token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = bearer(&app, Method::POST, "/v1/directory?path=builds", &token).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = app
+        .client
+        .download_file(&format!("{}/v1/token", app.address))
+        .await
+        .unwrap();
+    let tokens = response.json::<Value>().await.unwrap()["result"].clone();
+    assert_eq!(tokens.as_array().unwrap().len(), 1);
+    assert_eq!(tokens[0]["name"], "ci");
+    assert_eq!(tokens[0]["scope"], "write");
+    assert!(tokens[0].get("token").is_none());
+}
+
+#[tokio::test]
+async fn unknown_tokens_are_rejected() {
+    let app = spawn_app().await;
+    let response = bearer(&app, Method::GET, "/v1/directory?path=", "mibox_forged").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let challenge = response.headers()[WWW_AUTHENTICATE].to_str().unwrap();
+    assert!(challenge.contains(r#"error="invalid_token""#));
+}
+
+#[tokio::test]
+async fn read_tokens_cannot_modify_the_drive() {
+    let app = spawn_app().await;
+    app.client.create_dir(&app.address, "docs").await;
+    let token = create_token(&app, json!({ "name": "backup", "scope": "read" })).await;
+
+    let response = bearer(&app, Method::GET, "/v1/directory?path=docs", &token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = bearer(&app, Method::DELETE, "/v1/directory?path=docs", &token).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let challenge = response.headers()[WWW_AUTHENTICATE].to_str().unwrap();
+    assert!(challenge.contains(r#"error="insufficient_scope""#));
+}
+
+#[tokio::test]
+async fn tokens_are_restricted_to_their_path_prefix() {
+    let app = spawn_app().await;
+    app.client.create_dir(&app.address, "builds").await;
+    app.client.create_dir(&app.address, "private").await;
+    let token = create_token(
+        &app,
+        json!({ "name": "ci", "scope": "write", "path_prefix": "builds" }),
+    )
+    .await;
+
+    let response = bearer(&app, Method::POST, "/v1/directory?path=builds/1", &token).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = bearer(&app, Method::GET, "/v1/directory?path=private", &token).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = bearer(&app, Method::GET, "/v1/directory?path=", &token).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = bearer(
+        &app,
+        Method::PUT,
+        "/v1/directory?from=builds/1&to=private/1",
+        &token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn tokens_cannot_grant_more_than_they_have() {
+    let app = spawn_app().await;
+    let write = create_token(&app, json!({ "name": "ci", "scope": "write" })).await;
+    let admin = create_token(
+        &app,
+        json!({ "name": "admin", "scope": "admin", "path_prefix": "builds" }),
+    )
+    .await;
+
+    let response = bearer(&app, Method::GET, "/v1/token", &write).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let client = app.with_new_client().client;
+    let address = format!("{}/v1/token", app.address);
+    let authorization = format!("Bearer {}", admin);
+    let headers = [("Authorization", authorization.as_str())];
+    let response = client
+        .request_json(
+            Method::POST,
+            &address,
+            &headers,
+            json!({ "name": "escape", "scope": "read" }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client
+        .request_json(
+            Method::POST,
+            &address,
+            &headers,
+            json!({ "name": "nested", "scope": "read", "path_prefix": "builds/1" }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn revoked_tokens_stop_working() {
+    let app = spawn_app().await;
+    let token = create_token(&app, json!({ "name": "ci", "scope": "read" })).await;
+    let response = app
+        .client
+        .download_file(&format!("{}/v1/token", app.address))
+        .await
+        .unwrap();
+    let tokens = response.json::<Value>().await.unwrap()["result"].clone();
+    let id = tokens[0]["id"].as_str().unwrap();
+
+    let path = format!("{}/v1/token?id={}", app.address, id);
+    let response = app
+        .client
+        .request_with_headers(Method::DELETE, &path, &[])
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = app
+        .client
+        .request_with_headers(Method::DELETE, &path, &[])
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = bearer(&app, Method::GET, "/v1/directory?path=", &token).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn tokens_outlive_a_restart_without_being_stored() {
+    let drive = temp_drive();
+    let app = spawn_app_on(&drive).await;
+    let token = create_token(
+        &app,
+        json!({ "name": "ci", "scope": "read", "path_prefix": "builds" }),
+    )
+    .await;
+    let revoked = create_token(&app, json!({ "name": "old", "scope": "read" })).await;
+    let response = app
+        .client
+        .download_file(&format!("{}/v1/token", app.address))
+        .await
+        .unwrap();
+    let tokens = response.json::<Value>().await.unwrap()["result"].clone();
+    let path = format!(
+        "{}/v1/token?id={}",
+        app.address,
+        tokens[1]["id"].as_str().unwrap()
+    );
+    let response = app
+        .client
+        .request_with_headers(Method::DELETE, &path, &[])
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let restarted = spawn_app_on(&drive).await;
+    let response = bearer(&restarted, Method::GET, "/v1/directory?path=builds", &token).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = bearer(&restarted, Method::GET, "/v1/directory?path=", &token).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = bearer(&restarted, Method::GET, "/v1/directory?path=", &revoked).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Only the digest of the token is kept.
+    let records = drive.join(".mibox/records/tokens");
+    let records = std::fs::read_dir(records).unwrap().collect::<Vec<_>>();
+    assert_eq!(records.len(), 1);
+    let record = std::fs::read_to_string(records[0].as_ref().unwrap().path()).unwrap();
+    assert!(!record.contains(&token[6..]));
+    let _ = std::fs::remove_dir_all(drive);
+}
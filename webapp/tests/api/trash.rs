@@ -0,0 +1,139 @@
+use crate::helpers::{error_code, spawn_app, TestApp};
+use reqwest::{Method, StatusCode};
+use webapp::handlers::trash::TrashView;
+
+async fn upload(app: &TestApp, path: &str, name: &str, content: &str) {
+    let address = format!("{}/v1/file?path={}", app.address, path);
+    let response = app
+        .client
+        .upload_bytes(&address, name, content.as_bytes().to_vec())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+async fn list_trash(app: &TestApp) -> Vec<TrashView> {
+    let address = format!("{}/v1/trash", app.address);
+    let response = app
+        .client
+        .request_with_headers(Method::GET, &address, &[])
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    serde_json::from_value(body["result"].clone()).unwrap()
+}
+
+async fn restore(app: &TestApp, query: &str) -> reqwest::Response {
+    let address = format!("{}/v1/trash/restore?{}", app.address, query);
+    app.client
+        .request_with_headers(Method::POST, &address, &[])
+        .await
+}
+
+async fn download(app: &TestApp, path: &str) -> reqwest::Response {
+    let address = format!("{}/v1/file?path={}", app.address, path);
+    app.client.download_file(&address).await.unwrap()
+}
+
+#[tokio::test]
+async fn deleted_entries_are_moved_to_the_trash() {
+    let app = spawn_app().await;
+    app.client.create_dir(&app.address, "docs").await;
+    upload(&app, "docs", "a.txt", "a").await;
+
+    let address = format!("{}/v1/file?path=docs/a.txt", app.address);
+    let response = app.client.delete_file(&address).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = app.client.delete_dir(&app.address, "docs").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let trash = list_trash(&app).await;
+    assert_eq!(trash.len(), 2);
+    assert_eq!(trash[0].path, "docs");
+    assert!(trash[0].is_directory);
+    assert_eq!(trash[1].path, "docs/a.txt");
+    assert!(!trash[1].is_directory);
+    assert_eq!(trash[1].size, 1);
+    assert!(app.client.list(&app.address, "").await.is_empty());
+}
+
+#[tokio::test]
+async fn restored_entries_are_renamed_when_their_path_was_taken() {
+    let app = spawn_app().await;
+    upload(&app, "", "a.txt", "old").await;
+    app.client
+        .delete_file(&format!("{}/v1/file?path=a.txt", app.address))
+        .await
+        .unwrap();
+    upload(&app, "", "a.txt", "new").await;
+    let id = &list_trash(&app).await[0].id;
+
+    let response = restore(&app, &format!("id={}", id)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["result"]["path"], "a (1).txt");
+    let response = download(&app, "a%20(1).txt").await;
+    assert_eq!(response.text().await.unwrap(), "old");
+    let response = download(&app, "a.txt").await;
+    assert_eq!(response.text().await.unwrap(), "new");
+    assert!(list_trash(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn entries_can_be_restored_elsewhere() {
+    let app = spawn_app().await;
+    upload(&app, "", "a.txt", "a").await;
+    app.client
+        .delete_file(&format!("{}/v1/file?path=a.txt", app.address))
+        .await
+        .unwrap();
+    let id = &list_trash(&app).await[0].id;
+
+    let response = restore(&app, &format!("id={}&path=restored/b.txt", id)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = download(&app, "restored/b.txt").await;
+    assert_eq!(response.text().await.unwrap(), "a");
+}
+
+#[tokio::test]
+async fn purged_entries_cannot_be_restored() {
+    let app = spawn_app().await;
+    for name in ["a.txt", "b.txt"] {
+        upload(&app, "", name, name).await;
+        let address = format!("{}/v1/file?path={}", app.address, name);
+        app.client.delete_file(&address).await.unwrap();
+    }
+    let trash = list_trash(&app).await;
+
+    let address = format!("{}/v1/trash/item?id={}", app.address, trash[0].id);
+    let response = app
+        .client
+        .request_with_headers(Method::DELETE, &address, &[])
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = restore(&app, &format!("id={}", trash[0].id)).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(error_code(response).await, "entry_not_found");
+    assert_eq!(list_trash(&app).await.len(), 1);
+
+    let address = format!("{}/v1/trash", app.address);
+    let response = app
+        .client
+        .request_with_headers(Method::DELETE, &address, &[])
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(list_trash(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn the_trash_cannot_be_reached_as_a_directory() {
+    let app = spawn_app().await;
+    upload(&app, "", "a.txt", "a").await;
+    app.client
+        .delete_file(&format!("{}/v1/file?path=a.txt", app.address))
+        .await
+        .unwrap();
+
+    let response = download(&app, ".mibox/trash").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(error_code(response).await, "entry_name_invalid");
+}
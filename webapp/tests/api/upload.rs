@@ -0,0 +1,229 @@
+use crate::helpers::{error_code, spawn_app, spawn_app_on, temp_drive, TestApp};
+use reqwest::StatusCode;
+
+/// `Upload-Metadata` naming the uploaded file `notes.txt`.
+const NOTES_METADATA: &str = "filename bm90ZXMudHh0,filetype dGV4dC9wbGFpbg==";
+
+impl TestApp {
+    async fn tus(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> reqwest::Response {
+        let headers = [&[("Tus-Resumable", "1.0.0")], headers].concat();
+        self.client
+            .request_with_body(method, &format!("{}{}", self.address, path), &headers, body)
+            .await
+    }
+
+    /// Creates an upload of `length` bytes to `notes.txt`, returning its url.
+    async fn create_upload(&self, length: u64) -> String {
+        let response = self
+            .tus(
+                "POST",
+                "/v1/upload?path=",
+                &[
+                    ("Upload-Length", &length.to_string()),
+                    ("Upload-Metadata", NOTES_METADATA),
+                ],
+                "",
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["tus-resumable"], "1.0.0");
+        assert_eq!(response.headers()["upload-offset"], "0");
+        response.headers()["location"].to_str().unwrap().to_owned()
+    }
+
+    async fn patch_upload(&self, location: &str, offset: u64, chunk: &str) -> reqwest::Response {
+        let offset = offset.to_string();
+        let headers = [
+            ("Upload-Offset", offset.as_str()),
+            ("Content-Type", "application/offset+octet-stream"),
+        ];
+        self.tus("PATCH", location, &headers, chunk).await
+    }
+}
+
+#[tokio::test]
+async fn options_advertises_the_tus_extensions() {
+    let app = spawn_app().await;
+    let response = app.tus("OPTIONS", "/v1/upload", &[], "").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(response.headers()["tus-version"], "1.0.0");
+    let extensions = response.headers()["tus-extension"].to_str().unwrap();
+    assert!(extensions.contains("creation"));
+    assert!(extensions.contains("termination"));
+    assert!(extensions.contains("expiration"));
+}
+
+#[tokio::test]
+async fn uploads_resume_from_their_offset() {
+    let app = spawn_app().await;
+    let location = app.create_upload(11).await;
+
+    let response = app.patch_upload(&location, 0, "hello").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(response.headers()["upload-offset"], "5");
+    assert!(response.headers().contains_key("upload-expires"));
+
+    let response = app.tus("HEAD", &location, &[], "").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["upload-offset"], "5");
+    assert_eq!(response.headers()["upload-length"], "11");
+    assert_eq!(response.headers()["upload-metadata"], NOTES_METADATA);
+    assert_eq!(response.headers()["cache-control"], "no-store");
+
+    let response = app.patch_upload(&location, 3, "lo world").await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(error_code(response).await, "offset_mismatch");
+
+    let response = app.patch_upload(&location, 5, " world").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(response.headers()["upload-offset"], "11");
+
+    let response = app
+        .client
+        .download_file(&format!("{}/v1/file?path=notes.txt", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), "hello world");
+    let response = app.tus("HEAD", &location, &[], "").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn uploads_can_start_with_their_creation() {
+    let app = spawn_app().await;
+    app.client.create_dir(&app.address, "docs").await;
+    let response = app
+        .tus(
+            "POST",
+            "/v1/upload?path=docs",
+            &[
+                ("Upload-Length", "5"),
+                ("Upload-Metadata", NOTES_METADATA),
+                ("Content-Type", "application/offset+octet-stream"),
+            ],
+            "hello",
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.headers()["upload-offset"], "5");
+
+    let response = app
+        .client
+        .download_file(&format!("{}/v1/file?path=docs/notes.txt", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), "hello");
+}
+
+#[tokio::test]
+async fn uploads_can_be_terminated() {
+    let app = spawn_app().await;
+    let location = app.create_upload(11).await;
+    app.patch_upload(&location, 0, "hello").await;
+
+    let response = app.tus("DELETE", &location, &[], "").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = app.tus("HEAD", &location, &[], "").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = app.patch_upload(&location, 5, " world").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn uploads_are_private_to_their_user() {
+    let app = spawn_app().await;
+    let location = app.create_upload(11).await;
+    let anonymous = app.with_new_client();
+    let response = anonymous.tus("HEAD", &location, &[], "").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn invalid_uploads_are_rejected() {
+    let app = spawn_app().await;
+    let response = app
+        .client
+        .request_with_body(
+            "POST",
+            &format!("{}/v1/upload?path=", app.address),
+            &[("Upload-Length", "5"), ("Upload-Metadata", NOTES_METADATA)],
+            "",
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(response.headers()["tus-version"], "1.0.0");
+
+    let too_large = (u64::MAX / 2).to_string();
+    let response = app
+        .tus(
+            "POST",
+            "/v1/upload?path=",
+            &[
+                ("Upload-Length", &too_large),
+                ("Upload-Metadata", NOTES_METADATA),
+            ],
+            "",
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // `../notes.txt`
+    let response = app
+        .tus(
+            "POST",
+            "/v1/upload?path=",
+            &[
+                ("Upload-Length", "5"),
+                ("Upload-Metadata", "filename Li4vbm90ZXMudHh0"),
+            ],
+            "",
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let location = app.create_upload(5).await;
+    let response = app.patch_upload(&location, 0, "hello world").await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let response = app
+        .tus("PATCH", &location, &[("Upload-Offset", "0")], "hello")
+        .await;
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn uploads_are_staged_within_their_local_drive() {
+    let drive = temp_drive();
+    let app = spawn_app_on(&drive).await;
+    let other = temp_drive();
+    spawn_app_on(&other).await;
+    let location = app.create_upload(11).await;
+    let response = app.patch_upload(&location, 0, "hello").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let id = location.rsplit('/').next().unwrap();
+    let staged = drive.join(".mibox/uploads").join(id);
+    assert_eq!(std::fs::read_to_string(staged).unwrap(), "hello");
+    assert!(!other.join(".mibox/uploads").join(id).exists());
+}
+
+#[tokio::test]
+async fn files_staged_by_a_previous_run_are_removed() {
+    let drive = temp_drive();
+    let app = spawn_app_on(&drive).await;
+    let location = app.create_upload(11).await;
+    let response = app.patch_upload(&location, 0, "hello").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let id = location.rsplit('/').next().unwrap();
+    let staged = drive.join(".mibox/uploads").join(id);
+    assert!(staged.exists());
+
+    spawn_app_on(&drive).await;
+    assert!(!staged.exists());
+    let _ = std::fs::remove_dir_all(drive);
+}